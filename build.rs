@@ -0,0 +1,134 @@
+//! Generates the block state tables in `src/block.rs` and the entity type table in
+//! `src/entity.rs` from the data files in `data/`.
+
+use std::{env, fmt::Write as _, fs, path::Path};
+
+const BLOCKS: &str = "data/blocks-1.21.9.txt";
+const ENTITY_TYPES: &str = "data/entity_types-1.21.9.txt";
+
+struct Property {
+    name: String,
+    values: Vec<String>,
+    default: usize,
+}
+
+fn parse_property(spec: &str, line: usize) -> Property {
+    let fail = |msg: &str| -> ! { panic!("{BLOCKS}:{line}: {msg} in `{spec}`") };
+    let (name, rest) = spec.split_once('=').unwrap_or_else(|| fail("missing `=`"));
+    let (values, default) = rest
+        .split_once('@')
+        .unwrap_or_else(|| fail("missing `@default`"));
+    let values: Vec<String> = match values.split_once("..") {
+        Some((lo, hi)) => {
+            let lo: u32 = lo.parse().unwrap_or_else(|_| fail("bad range"));
+            let hi: u32 = hi.parse().unwrap_or_else(|_| fail("bad range"));
+            (lo..=hi).map(|v| v.to_string()).collect()
+        }
+        None => values.split('|').map(str::to_owned).collect(),
+    };
+    let default = values
+        .iter()
+        .position(|v| v == default)
+        .unwrap_or_else(|| fail("default is not one of the values"));
+    Property {
+        name: name.to_owned(),
+        values,
+        default,
+    }
+}
+
+fn shape(spec: &str, line: usize) -> String {
+    match spec {
+        "full" => "BlockShape::Full".to_owned(),
+        "empty" => "BlockShape::Empty".to_owned(),
+        "liquid" => "BlockShape::Liquid".to_owned(),
+        _ => match spec.strip_prefix("height:").map(str::parse::<u8>) {
+            Some(Ok(height)) if (1..=16).contains(&height) => {
+                format!("BlockShape::Height({height})")
+            }
+            _ => panic!("{BLOCKS}:{line}: unknown shape `{spec}`"),
+        },
+    }
+}
+
+fn entity_types() {
+    println!("cargo:rerun-if-changed={ENTITY_TYPES}");
+    let data = fs::read_to_string(ENTITY_TYPES).unwrap();
+
+    let mut out = String::new();
+    writeln!(out, "// generated by build.rs from {ENTITY_TYPES}").unwrap();
+    writeln!(out, "static ENTITY_TYPES: &[&str] = &[").unwrap();
+    for (index, text) in data.lines().enumerate() {
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if text.contains(char::is_whitespace) {
+            panic!("{ENTITY_TYPES}:{}: expected a single name", index + 1);
+        }
+        writeln!(out, "    {text:?},").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("entity_types.rs");
+    fs::write(path, out).unwrap();
+}
+
+fn main() {
+    entity_types();
+
+    println!("cargo:rerun-if-changed={BLOCKS}");
+    let data = fs::read_to_string(BLOCKS).unwrap();
+
+    let mut out = String::new();
+    writeln!(out, "// generated by build.rs from {BLOCKS}").unwrap();
+    writeln!(out, "static BLOCKS: &[BlockInfo] = &[").unwrap();
+    let mut next_state = 0u32;
+    for (index, text) in data.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (name, rest) = words.split_first().unwrap();
+        let (shape_spec, properties) = rest
+            .split_last()
+            .unwrap_or_else(|| panic!("{BLOCKS}:{line}: missing shape"));
+        let mut properties: Vec<Property> = properties
+            .iter()
+            .map(|spec| parse_property(spec, line))
+            .collect();
+        properties.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // the last property varies fastest
+        let mut default_offset = 0;
+        let mut state_count = 1;
+        for property in properties.iter().rev() {
+            default_offset += property.default as u32 * state_count;
+            state_count *= property.values.len() as u32;
+        }
+
+        write!(
+            out,
+            "    BlockInfo {{ name: {name:?}, first_state: {next_state}, default_state: {}, shape: {}, properties: &[",
+            next_state + default_offset,
+            shape(shape_spec, line)
+        )
+        .unwrap();
+        for property in &properties {
+            write!(
+                out,
+                "Property {{ name: {:?}, values: &{:?} }}, ",
+                property.name, property.values
+            )
+            .unwrap();
+        }
+        writeln!(out, "] }},").unwrap();
+        next_state += state_count;
+    }
+    writeln!(out, "];").unwrap();
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("blocks.rs");
+    fs::write(path, out).unwrap();
+}
@@ -2,8 +2,9 @@ use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{format_ident, quote, ToTokens};
 use syn::{
-    parse_macro_input, AngleBracketedGenericArguments, Data, DataEnum, DataStruct, DeriveInput,
-    Expr, Fields, GenericArgument, Ident, Member, Path, PathArguments, PathSegment, Type, TypePath,
+    parse_macro_input, parse_quote, AngleBracketedGenericArguments, Data, DataEnum, DataStruct,
+    DeriveInput, Expr, Fields, GenericArgument, Ident, Lifetime, Member, Path, PathArguments,
+    PathSegment, Type, TypePath,
 };
 
 #[derive(Clone)]
@@ -11,6 +12,9 @@ struct MyField {
     ident: Ident,
     ty: Type,
     cond: Option<Expr>,
+    max_len: Option<Expr>,
+    /// Whether the field exists in the protocol version being decoded or encoded
+    version: Option<TokenStream2>,
 }
 
 impl ToTokens for MyField {
@@ -18,6 +22,8 @@ impl ToTokens for MyField {
         self.ident.to_tokens(tokens);
         self.ty.to_tokens(tokens);
         self.cond.to_tokens(tokens);
+        self.max_len.to_tokens(tokens);
+        self.version.to_tokens(tokens);
     }
 }
 
@@ -57,11 +63,22 @@ fn optional_type(ty: &Type) -> Option<&Type> {
 }
 
 fn derive_minecraft_data_for_fields(
+    krate: &Path,
+    borrowed: Option<&Lifetime>,
     reader_id: &Ident,
     writer_id: &Ident,
     raw_fields: Fields,
     constructor: TokenStream2,
-) -> Result<(TokenStream2, TokenStream2, TokenStream2, TokenStream2), TokenStream2> {
+) -> Result<
+    (
+        TokenStream2,
+        TokenStream2,
+        TokenStream2,
+        TokenStream2,
+        TokenStream2,
+    ),
+    TokenStream2,
+> {
     let is_named = matches!(raw_fields, syn::Fields::Named(_));
     let members = raw_fields.members().collect::<Vec<_>>();
     let mut fields = Vec::new();
@@ -85,6 +102,42 @@ fn derive_minecraft_data_for_fields(
         } else {
             None
         };
+        let mut max_len = None;
+        let mut version_bounds = Vec::new();
+        for attr in f.attrs.iter().filter(|attr| attr.path().is_ident("mc")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("max_len") {
+                    max_len = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("since") {
+                    let since: Expr = meta.value()?.parse()?;
+                    version_bounds.push(quote! {
+                        #krate::protocol::ProtocolVersion::current() >= #since
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("before") {
+                    let before: Expr = meta.value()?.parse()?;
+                    version_bounds.push(quote! {
+                        #krate::protocol::ProtocolVersion::current() < #before
+                    });
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown mc field attribute"))
+                }
+            })
+            .map_err(|e| e.into_compile_error())?;
+        }
+        let version = if version_bounds.is_empty() {
+            None
+        } else {
+            if optional_type(&f.ty).is_none() {
+                return Err(quote! {compile_error!("since and before are only valid on fields of type Option<T>");}.into());
+            }
+            if !is_named {
+                return Err(quote! {compile_error!{"since and before are only valid on structs with named fields"};}.into());
+            }
+            Some(quote! {(#(#version_bounds)&&*)})
+        };
         let ident = match member {
             Member::Named(i) => i,
             Member::Unnamed(i) => format_ident!("__field{}", i),
@@ -94,16 +147,53 @@ fn derive_minecraft_data_for_fields(
             ident,
             ty: f.ty,
             cond,
+            max_len,
+            version,
         });
     }
 
-    let decode_expr = quote! {crate::datatypes::MinecraftData::decode(#reader_id)?};
+    let decode_expr = |MyField {
+                           ty,
+                           cond,
+                           max_len,
+                           version,
+                           ..
+                       }: &MyField| {
+        // fields with a present_if or version bounds are decoded as their inner type
+        let ty = match (cond, version) {
+            (None, None) => ty,
+            _ => optional_type(ty).unwrap_or(ty),
+        };
+        match (max_len, borrowed) {
+            (Some(max_len), None) => quote! {
+                <#ty as #krate::datatypes::LengthPrefixed>::decode_with_max_len(#reader_id, #max_len)?
+            },
+            (Some(max_len), Some(lt)) => quote! {
+                <#ty as #krate::datatypes::LengthPrefixedRef<#lt>>::decode_ref_with_max_len(#reader_id, #max_len)?
+            },
+            (None, None) => quote! {#krate::datatypes::MinecraftData::decode(#reader_id)?},
+            (None, Some(_)) => {
+                quote! {#krate::datatypes::MinecraftDataRef::decode_ref(#reader_id)?}
+            }
+        }
+    };
     let decode_block = if is_named {
-        let decode_lines = fields.iter().map(|MyField { ident, cond, ty }| {
-            let rvalue = if let Some(cond) = cond {
-                quote! {if #cond { Some(#decode_expr) } else { None }}
-            } else {
-                quote! {#decode_expr}
+        let decode_lines = fields.iter().map(|field| {
+            let MyField {
+                ident,
+                cond,
+                ty,
+                version,
+                ..
+            } = field;
+            let decode_expr = decode_expr(field);
+            let rvalue = match (cond, version) {
+                (None, None) => quote! {#decode_expr},
+                (Some(cond), None) => quote! {if #cond { Some(#decode_expr) } else { None }},
+                (None, Some(version)) => quote! {if #version { Some(#decode_expr) } else { None }},
+                (Some(cond), Some(version)) => {
+                    quote! {if #version && #cond { Some(#decode_expr) } else { None }}
+                }
             };
             quote! {let #ident: #ty = #rvalue;}
         });
@@ -116,10 +206,11 @@ fn derive_minecraft_data_for_fields(
             }
         }
     } else {
+        let decode_exprs = fields.iter().map(decode_expr);
         quote! {
             {
                 #constructor {
-                    #(#members: #decode_expr),*
+                    #(#members: #decode_exprs),*
                 }
             }
         }
@@ -129,70 +220,216 @@ fn derive_minecraft_data_for_fields(
         Fields::Unnamed(_) => quote! {#constructor(#(#idents),*)},
         Fields::Named(_) => quote! {#constructor{#(#idents),*}},
     };
-    let encode_lines = fields.iter().map(|MyField { ident, cond, .. }| match cond {
-        Some(_) => quote! {
-            if let Some(val) = #ident {
-                crate::datatypes::MinecraftData::encode(val, #writer_id)?;
-            }
+    // fields gated on a version the packet is not being encoded for are left out even if set
+    let encode_lines = fields.iter().map(
+        |MyField {
+             ident,
+             cond,
+             version,
+             ..
+         }| match (cond, version) {
+            (None, None) => quote! {#krate::datatypes::MinecraftData::encode(#ident, #writer_id)?;},
+            (_, None) => quote! {
+                if let Some(val) = #ident {
+                    #krate::datatypes::MinecraftData::encode(val, #writer_id)?;
+                }
+            },
+            (_, Some(version)) => quote! {
+                if let (true, Some(val)) = (#version, #ident) {
+                    #krate::datatypes::MinecraftData::encode(val, #writer_id)?;
+                }
+            },
         },
-        None => quote! {crate::datatypes::MinecraftData::encode(#ident, #writer_id)?;},
-    });
-    let num_bytes_lines = fields.iter().map(|MyField { ident, cond, .. }| match cond {
-        Some(_) => quote! {
-            if let Some(val) = #ident {
-                crate::datatypes::MinecraftData::num_bytes(val)
-            } else {
-                0
-            }
+    );
+    let num_bytes_lines = fields.iter().map(
+        |MyField {
+             ident,
+             cond,
+             version,
+             ..
+         }| match (cond, version) {
+            (None, None) => quote! {#krate::datatypes::MinecraftData::num_bytes(#ident)},
+            (_, None) => quote! {
+                if let Some(val) = #ident {
+                    #krate::datatypes::MinecraftData::num_bytes(val)
+                } else {
+                    0
+                }
+            },
+            (_, Some(version)) => quote! {
+                if let (true, Some(val)) = (#version, #ident) {
+                    #krate::datatypes::MinecraftData::num_bytes(val)
+                } else {
+                    0
+                }
+            },
         },
-        None => quote! {crate::datatypes::MinecraftData::num_bytes(#ident)},
-    });
+    );
     let num_bytes_expr = if members.len() == 0 {
         quote! {0}
     } else {
         quote! {(#(#num_bytes_lines)+*)}
     };
+    // fields that may be left out count for nothing
+    let min_bytes_terms = fields
+        .iter()
+        .filter(|field| field.cond.is_none() && field.version.is_none())
+        .map(|MyField { ty, .. }| match borrowed {
+            Some(lt) => quote! {<#ty as #krate::datatypes::MinecraftDataRef<#lt>>::MIN_BYTES},
+            None => quote! {<#ty as #krate::datatypes::MinecraftData>::MIN_BYTES},
+        });
+    let min_bytes_expr = quote! {(0 #(+ #min_bytes_terms)*)};
     return Ok((
         match_arm,
         decode_block,
         quote! {#(#encode_lines)*},
         num_bytes_expr,
+        min_bytes_expr,
     ));
 }
 
-fn derive_minecraft_data_for_struct(name: Ident, data: DataStruct) -> TokenStream {
-    let reader_id = format_ident!("reader");
-    let writer_id = format_ident!("writer");
+/// Options set on the type itself with `#[mc(...)]`
+struct Container {
+    krate: Path,
+    /// Set by `#[mc(borrowed)]`, in which case only `MinecraftDataRef` is derived, borrowing for
+    /// the type's lifetime parameter
+    borrowed: Option<Lifetime>,
+}
 
-    match derive_minecraft_data_for_fields(&reader_id, &writer_id, data.fields, quote! {Self}) {
-        Err(msg) => return msg.into(),
-        Ok((match_arm, decode_block, encode_expr, num_bytes_expr)) => {
-            let decode_body = quote! {Ok(#decode_block)};
-            quote!{
-                impl crate::datatypes::MinecraftData for #name {
-                    fn decode<R: ::std::io::Read>(#reader_id: &mut R) -> ::std::result::Result<Self, crate::datatypes::Error> {
+impl Container {
+    fn parse(input: &DeriveInput) -> Result<Self, TokenStream2> {
+        let mut krate = parse_quote! {::minecraft_client};
+        let mut borrowed = false;
+        for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("mc")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("crate") {
+                    krate = meta.value()?.parse()?;
+                    Ok(())
+                } else if meta.path.is_ident("borrowed") {
+                    borrowed = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown mc attribute"))
+                }
+            })
+            .map_err(|e| e.into_compile_error())?;
+        }
+        let borrowed = if borrowed {
+            match input.generics.lifetimes().next() {
+                Some(param) => Some(param.lifetime.clone()),
+                None => {
+                    return Err(
+                        quote! {compile_error!("mc(borrowed) types need a lifetime parameter");},
+                    )
+                }
+            }
+        } else {
+            None
+        };
+        Ok(Container { krate, borrowed })
+    }
+
+    /// Wraps the body of `decode` in the impls for the type
+    fn impls(
+        &self,
+        input: &DeriveInput,
+        reader_id: &Ident,
+        writer_id: &Ident,
+        decode_body: TokenStream2,
+        encode_body: TokenStream2,
+        num_bytes_body: TokenStream2,
+        min_bytes: TokenStream2,
+    ) -> TokenStream {
+        let Container { krate, borrowed } = self;
+        let name = &input.ident;
+        match borrowed {
+            Some(lt) => {
+                let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+                quote! {
+                    impl #impl_generics #krate::datatypes::MinecraftDataRef<#lt> for #name #ty_generics #where_clause {
+                        const MIN_BYTES: usize = #min_bytes;
+
+                        fn decode_ref(#reader_id: &mut &#lt [u8]) -> ::std::result::Result<Self, #krate::datatypes::Error> {
+                            #decode_body
+                        }
+                    }
+                }
+            }
+            None => quote! {
+                impl #krate::datatypes::MinecraftData for #name {
+                    const MIN_BYTES: usize = #min_bytes;
+
+                    fn decode<R: #krate::datatypes::MinecraftRead>(#reader_id: &mut R) -> ::std::result::Result<Self, #krate::datatypes::Error> {
                         #decode_body
                     }
 
-                    fn encode<W: ::std::io::Write>(self, #writer_id: &mut W) -> ::std::result::Result<(), crate::datatypes::Error> {
-                        match self {
-                            #match_arm => { #encode_expr }
-                        }
-                        Ok(())
+                    fn encode<W: ::std::io::Write>(self, #writer_id: &mut W) -> ::std::result::Result<(), #krate::datatypes::Error> {
+                        #encode_body
                     }
 
                     fn num_bytes(&self) -> usize {
-                        match self {
-                            #match_arm => #num_bytes_expr
-                        }
+                        #num_bytes_body
+                    }
+                }
+
+                impl<'__mc> #krate::datatypes::MinecraftDataRef<'__mc> for #name {
+                    const MIN_BYTES: usize = <Self as #krate::datatypes::MinecraftData>::MIN_BYTES;
+
+                    fn decode_ref(#reader_id: &mut &'__mc [u8]) -> ::std::result::Result<Self, #krate::datatypes::Error> {
+                        <Self as #krate::datatypes::MinecraftData>::decode(#reader_id)
                     }
                 }
-            }.into()
+            },
         }
+        .into()
+    }
+}
+
+fn derive_minecraft_data_for_struct(
+    container: Container,
+    input: &DeriveInput,
+    data: &DataStruct,
+) -> TokenStream {
+    let reader_id = format_ident!("reader");
+    let writer_id = format_ident!("writer");
+
+    match derive_minecraft_data_for_fields(
+        &container.krate,
+        container.borrowed.as_ref(),
+        &reader_id,
+        &writer_id,
+        data.fields.clone(),
+        quote! {Self},
+    ) {
+        Err(msg) => return msg.into(),
+        Ok((match_arm, decode_block, encode_expr, num_bytes_expr, min_bytes)) => container.impls(
+            input,
+            &reader_id,
+            &writer_id,
+            quote! {Ok(#decode_block)},
+            quote! {
+                match self {
+                    #match_arm => { #encode_expr }
+                }
+                Ok(())
+            },
+            quote! {
+                match self {
+                    #match_arm => #num_bytes_expr
+                }
+            },
+            min_bytes,
+        ),
     }
 }
 
-fn derive_minecraft_data_for_enum(name: Ident, data: DataEnum) -> TokenStream {
+fn derive_minecraft_data_for_enum(
+    container: Container,
+    input: &DeriveInput,
+    data: &DataEnum,
+) -> TokenStream {
+    let krate = &container.krate;
+    let name = &input.ident;
     let reader_id = format_ident!("reader");
     let writer_id = format_ident!("writer");
     let mut idents = Vec::new();
@@ -201,7 +438,7 @@ fn derive_minecraft_data_for_enum(name: Ident, data: DataEnum) -> TokenStream {
     let mut decode_blocks = Vec::new();
     let mut encode_exprs = Vec::new();
     let mut num_bytes_exprs = Vec::new();
-    for v in data.variants.into_iter() {
+    for v in data.variants.iter().cloned() {
         // TODO: delete
         // if !matches!(v.fields, syn::Fields::Unit) {
         //     return quote!(compile_error!(
@@ -220,13 +457,15 @@ fn derive_minecraft_data_for_enum(name: Ident, data: DataEnum) -> TokenStream {
         };
 
         match derive_minecraft_data_for_fields(
+            krate,
+            container.borrowed.as_ref(),
             &reader_id,
             &writer_id,
             v.fields,
             quote! {Self::#ident},
         ) {
             Err(msg) => return msg.into(),
-            Ok((match_arm, decode_block, encode_expr, num_bytes_expr)) => {
+            Ok((match_arm, decode_block, encode_expr, num_bytes_expr, _)) => {
                 match_arms.push(match_arm);
                 decode_blocks.push(decode_block);
                 encode_exprs.push(encode_expr);
@@ -238,37 +477,50 @@ fn derive_minecraft_data_for_enum(name: Ident, data: DataEnum) -> TokenStream {
         reprs.push(repr);
     }
 
-    quote!{
-        impl crate::datatypes::MinecraftData for #name {
-            fn decode<R: ::std::io::Read>(#reader_id: &mut R) -> ::std::result::Result<Self, crate::datatypes::Error> {
-                match crate::datatypes::MinecraftData::decode(#reader_id)? {
-                    #(#reprs => Ok(#decode_blocks),)*
-                    _ => Err(anyhow!("Invalid #name")),
-                }
-            }
-
-            fn encode<W: ::std::io::Write>(self, #writer_id: &mut W) -> ::std::result::Result<(), crate::datatypes::Error> {
-                match self {
-                    #(#match_arms => { crate::datatypes::MinecraftData::encode(#reprs, #writer_id)?; #encode_exprs })*
-                };
-                Ok(())
+    let decode_repr = match &container.borrowed {
+        Some(_) => quote! {#krate::datatypes::MinecraftDataRef::decode_ref(#reader_id)?},
+        None => quote! {#krate::datatypes::MinecraftData::decode(#reader_id)?},
+    };
+    container.impls(
+        input,
+        &reader_id,
+        &writer_id,
+        quote! {
+            match #decode_repr {
+                #(#reprs => Ok(#decode_blocks),)*
+                _ => Err(#krate::datatypes::Error::msg(concat!("Invalid ", stringify!(#name)))),
             }
-
-            fn num_bytes(&self) -> usize {
-                match self {
-                    #(#match_arms => crate::datatypes::MinecraftData::num_bytes(&#reprs) + #num_bytes_exprs,)*
-                }
+        },
+        quote! {
+            match self {
+                #(#match_arms => { #krate::datatypes::MinecraftData::encode(#reprs, #writer_id)?; #encode_exprs })*
+            };
+            Ok(())
+        },
+        quote! {
+            match self {
+                #(#match_arms => #krate::datatypes::MinecraftData::num_bytes(&#reprs) + #num_bytes_exprs,)*
             }
-        }
-    }.into()
+        },
+        // every repr is a number or VarInt, so at least a byte
+        quote! {1},
+    )
 }
 
-#[proc_macro_derive(MinecraftData, attributes(present_if, mc_repr))]
+/// The path to the `minecraft_client` crate can be overridden with
+/// `#[mc(crate = path::to::minecraft_client)]` when it is re-exported somewhere else.
+#[proc_macro_derive(MinecraftData, attributes(present_if, mc_repr, mc))]
 pub fn derive_minecraft_data(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    match input.data {
-        Data::Struct(data_struct) => derive_minecraft_data_for_struct(input.ident, data_struct),
-        Data::Enum(data_enum) => derive_minecraft_data_for_enum(input.ident, data_enum),
+    let container = match Container::parse(&input) {
+        Ok(container) => container,
+        Err(e) => return e.into(),
+    };
+    match &input.data {
+        Data::Struct(data_struct) => {
+            derive_minecraft_data_for_struct(container, &input, data_struct)
+        }
+        Data::Enum(data_enum) => derive_minecraft_data_for_enum(container, &input, data_enum),
         Data::Union(_) => {
             quote! {compile_error!{"derive(MinecraftData) does not work on unions"};}.into()
         }
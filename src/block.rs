@@ -0,0 +1,313 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+
+use crate::{
+    datatypes::{Error, Vec3d},
+    protocol::ProtocolVersion,
+};
+
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+
+/// The version whose state ids the bundled block table uses. Other versions number states
+/// differently, so connections only run physics and pathfinding when they speak this one.
+pub const BLOCKS_VERSION: ProtocolVersion = ProtocolVersion::V1_21_9;
+
+/// What a block's collision looks like, coarse enough to be listed per block in the data file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockShape {
+    Empty,
+    Full,
+    /// No collision, but entities in it swim
+    Liquid,
+    /// A full footprint this many sixteenths of a block tall
+    Height(u8),
+}
+
+#[derive(Debug)]
+pub struct Property {
+    pub name: &'static str,
+    pub values: &'static [&'static str],
+}
+
+#[derive(Debug)]
+pub struct BlockInfo {
+    pub name: &'static str,
+    pub first_state: i32,
+    pub default_state: i32,
+    /// Sorted by name, which is the order state ids are assigned in
+    pub properties: &'static [Property],
+    pub shape: BlockShape,
+}
+
+impl BlockInfo {
+    pub fn state_count(&self) -> i32 {
+        self.properties
+            .iter()
+            .map(|property| property.values.len() as i32)
+            .product()
+    }
+
+    pub fn default_state(&self) -> BlockState {
+        BlockState(self.default_state)
+    }
+
+    /// The state with these properties, taking the default for any left out
+    pub fn state(&self, properties: &[(&str, &str)]) -> Option<BlockState> {
+        let mut state = self.default_state();
+        for (name, value) in properties {
+            state = state.with(name, value)?;
+        }
+        Some(state)
+    }
+
+    /// Distance between consecutive values of each property, in state ids
+    fn strides(&self) -> impl Iterator<Item = (&'static Property, i32)> {
+        let mut stride = 1;
+        let mut strides: Vec<_> = self
+            .properties
+            .iter()
+            .rev()
+            .map(|property| {
+                let this = stride;
+                stride *= property.values.len() as i32;
+                (property, this)
+            })
+            .collect();
+        strides.reverse();
+        strides.into_iter()
+    }
+}
+
+/// Every block in the bundled table, in registration order
+pub fn blocks() -> &'static [BlockInfo] {
+    BLOCKS
+}
+
+pub fn block(name: &str) -> Option<&'static BlockInfo> {
+    BLOCKS.iter().find(|block| block.name == name)
+}
+
+/// An axis aligned box, in blocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3d,
+    pub max: Vec3d,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3d, max: Vec3d) -> Self {
+        Self { min, max }
+    }
+
+    /// A full block at the origin
+    pub fn unit() -> Self {
+        Self::new(
+            Vec3d::default(),
+            Vec3d {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        )
+    }
+
+    pub fn offset(self, x: f64, y: f64, z: f64) -> Self {
+        let shift = |v: Vec3d| Vec3d {
+            x: v.x + x,
+            y: v.y + y,
+            z: v.z + z,
+        };
+        Self {
+            min: shift(self.min),
+            max: shift(self.max),
+        }
+    }
+
+    /// Grows the box in the direction of a movement, to cover everything it passes through
+    pub fn expand_towards(self, delta: Vec3d) -> Self {
+        let mut expanded = self;
+        for (min, max, d) in [
+            (&mut expanded.min.x, &mut expanded.max.x, delta.x),
+            (&mut expanded.min.y, &mut expanded.max.y, delta.y),
+            (&mut expanded.min.z, &mut expanded.max.z, delta.z),
+        ] {
+            if d < 0.0 {
+                *min += d;
+            } else {
+                *max += d;
+            }
+        }
+        expanded
+    }
+
+    /// Touching faces do not count
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x < other.max.x
+            && self.max.x > other.min.x
+            && self.min.y < other.max.y
+            && self.max.y > other.min.y
+            && self.min.z < other.max.z
+            && self.max.z > other.min.z
+    }
+}
+
+/// A block state id, as stored in chunks.
+///
+/// Ids the bundled table does not know are treated as solid, so that nothing walks into them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockState(pub i32);
+
+impl BlockState {
+    pub const AIR: Self = Self(0);
+
+    pub fn info(self) -> Option<&'static BlockInfo> {
+        let index = BLOCKS.partition_point(|block| block.first_state <= self.0);
+        let block = BLOCKS.get(index.checked_sub(1)?)?;
+        (self.0 < block.first_state + block.state_count()).then_some(block)
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        self.info().map(|block| block.name)
+    }
+
+    /// Property names and values, in id order
+    pub fn properties(self) -> Vec<(&'static str, &'static str)> {
+        let Some(block) = self.info() else {
+            return Vec::new();
+        };
+        let offset = self.0 - block.first_state;
+        block
+            .strides()
+            .map(|(property, stride)| {
+                let index = (offset / stride) as usize % property.values.len();
+                (property.name, property.values[index])
+            })
+            .collect()
+    }
+
+    pub fn property(self, name: &str) -> Option<&'static str> {
+        self.properties()
+            .into_iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The same block with one property changed
+    pub fn with(self, name: &str, value: &str) -> Option<Self> {
+        let block = self.info()?;
+        let offset = self.0 - block.first_state;
+        let (property, stride) = block.strides().find(|(p, _)| p.name == name)?;
+        let current = (offset / stride) % property.values.len() as i32;
+        let new = property.values.iter().position(|v| *v == value)? as i32;
+        Some(Self(self.0 + (new - current) * stride))
+    }
+
+    pub fn shape(self) -> BlockShape {
+        self.info().map_or(BlockShape::Full, |block| block.shape)
+    }
+
+    pub fn is_air(self) -> bool {
+        matches!(
+            self.name(),
+            Some("minecraft:air" | "minecraft:cave_air" | "minecraft:void_air")
+        )
+    }
+
+    /// A full cube, which blocks movement and can be stood on
+    pub fn is_solid(self) -> bool {
+        self.shape() == BlockShape::Full
+    }
+
+    pub fn is_liquid(self) -> bool {
+        self.shape() == BlockShape::Liquid
+    }
+
+    /// Boxes within the unit cube at the block's position
+    pub fn collision_boxes(self) -> Vec<Aabb> {
+        let height = match self.shape() {
+            BlockShape::Empty | BlockShape::Liquid => return Vec::new(),
+            BlockShape::Full => 1.0,
+            BlockShape::Height(sixteenths) => sixteenths as f64 / 16.0,
+        };
+        vec![Aabb::new(
+            Vec3d::default(),
+            Vec3d {
+                x: 1.0,
+                y: height,
+                z: 1.0,
+            },
+        )]
+    }
+
+    /// Whether a player can occupy the block's space
+    pub fn is_passable(self) -> bool {
+        self.collision_boxes().is_empty()
+    }
+
+    /// Whether a player can stand on top of the block
+    pub fn can_stand_on(self) -> bool {
+        !self.collision_boxes().is_empty()
+    }
+}
+
+impl From<i32> for BlockState {
+    fn from(value: i32) -> Self {
+        Self(value)
+    }
+}
+
+/// `minecraft:oak_stairs[facing=north,half=top]`, or the bare id for unknown states
+impl Display for BlockState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(block) = self.info() else {
+            return write!(f, "{}", self.0);
+        };
+        f.write_str(block.name)?;
+        let properties = self.properties();
+        if !properties.is_empty() {
+            let properties: Vec<String> = properties
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the [`Display`] form; the namespace may be left out and so may properties, which
+/// take their default
+impl FromStr for BlockState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, properties) = match s.split_once('[') {
+            Some((name, rest)) => {
+                let properties = rest
+                    .strip_suffix(']')
+                    .ok_or_else(|| anyhow!("missing `]` in {s}"))?;
+                (name, properties)
+            }
+            None => (s, ""),
+        };
+        let name = if name.contains(':') {
+            name.to_owned()
+        } else {
+            format!("minecraft:{name}")
+        };
+        let block = block(&name).ok_or_else(|| anyhow!("unknown block {name}"))?;
+        let properties = properties
+            .split(',')
+            .filter(|property| !property.is_empty())
+            .map(|property| {
+                property
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("missing `=` in {property}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        block
+            .state(&properties)
+            .ok_or_else(|| anyhow!("invalid properties for {s}"))
+    }
+}
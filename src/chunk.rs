@@ -0,0 +1,663 @@
+use std::io::Write;
+
+use anyhow::anyhow;
+
+use crate::{
+    datatypes::{decode_len, BitSet, Error, MinecraftData, MinecraftRead, VarInt},
+    nbt::{Nbt, NbtRef},
+    packet::ChunkDataAndUpdateLightPacket,
+    registry::Registries,
+};
+
+/// Bits per entry of a direct block state palette, enough for every block state in 1.21.x
+pub const BLOCK_STATE_BITS: u8 = 15;
+
+/// `ceil(log2(count))`, how vanilla sizes direct palettes and heightmaps
+fn ceil_log2(count: usize) -> u8 {
+    (usize::BITS - count.saturating_sub(1).leading_zeros()) as u8
+}
+
+/// How a paletted container picks its palette and storage size from the bits per entry it is
+/// sent with. Vanilla rounds small indirect palettes up, so the bits on the wire are not always
+/// the bits the entries are packed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteStrategy {
+    pub entries: usize,
+    min_indirect_bits: u8,
+    max_indirect_bits: u8,
+    direct_bits: u8,
+}
+
+impl PaletteStrategy {
+    pub const BLOCK_STATES: Self = Self {
+        entries: 4096,
+        min_indirect_bits: 4,
+        max_indirect_bits: 8,
+        direct_bits: BLOCK_STATE_BITS,
+    };
+
+    pub fn biomes(biome_count: usize) -> Self {
+        Self {
+            entries: 64,
+            min_indirect_bits: 1,
+            max_indirect_bits: 3,
+            direct_bits: ceil_log2(biome_count),
+        }
+    }
+
+    /// The bits entries are packed with, or `None` for a direct palette
+    fn indirect_bits(&self, sent: u8) -> Option<u8> {
+        (sent <= self.max_indirect_bits).then(|| sent.max(self.min_indirect_bits))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Palette {
+    /// Every entry is this value and no data is sent
+    Single(i32),
+    /// Entries are indices into this list
+    Indirect(Vec<i32>),
+    /// Entries are the values themselves
+    Direct,
+}
+
+/// The block states of a chunk section or its biomes, packed into longs.
+///
+/// Entries never straddle two longs, so the top bits of each long can be padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PalettedContainer {
+    strategy: PaletteStrategy,
+    bits: u8,
+    palette: Palette,
+    data: Vec<u64>,
+}
+
+impl PalettedContainer {
+    pub fn single(strategy: PaletteStrategy, value: i32) -> Self {
+        Self {
+            strategy,
+            bits: 0,
+            palette: Palette::Single(value),
+            data: Vec::new(),
+        }
+    }
+
+    /// Packs `values`, which must hold one value per entry, with the smallest palette that fits
+    pub fn from_values(strategy: PaletteStrategy, values: &[i32]) -> Self {
+        assert_eq!(values.len(), strategy.entries);
+        let mut distinct = values.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
+        if distinct.len() == 1 {
+            return Self::single(strategy, distinct[0]);
+        }
+
+        let sent = ceil_log2(distinct.len());
+        let (bits, palette) = match strategy.indirect_bits(sent) {
+            Some(bits) => (bits, Palette::Indirect(distinct)),
+            None => (strategy.direct_bits, Palette::Direct),
+        };
+        let mut container = Self {
+            strategy,
+            bits,
+            palette,
+            data: vec![0; strategy.entries.div_ceil(64 / bits as usize)],
+        };
+        for (index, &value) in values.iter().enumerate() {
+            let raw = match &container.palette {
+                Palette::Indirect(palette) => palette.binary_search(&value).unwrap() as u64,
+                _ => value as u64,
+            };
+            container.set_raw(index, raw);
+        }
+        container
+    }
+
+    pub fn decode<R: MinecraftRead>(
+        reader: &mut R,
+        strategy: PaletteStrategy,
+    ) -> Result<Self, Error> {
+        let sent = u8::decode(reader)?;
+        if sent == 0 {
+            return Ok(Self::single(strategy, VarInt::decode(reader)?.0));
+        }
+        let (bits, palette) = match strategy.indirect_bits(sent) {
+            Some(bits) => {
+                let len = decode_len(reader, strategy.entries)?;
+                let mut palette = Vec::with_capacity(len);
+                for _ in 0..len {
+                    palette.push(VarInt::decode(reader)?.0);
+                }
+                (bits, Palette::Indirect(palette))
+            }
+            None => (strategy.direct_bits, Palette::Direct),
+        };
+        if bits == 0 || bits > 32 {
+            return Err(anyhow!("cannot pack entries into {bits} bits"));
+        }
+
+        // the number of longs is not sent, it follows from the bits per entry
+        let longs = strategy.entries.div_ceil(64 / bits as usize);
+        let mut data = Vec::with_capacity(longs);
+        for _ in 0..longs {
+            data.push(u64::decode(reader)?);
+        }
+        let container = Self {
+            strategy,
+            bits,
+            palette,
+            data,
+        };
+        if let Palette::Indirect(palette) = &container.palette
+            && let Some(index) =
+                (0..strategy.entries).find(|&i| container.raw(i) as usize >= palette.len())
+        {
+            return Err(anyhow!(
+                "entry {index} points past the end of a palette of {}",
+                palette.len()
+            ));
+        }
+        Ok(container)
+    }
+
+    pub fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        match &self.palette {
+            Palette::Single(value) => {
+                0u8.encode(writer)?;
+                VarInt(*value).encode(writer)?;
+            }
+            Palette::Indirect(palette) => {
+                self.bits.encode(writer)?;
+                VarInt(palette.len() as i32).encode(writer)?;
+                for &value in palette {
+                    VarInt(value).encode(writer)?;
+                }
+            }
+            Palette::Direct => self.bits.encode(writer)?,
+        }
+        for &long in &self.data {
+            long.encode(writer)?;
+        }
+        Ok(())
+    }
+
+    pub fn num_bytes(&self) -> usize {
+        let palette = match &self.palette {
+            Palette::Single(value) => VarInt(*value).num_bytes(),
+            Palette::Indirect(palette) => {
+                VarInt(palette.len() as i32).num_bytes()
+                    + palette
+                        .iter()
+                        .map(|&v| VarInt(v).num_bytes())
+                        .sum::<usize>()
+            }
+            Palette::Direct => 0,
+        };
+        1 + palette + self.data.len() * 8
+    }
+
+    pub fn strategy(&self) -> PaletteStrategy {
+        self.strategy
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Bits each entry is packed with, 0 for a single value
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    pub fn get(&self, index: usize) -> i32 {
+        assert!(index < self.strategy.entries);
+        match &self.palette {
+            Palette::Single(value) => *value,
+            Palette::Indirect(palette) => palette[self.raw(index) as usize],
+            Palette::Direct => self.raw(index) as i32,
+        }
+    }
+
+    /// Changes one entry, growing the palette or switching to a direct one when it runs out
+    /// of room
+    pub fn set(&mut self, index: usize, value: i32) {
+        assert!(index < self.strategy.entries);
+        let raw = match &mut self.palette {
+            Palette::Single(single) if *single == value => return,
+            Palette::Single(_) => None,
+            Palette::Indirect(palette) => match palette.iter().position(|&v| v == value) {
+                Some(raw) => Some(raw as u64),
+                None if palette.len() < 1 << self.bits => {
+                    palette.push(value);
+                    Some(palette.len() as u64 - 1)
+                }
+                None => None,
+            },
+            Palette::Direct => Some(value as u64),
+        };
+        match raw {
+            Some(raw) => self.set_raw(index, raw),
+            None => {
+                let mut values: Vec<i32> = self.iter().collect();
+                values[index] = value;
+                *self = Self::from_values(self.strategy, &values);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i32> + '_ {
+        (0..self.strategy.entries).map(|index| self.get(index))
+    }
+
+    fn raw(&self, index: usize) -> u64 {
+        let per_long = 64 / self.bits as usize;
+        let shift = (index % per_long) * self.bits as usize;
+        (self.data[index / per_long] >> shift) & ((1 << self.bits) - 1)
+    }
+
+    fn set_raw(&mut self, index: usize, raw: u64) {
+        let per_long = 64 / self.bits as usize;
+        let shift = (index % per_long) * self.bits as usize;
+        let mask = ((1u64 << self.bits) - 1) << shift;
+        let long = &mut self.data[index / per_long];
+        *long = (*long & !mask) | ((raw << shift) & mask);
+    }
+}
+
+/// 16×16×16 blocks and their 4×4×4 biomes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkSection {
+    /// Blocks that are not air, which the server counts for us
+    pub block_count: i16,
+    pub block_states: PalettedContainer,
+    pub biomes: PalettedContainer,
+}
+
+impl ChunkSection {
+    pub fn decode<R: MinecraftRead>(
+        reader: &mut R,
+        biomes: PaletteStrategy,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            block_count: i16::decode(reader)?,
+            block_states: PalettedContainer::decode(reader, PaletteStrategy::BLOCK_STATES)?,
+            biomes: PalettedContainer::decode(reader, biomes)?,
+        })
+    }
+
+    /// Decodes the sections of a chunk from the data of a chunk packet, borrowed or not, which
+    /// must hold exactly one per section of the dimension
+    pub fn decode_all(mut data: &[u8], dimension: &Dimension) -> Result<Vec<Self>, Error> {
+        let biomes = dimension.biome_strategy();
+        let sections = (0..dimension.section_count())
+            .map(|_| Self::decode(&mut data, biomes))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !data.is_empty() {
+            return Err(anyhow!(
+                "{} unread bytes after the {} sections of a chunk",
+                data.len(),
+                sections.len()
+            ));
+        }
+        Ok(sections)
+    }
+
+    pub fn encode<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.block_count.encode(writer)?;
+        self.block_states.encode(writer)?;
+        self.biomes.encode(writer)
+    }
+
+    pub fn num_bytes(&self) -> usize {
+        2 + self.block_states.num_bytes() + self.biomes.num_bytes()
+    }
+
+    /// The block state at coordinates within the section, each from 0 to 15
+    pub fn block_state(&self, x: usize, y: usize, z: usize) -> i32 {
+        self.block_states.get((y * 16 + z) * 16 + x)
+    }
+
+    /// Changes a block, leaving `block_count` as the server last sent it
+    pub fn set_block_state(&mut self, x: usize, y: usize, z: usize, state: i32) {
+        self.block_states.set((y * 16 + z) * 16 + x, state);
+    }
+
+    /// The biome at block coordinates within the section, each from 0 to 15
+    pub fn biome(&self, x: usize, y: usize, z: usize) -> i32 {
+        self.biomes.get(((y / 4) * 4 + z / 4) * 4 + x / 4)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+pub enum HeightmapKind {
+    #[mc_repr(VarInt(0))]
+    WorldSurfaceWorldgen,
+    #[mc_repr(VarInt(1))]
+    WorldSurface,
+    #[mc_repr(VarInt(2))]
+    OceanFloorWorldgen,
+    #[mc_repr(VarInt(3))]
+    OceanFloor,
+    #[mc_repr(VarInt(4))]
+    MotionBlocking,
+    #[mc_repr(VarInt(5))]
+    MotionBlockingNoLeaves,
+}
+
+/// The highest block of some kind in each column, packed like a paletted container with
+/// `ceil(log2(height + 1))` bits per column
+#[derive(Debug, Clone, PartialEq, Eq, MinecraftData)]
+pub struct Heightmap {
+    pub kind: HeightmapKind,
+    #[mc(max_len = 256)]
+    pub data: Vec<i64>,
+}
+
+impl Heightmap {
+    /// The y just above the highest block in a column, or `None` if the data is too short for
+    /// this dimension
+    pub fn get(&self, x: usize, z: usize, dimension: &Dimension) -> Option<i32> {
+        let bits = ceil_log2(dimension.height as usize + 1) as usize;
+        let per_long = 64 / bits;
+        let index = z * 16 + x;
+        let long = *self.data.get(index / per_long)? as u64;
+        let raw = (long >> ((index % per_long) * bits)) & ((1 << bits) - 1);
+        Some(dimension.min_y + raw as i32)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, MinecraftData)]
+pub struct BlockEntity {
+    /// x in the high nibble and z in the low one, relative to the chunk
+    pub packed_xz: u8,
+    pub y: i16,
+    /// Network id in the `minecraft:block_entity_type` registry
+    pub kind: VarInt,
+    pub data: Nbt,
+}
+
+impl BlockEntity {
+    pub fn x(&self) -> usize {
+        (self.packed_xz >> 4) as usize
+    }
+
+    pub fn z(&self) -> usize {
+        (self.packed_xz & 0xF) as usize
+    }
+}
+
+/// [`BlockEntity`] borrowing its NBT from the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+#[mc(borrowed)]
+pub struct BlockEntityRef<'a> {
+    pub packed_xz: u8,
+    pub y: i16,
+    pub kind: VarInt,
+    pub data: NbtRef<'a>,
+}
+
+impl BlockEntityRef<'_> {
+    pub fn x(&self) -> usize {
+        (self.packed_xz >> 4) as usize
+    }
+
+    pub fn z(&self) -> usize {
+        (self.packed_xz & 0xF) as usize
+    }
+
+    pub fn to_owned(&self) -> Result<BlockEntity, Error> {
+        Ok(BlockEntity {
+            packed_xz: self.packed_xz,
+            y: self.y,
+            kind: self.kind,
+            data: self.data.to_owned()?,
+        })
+    }
+}
+
+/// One light level per block of a section, two to a byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightArray(pub Vec<u8>);
+
+impl LightArray {
+    pub const LEN: usize = 2048;
+
+    /// The light level at coordinates within the section, each from 0 to 15
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        let index = (y * 16 + z) * 16 + x;
+        (self.0[index / 2] >> ((index % 2) * 4)) & 0xF
+    }
+}
+
+impl MinecraftData for LightArray {
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let len = decode_len(reader, Self::LEN)?;
+        if len != Self::LEN {
+            return Err(anyhow!(
+                "light array of {len} bytes instead of {}",
+                Self::LEN
+            ));
+        }
+        let mut data = vec![0; Self::LEN];
+        reader.read_exact(&mut data)?;
+        Ok(LightArray(data))
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        if self.0.len() != Self::LEN {
+            return Err(anyhow!(
+                "light array of {} bytes instead of {}",
+                self.0.len(),
+                Self::LEN
+            ));
+        }
+        VarInt(Self::LEN as i32).encode(writer)?;
+        writer.write_all(&self.0)?;
+        Ok(())
+    }
+
+    fn num_bytes(&self) -> usize {
+        VarInt(Self::LEN as i32).num_bytes() + self.0.len()
+    }
+}
+
+/// Sky and block light for the sections of a chunk, plus one below and one above the world.
+///
+/// Sections whose bit is set in a mask have an array, in order. Bits in the empty masks mean the
+/// section is all zeros; sections in neither mask were not sent and keep the light they had.
+#[derive(Debug, Clone, Default, PartialEq, Eq, MinecraftData)]
+pub struct LightData {
+    pub sky_light_mask: BitSet,
+    pub block_light_mask: BitSet,
+    pub empty_sky_light_mask: BitSet,
+    pub empty_block_light_mask: BitSet,
+    pub sky_light: Vec<LightArray>,
+    pub block_light: Vec<LightArray>,
+}
+
+impl LightData {
+    /// The sky light of a section, counting from the one below the world
+    pub fn sky_light(&self, section: usize) -> Option<&LightArray> {
+        Self::array(&self.sky_light_mask, &self.sky_light, section)
+    }
+
+    /// The block light of a section, counting from the one below the world
+    pub fn block_light(&self, section: usize) -> Option<&LightArray> {
+        Self::array(&self.block_light_mask, &self.block_light, section)
+    }
+
+    /// Applies Update Light: sections in a mask are replaced, sections in an empty mask are
+    /// cleared and the rest are kept
+    pub fn update(&mut self, update: LightData) {
+        let (sky_light_mask, empty_sky_light_mask, sky_light) = Self::merge(
+            (
+                &self.sky_light_mask,
+                &self.empty_sky_light_mask,
+                &mut self.sky_light,
+            ),
+            (
+                &update.sky_light_mask,
+                &update.empty_sky_light_mask,
+                update.sky_light,
+            ),
+        );
+        let (block_light_mask, empty_block_light_mask, block_light) = Self::merge(
+            (
+                &self.block_light_mask,
+                &self.empty_block_light_mask,
+                &mut self.block_light,
+            ),
+            (
+                &update.block_light_mask,
+                &update.empty_block_light_mask,
+                update.block_light,
+            ),
+        );
+        *self = LightData {
+            sky_light_mask,
+            block_light_mask,
+            empty_sky_light_mask,
+            empty_block_light_mask,
+            sky_light,
+            block_light,
+        };
+    }
+
+    fn merge(
+        (mask, empty, arrays): (&BitSet, &BitSet, &mut Vec<LightArray>),
+        (new_mask, new_empty, new_arrays): (&BitSet, &BitSet, Vec<LightArray>),
+    ) -> (BitSet, BitSet, Vec<LightArray>) {
+        // every section any of the masks has room for, set or not
+        let sections = mask
+            .bit_len()
+            .max(empty.bit_len())
+            .max(new_mask.bit_len())
+            .max(new_empty.bit_len());
+        let mut old_arrays = std::mem::take(arrays).into_iter();
+        let mut new_arrays = new_arrays.into_iter();
+        let mut merged = (BitSet::default(), BitSet::default(), Vec::new());
+        for section in 0..sections {
+            let old = mask.get(section).then(|| old_arrays.next()).flatten();
+            let new = new_mask.get(section).then(|| new_arrays.next()).flatten();
+            if let Some(array) = new.or(if new_empty.get(section) { None } else { old }) {
+                merged.0.set(section, true);
+                merged.2.push(array);
+            } else if new_empty.get(section) || empty.get(section) {
+                merged.1.set(section, true);
+            }
+        }
+        merged
+    }
+
+    fn array<'a>(
+        mask: &BitSet,
+        arrays: &'a [LightArray],
+        section: usize,
+    ) -> Option<&'a LightArray> {
+        if !mask.get(section) {
+            return None;
+        }
+        arrays.get(mask.iter_ones().take_while(|&i| i < section).count())
+    }
+}
+
+/// What decoding chunks needs to know about the current dimension, from the registries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimension {
+    pub min_y: i32,
+    pub height: u32,
+    /// Entries in `minecraft:worldgen/biome`, which sizes direct biome palettes
+    pub biome_count: usize,
+}
+
+impl Dimension {
+    /// Looks up the `minecraft:dimension_type` entry sent in Login or Respawn
+    pub fn from_registries(registries: &Registries, dimension_type: usize) -> Result<Self, Error> {
+        let entry = registries
+            .entry("minecraft:dimension_type", dimension_type)
+            .ok_or_else(|| anyhow!("no dimension type {dimension_type}"))?;
+        let data = entry
+            .data
+            .as_ref()
+            .ok_or_else(|| anyhow!("no data for dimension type {}", entry.id))?;
+        let int = |key: &str| {
+            data.get(key)
+                .and_then(Nbt::as_i64)
+                .ok_or_else(|| anyhow!("dimension type {} has no {key}", entry.id))
+        };
+        let min_y = int("min_y")? as i32;
+        let height = int("height")?;
+        if height <= 0 || height % 16 != 0 || height > 4064 {
+            return Err(anyhow!("dimension type {} has height {height}", entry.id));
+        }
+        let biome_count = registries
+            .registry("minecraft:worldgen/biome")
+            .ok_or_else(|| anyhow!("no biome registry"))?
+            .len();
+        Ok(Self {
+            min_y,
+            height: height as u32,
+            biome_count,
+        })
+    }
+
+    pub fn section_count(&self) -> usize {
+        self.height as usize / 16
+    }
+
+    pub fn biome_strategy(&self) -> PaletteStrategy {
+        PaletteStrategy::biomes(self.biome_count)
+    }
+}
+
+/// A decoded chunk column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub x: i32,
+    pub z: i32,
+    pub min_y: i32,
+    /// From the bottom of the world up
+    pub sections: Vec<ChunkSection>,
+    pub heightmaps: Vec<Heightmap>,
+    pub block_entities: Vec<BlockEntity>,
+    pub light: LightData,
+}
+
+impl Chunk {
+    pub fn from_packet(
+        packet: ChunkDataAndUpdateLightPacket,
+        dimension: &Dimension,
+    ) -> Result<Self, Error> {
+        let sections = ChunkSection::decode_all(&packet.data, dimension)?;
+        Ok(Self {
+            x: packet.chunk_x,
+            z: packet.chunk_z,
+            min_y: dimension.min_y,
+            sections,
+            heightmaps: packet.heightmaps,
+            block_entities: packet.block_entities,
+            light: packet.light,
+        })
+    }
+
+    /// Changes a block at `y` and x and z within the chunk, returning false outside the world
+    pub fn set_block_state(&mut self, x: usize, y: i32, z: usize, state: i32) -> bool {
+        let Ok(offset) = usize::try_from(y - self.min_y) else {
+            return false;
+        };
+        match self.sections.get_mut(offset / 16) {
+            Some(section) => {
+                section.set_block_state(x, offset % 16, z, state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The block state at `y` and x and z within the chunk, or `None` outside the world
+    pub fn block_state(&self, x: usize, y: i32, z: usize) -> Option<i32> {
+        let offset = usize::try_from(y - self.min_y).ok()?;
+        let section = self.sections.get(offset / 16)?;
+        Some(section.block_state(x, offset % 16, z))
+    }
+}
@@ -1,70 +1,731 @@
 use std::{
-    io::{BufReader, BufWriter, Read, Write},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
     net::TcpStream,
+    time::{Duration, Instant},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use crate::{
-    datatypes::{MinecraftData, VarInt, UUID},
+    block::BLOCKS_VERSION,
+    chunk::Dimension,
+    cookie::{Cookie, CookieJar},
+    data_pack::DataPackCatalog,
+    datatypes::{Identifier, MinecraftDataRef, Position, RemainingBytes, VarInt, UUID},
+    entity::Entities,
     packet::{
-        decode_packet_header, AcknowledgeFinishConfigurationPacket, ChangeDifficultyPacket,
-        ClientboundConfigurationPluginMessagePacket, ClientboundKnownPacksPacket,
-        ClientboundPlayKeepAlivePacket, ClientboundPlayerAbilitiesPacket,
-        ClientboundSetHeldItemPacket, ConfigurationKeepAlivePacket, ConfigurationUpdateTagsPacket,
-        FeatureFlagsPacket, FinishConfigurationPacket, HandshakeIntent, HandshakePacket,
-        LoginAcknowledgedPacket, LoginStartPacket, LoginSuccessPacket, Packet, PacketHeader,
-        PlayLoginPacket, RegistryDataPacket, ServerboundKnownPacksPacket,
-        ServerboundPlayKeepAlivePacket, SetHealthPacket, StatusRequestPacket, StatusResponsePacket,
-        UpdateRecipesPacket,
+        decode_packet_body, decode_packet_body_ref, decode_packet_header,
+        AcknowledgeFinishConfigurationPacket, BlockUpdatePacket, ChangeDifficultyPacket,
+        ChannelList, ChunkBatchFinishedPacket, ChunkBatchReceivedPacket, ChunkBatchStartPacket,
+        ChunkDataAndUpdateLightPacket, ClientSettings, ClientboundConfigurationPluginMessagePacket,
+        ClientboundKnownPacksPacket, ClientboundPlayKeepAlivePacket,
+        ClientboundPlayPluginMessagePacket, ClientboundPlayerAbilitiesPacket,
+        ClientboundSetHeldItemPacket, ConfigurationAcknowledgedPacket,
+        ConfigurationAddResourcePackPacket, ConfigurationClientInformationPacket,
+        ConfigurationCookieRequestPacket, ConfigurationCookieResponsePacket,
+        ConfigurationDisconnectPacket, ConfigurationKeepAlivePacket,
+        ConfigurationRemoveResourcePackPacket, ConfigurationResourcePackResponsePacket,
+        ConfigurationStoreCookiePacket, ConfigurationTransferPacket, ConfigurationUpdateTagsPacket,
+        ConfirmTeleportationPacket, FeatureFlagsPacket, FinishConfigurationPacket, HandshakeIntent,
+        HandshakePacket, LoginAcknowledgedPacket, LoginCookieRequestPacket,
+        LoginCookieResponsePacket, LoginDisconnectPacket, LoginPluginRequestPacket,
+        LoginPluginResponsePacket, LoginStartPacket, LoginSuccessPacket, MovementFlags, Packet,
+        PacketHeader, PlayAddResourcePackPacket, PlayClientInformationPacket,
+        PlayCookieRequestPacket, PlayCookieResponsePacket, PlayDisconnectPacket, PlayLoginPacket,
+        PlayRemoveResourcePackPacket, PlayResourcePackResponsePacket, PlayStoreCookiePacket,
+        PlayTransferPacket, PlayerAction, PlayerCommandPacket, PlayerInputPacket,
+        PlayerLoadedPacket, PluginChannelData, RegistryDataPacket, RegistryDataPacketRef,
+        RegistryEntry, RemoveEntitiesPacket, ResourcePackPush, RespawnPacket,
+        SectionBlocksUpdatePacket, ServerboundConfigurationPluginMessagePacket,
+        ServerboundKnownPacksPacket, ServerboundPlayKeepAlivePacket,
+        ServerboundPlayPluginMessagePacket, SetEntityMetadataPacket, SetEntityVelocityPacket,
+        SetHeadRotationPacket, SetHealthPacket, SetPlayerPositionAndRotationPacket,
+        SpawnEntityPacket, StartConfigurationPacket, StatusRequestPacket, StatusResponsePacket,
+        SynchronizePlayerPositionPacket, TeleportEntityPacket, UnloadChunkPacket,
+        UpdateEntityPositionAndRotationPacket, UpdateEntityPositionPacket,
+        UpdateEntityRotationPacket, UpdateLightPacket, UpdateRecipesPacket,
     },
+    pathfinding::{NavigationStatus, Navigator, PathCosts},
+    player::{Input, MovementPacket, MovementReporter, PlayerState},
+    plugin::{LoginPluginHandler, LoginPluginHandlers, PluginChannels},
+    protocol::{status_protocol, Direction, ProtocolVersion},
+    registry::Registries,
+    resource_pack::ResourcePacks,
+    text::plain_text,
+    world::{chunk_of, World},
 };
 
 const DEBUG_SENT_PACKETS: bool = false;
 
+/// Time between ticks of the play loop, which vanilla runs 20 times a second
+pub const TICK: Duration = Duration::from_millis(50);
+
+type TickHandler = Box<dyn FnMut(&mut Connection) -> Result<()> + Send>;
+
+/// Vanilla servers send a keep-alive every 15 seconds, so this allows for one to go missing
+pub const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Longest a single read may block, `None` to wait forever
+    pub read: Option<Duration>,
+    pub write: Option<Duration>,
+    /// Longest to go without a clientbound keep-alive in configuration and play, `None` to
+    /// never give up
+    pub keep_alive: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            read: None,
+            write: None,
+            keep_alive: Some(DEFAULT_KEEP_ALIVE_TIMEOUT),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeepAliveMetrics {
+    /// Keep-alives answered on this connection
+    pub count: u64,
+    pub last_received: Option<Instant>,
+    /// Time between the last two keep-alives
+    pub interval: Option<Duration>,
+    /// Time from the last keep-alive arriving to our answer being flushed. This is only the time
+    /// we took to answer, not a round trip: the ping the server measures also includes the
+    /// network both ways.
+    pub answer_delay: Option<Duration>,
+}
+
+/// The error returned, inside an `anyhow::Error`, when the server disconnects us with a reason
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disconnected {
+    /// Plain text of the reason, or the raw JSON text component during login
+    pub reason: String,
+}
+
+impl std::fmt::Display for Disconnected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "disconnected by the server: {}", self.reason)
+    }
+}
+
+impl std::error::Error for Disconnected {}
+
+/// The protocol state, which decides which packet ids mean what
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Handshaking,
+    Login,
+    Configuration,
+    Play,
+}
+
 pub struct Connection {
     host: String,
     port: u16,
     writer: BufWriter<TcpStream>,
     reader: BufReader<TcpStream>,
+    /// Body of the last packet received, reused between packets
+    frame: Vec<u8>,
+    /// Cookies stored by the server, kept across transfers
+    cookies: CookieJar,
+    /// Responders for Login Plugin Requests, also kept across transfers
+    login_plugins: LoginPluginHandlers,
+    plugin_channels: PluginChannels,
+    state: ConnectionState,
+    /// Decides the handshake protocol number, packet ids and version-gated fields
+    version: ProtocolVersion,
+    /// What the status ping reported, when `version` was chosen from it
+    server_protocol: Option<i32>,
+    /// Sent on `minecraft:brand` when configuration starts
+    brand: String,
+    server_brand: Option<String>,
+    client_settings: ClientSettings,
+    registries: Registries,
+    /// Chunks in the current dimension, which Login and Respawn in play pick from the registries
+    world: World,
+    /// Our own position, known once the server first synchronizes it in play
+    player: Option<PlayerState>,
+    /// Entities around us, our own player included under the entity id from Login in play
+    entities: Entities,
+    /// Movement keys held, applied every tick
+    input: Input,
+    movement: MovementReporter,
+    /// Where `goto` is taking us, steering the input every tick until it is over
+    navigator: Option<Navigator>,
+    path_costs: PathCosts,
+    /// Whether Player Loaded has been sent since the last login or respawn
+    player_loaded: bool,
+    next_tick: Instant,
+    on_tick: Option<TickHandler>,
+    timeouts: Timeouts,
+    /// When the keep-alive watchdog started waiting, reset by every keep-alive
+    keep_alive_since: Instant,
+    keep_alive_metrics: KeepAliveMetrics,
+    resource_packs: ResourcePacks,
+    /// Data packs we can claim in Known Packs
+    data_packs: DataPackCatalog,
 }
 
 impl Connection {
     pub fn connect(host: String, port: u16) -> Result<Connection> {
+        Self::connect_with_version(host, port, ProtocolVersion::LATEST)
+    }
+
+    /// Pings the server for its protocol number and connects again with the closest version we
+    /// support, ready to log in
+    pub fn connect_auto(host: String, port: u16) -> Result<Connection> {
+        let status = Self::connect(host.clone(), port)?.get_status()?;
+        let protocol = status_protocol(&status)
+            .ok_or_else(|| anyhow!("no protocol version in status response: {status}"))?;
+        let mut conn = Self::connect_with_version(host, port, ProtocolVersion::closest(protocol))?;
+        conn.server_protocol = Some(protocol);
+        Ok(conn)
+    }
+
+    /// Connects to a server running an older version, see [`ProtocolVersion::from_status`]
+    pub fn connect_with_version(
+        host: String,
+        port: u16,
+        version: ProtocolVersion,
+    ) -> Result<Connection> {
         let stream = TcpStream::connect((host.as_str(), port))?;
         Ok(Connection {
             host,
             port,
             writer: BufWriter::new(stream.try_clone()?),
             reader: BufReader::new(stream),
+            frame: Vec::new(),
+            cookies: CookieJar::new(),
+            login_plugins: LoginPluginHandlers::new(),
+            plugin_channels: PluginChannels::new(),
+            state: ConnectionState::Handshaking,
+            version,
+            server_protocol: None,
+            brand: "vanilla".to_string(),
+            server_brand: None,
+            client_settings: ClientSettings::default(),
+            registries: Registries::new(),
+            world: World::new(),
+            player: None,
+            entities: Entities::new(),
+            input: Input::default(),
+            movement: MovementReporter::new(),
+            navigator: None,
+            path_costs: PathCosts::default(),
+            player_loaded: false,
+            next_tick: Instant::now(),
+            on_tick: None,
+            timeouts: Timeouts::default(),
+            keep_alive_since: Instant::now(),
+            keep_alive_metrics: KeepAliveMetrics::default(),
+            resource_packs: ResourcePacks::new(),
+            data_packs: DataPackCatalog::new(),
         })
     }
 
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Whether the server numbers block states like the bundled block table, which physics and
+    /// pathfinding rely on. Without it the player is left where the server puts it.
+    pub fn blocks_known(&self) -> bool {
+        self.version == BLOCKS_VERSION
+    }
+
+    /// The protocol number the server reported to [`connect_auto`](Self::connect_auto), which
+    /// differs from [`version`](Self::version) when we had to fall back to the closest one
+    pub fn server_protocol(&self) -> Option<i32> {
+        self.server_protocol
+    }
+
+    pub fn cookies(&self) -> &CookieJar {
+        &self.cookies
+    }
+
+    /// For example to restore cookies saved by a previous run before logging in
+    pub fn cookies_mut(&mut self) -> &mut CookieJar {
+        &mut self.cookies
+    }
+
+    /// Answers Login Plugin Requests on `channel`, for example to get through a proxy that
+    /// probes clients during login. Unhandled channels are answered as not understood.
+    pub fn register_login_plugin_handler(
+        &mut self,
+        channel: Identifier,
+        handler: impl LoginPluginHandler + 'static,
+    ) {
+        self.login_plugins.register(channel, handler);
+    }
+
+    pub fn login_plugins_mut(&mut self) -> &mut LoginPluginHandlers {
+        &mut self.login_plugins
+    }
+
+    /// Handlers for plugin messages in configuration and play. Register them before logging in
+    /// so that their channels are announced with `minecraft:register`.
+    pub fn plugin_channels_mut(&mut self) -> &mut PluginChannels {
+        &mut self.plugin_channels
+    }
+
+    pub fn plugin_channels(&self) -> &PluginChannels {
+        &self.plugin_channels
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        self.state = state;
+        // the server only starts sending keep-alives once we are in configuration
+        self.keep_alive_since = Instant::now();
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) -> Result<()> {
+        let stream = self.reader.get_ref();
+        stream.set_read_timeout(timeouts.read)?;
+        stream.set_write_timeout(timeouts.write)?;
+        self.timeouts = timeouts;
+        Ok(())
+    }
+
+    pub fn keep_alive_metrics(&self) -> KeepAliveMetrics {
+        self.keep_alive_metrics
+    }
+
+    /// Call when a keep-alive arrives, and `keep_alive_answered` once the answer is flushed
+    fn keep_alive_received(&mut self) -> Instant {
+        let now = Instant::now();
+        let metrics = &mut self.keep_alive_metrics;
+        metrics.interval = metrics.last_received.map(|last| now - last);
+        metrics.last_received = Some(now);
+        self.keep_alive_since = now;
+        now
+    }
+
+    fn keep_alive_answered(&mut self, received: Instant) {
+        self.keep_alive_metrics.count += 1;
+        self.keep_alive_metrics.answer_delay = Some(received.elapsed());
+    }
+
+    pub fn set_brand(&mut self, brand: String) {
+        self.brand = brand;
+    }
+
+    /// The dimension we are in, once Login in play has arrived
+    pub fn dimension(&self) -> Option<&Dimension> {
+        self.world.dimension()
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn player(&self) -> Option<&PlayerState> {
+        self.player.as_ref()
+    }
+
+    pub fn entity_id(&self) -> Option<i32> {
+        self.entities.local_player_id()
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    /// Holds down movement keys until changed: `forward` and `strafe` (positive to the left) go
+    /// from -1 to 1
+    pub fn set_input(&mut self, forward: f32, strafe: f32, jump: bool, sneak: bool, sprint: bool) {
+        self.input = Input {
+            forward,
+            strafe,
+            jump,
+            sneak,
+            sprint,
+        };
+    }
+
+    pub fn input(&self) -> Input {
+        self.input
+    }
+
+    /// Turns to face a direction, in degrees, from the next tick on
+    pub fn set_rotation(&mut self, yaw: f32, pitch: f32) {
+        if let Some(player) = &mut self.player {
+            player.yaw = yaw;
+            player.pitch = pitch.clamp(-90.0, 90.0);
+        }
+    }
+
+    /// Plans a path from where we stand to a position to stand at, which the play loop then
+    /// follows by steering the input. Fails with [`Unreachable`](crate::pathfinding::Unreachable)
+    /// if there is no path over the loaded chunks.
+    pub fn goto(&mut self, goal: Position) -> Result<()> {
+        if !self.blocks_known() {
+            return Err(anyhow!(
+                "pathfinding needs the block states of protocol {}, but we speak {}",
+                BLOCKS_VERSION.protocol(),
+                self.version.protocol()
+            ));
+        }
+        let player = self
+            .player
+            .as_ref()
+            .ok_or_else(|| anyhow!("cannot go anywhere before the server places us"))?;
+        self.navigator = Some(Navigator::new(&self.world, player, goal, self.path_costs)?);
+        Ok(())
+    }
+
+    /// Stops following the path from `goto`, releasing the movement keys
+    pub fn stop(&mut self) {
+        if self.navigator.take().is_some() {
+            self.input = Input::default();
+        }
+    }
+
+    pub fn navigator(&self) -> Option<&Navigator> {
+        self.navigator.as_ref()
+    }
+
+    /// How the last `goto` is going, or `None` if there was none
+    pub fn navigation_status(&self) -> Option<NavigationStatus> {
+        self.navigator.as_ref().map(Navigator::status)
+    }
+
+    pub fn path_costs(&self) -> PathCosts {
+        self.path_costs
+    }
+
+    pub fn set_path_costs(&mut self, costs: PathCosts) {
+        self.path_costs = costs;
+    }
+
+    /// Runs at the start of every tick of the play loop, before physics, for example to steer
+    /// with [`set_input`](Self::set_input)
+    pub fn on_tick(&mut self, on_tick: impl FnMut(&mut Connection) -> Result<()> + Send + 'static) {
+        self.on_tick = Some(Box::new(on_tick));
+    }
+
+    /// Registries and tags from the last configuration
+    pub fn registries(&self) -> &Registries {
+        &self.registries
+    }
+
+    pub fn data_packs(&self) -> &DataPackCatalog {
+        &self.data_packs
+    }
+
+    /// Add packs here before configuration to have the server leave their contents out
+    pub fn data_packs_mut(&mut self) -> &mut DataPackCatalog {
+        &mut self.data_packs
+    }
+
+    pub fn resource_packs(&self) -> &ResourcePacks {
+        &self.resource_packs
+    }
+
+    /// For choosing how Add Resource Pack is answered and setting a fetcher
+    pub fn resource_packs_mut(&mut self) -> &mut ResourcePacks {
+        &mut self.resource_packs
+    }
+
+    pub fn client_settings(&self) -> &ClientSettings {
+        &self.client_settings
+    }
+
+    /// Changes our Client Information, telling the server straight away if we are in
+    /// configuration or play, otherwise when configuration starts.
+    pub fn set_client_settings(&mut self, settings: ClientSettings) -> Result<()> {
+        self.client_settings = settings;
+        match self.state {
+            ConnectionState::Configuration | ConnectionState::Play => self.send_client_settings(),
+            _ => Ok(()),
+        }
+    }
+
+    fn send_client_settings(&mut self) -> Result<()> {
+        let settings = self.client_settings.clone();
+        match self.state {
+            ConnectionState::Configuration => {
+                self.send_packet(ConfigurationClientInformationPacket { settings })
+            }
+            ConnectionState::Play => self.send_packet(PlayClientInformationPacket { settings }),
+            state => Err(anyhow!(
+                "cannot send client information in the {state:?} state"
+            )),
+        }
+    }
+
+    /// What the server sent on `minecraft:brand`, if anything
+    pub fn server_brand(&self) -> Option<&str> {
+        self.server_brand.as_deref()
+    }
+
+    /// Sends a plugin message in the current state, which must be configuration or play
+    pub fn send_plugin_message(&mut self, data: PluginChannelData) -> Result<()> {
+        match self.state {
+            ConnectionState::Configuration => {
+                self.send_packet(ServerboundConfigurationPluginMessagePacket { data })
+            }
+            ConnectionState::Play => self.send_packet(ServerboundPlayPluginMessagePacket { data }),
+            state => Err(anyhow!(
+                "cannot send plugin messages in the {state:?} state"
+            )),
+        }
+    }
+
+    /// Tells the server which channels we have handlers for
+    pub fn announce_plugin_channels(&mut self) -> Result<()> {
+        let mut channels: Vec<_> = self.plugin_channels.channels().cloned().collect();
+        if channels.is_empty() {
+            return Ok(());
+        }
+        channels.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        self.send_plugin_message(PluginChannelData::Register(ChannelList(channels)))
+    }
+
+    fn handle_plugin_message(&mut self, data: PluginChannelData) -> Result<()> {
+        match data {
+            PluginChannelData::MinecraftBrand(brand) => {
+                self.server_brand = Some(brand.into_inner());
+            }
+            PluginChannelData::Register(channels) => {
+                self.plugin_channels.server_registered(channels.0);
+            }
+            PluginChannelData::Unregister(channels) => {
+                self.plugin_channels.server_unregistered(channels.0);
+            }
+            PluginChannelData::Unknown(channel, data) => {
+                if let Some(reply) = self.plugin_channels.handle(&channel, &data)? {
+                    self.send_plugin_message(PluginChannelData::Unknown(channel, reply))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push_resource_pack(&mut self, pack: ResourcePackPush) -> Result<()> {
+        for result in self.resource_packs.push(&pack) {
+            let uuid = pack.uuid;
+            match self.state {
+                ConnectionState::Configuration => {
+                    self.send_packet(ConfigurationResourcePackResponsePacket { uuid, result })?
+                }
+                ConnectionState::Play => {
+                    self.send_packet(PlayResourcePackResponsePacket { uuid, result })?
+                }
+                state => {
+                    return Err(anyhow!(
+                        "cannot answer resource packs in the {state:?} state"
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Follows a Transfer packet: connects to the new server, logs in with the transfer intent so
+    /// it can ask for our cookies, and runs configuration again.
+    pub fn transfer(&mut self, host: String, port: u16) -> Result<()> {
+        let stream = TcpStream::connect((host.as_str(), port))?;
+        stream.set_read_timeout(self.timeouts.read)?;
+        stream.set_write_timeout(self.timeouts.write)?;
+        self.writer = BufWriter::new(stream.try_clone()?);
+        self.reader = BufReader::new(stream);
+        self.host = host;
+        self.port = port;
+        self.leave_play();
+
+        self.login_with_intent(HandshakeIntent::Transfer)?;
+        self.configure()
+    }
+
     fn send_packet<P: Packet>(&mut self, packet: P) -> Result<()> {
+        let version = self.version;
+        let id = version
+            .packet_id(self.state, Direction::Serverbound, P::ID)
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} does not exist in protocol {}",
+                    std::any::type_name::<P>(),
+                    version.protocol()
+                )
+            })?;
         if DEBUG_SENT_PACKETS {
             let mut bytes = Vec::new();
-            packet.encode_packet(&mut bytes)?;
+            packet.encode_packet_with_id(version, id, &mut bytes)?;
             eprintln!("{:?}", bytes);
             self.writer.write_all(&bytes)?;
         } else {
-            packet.encode_packet(&mut self.writer)?;
+            packet.encode_packet_with_id(version, id, &mut self.writer)?;
         }
         self.writer.flush()?;
         Ok(())
     }
 
-    fn recv_packet_header(&mut self) -> Result<PacketHeader> {
-        decode_packet_header(&mut self.reader)
+    pub fn recv_packet_header(&mut self) -> Result<PacketHeader> {
+        if self.reader.buffer().is_empty() {
+            self.wait_for_data()?;
+        }
+        self.limit_frame_read()?;
+        let mut header =
+            decode_packet_header(&mut self.reader).map_err(|err| self.frame_read_failed(err))?;
+        header.id = self
+            .version
+            .latest_packet_id(self.state, Direction::Clientbound, header.id);
+        // a server that keeps sending other packets must still send keep-alives in time
+        let is_keep_alive = match self.state {
+            ConnectionState::Configuration => header.id == ConfigurationKeepAlivePacket::ID,
+            ConnectionState::Play => header.id == ClientboundPlayKeepAlivePacket::ID,
+            _ => false,
+        };
+        if !is_keep_alive
+            && self
+                .keep_alive_deadline()
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(self.keep_alive_timed_out());
+        }
+        Ok(header)
+    }
+
+    /// When the watchdog gives up if no keep-alive arrives, in the states that have them
+    fn keep_alive_deadline(&self) -> Option<Instant> {
+        match (self.state, self.timeouts.keep_alive) {
+            (ConnectionState::Configuration | ConnectionState::Play, Some(timeout)) => {
+                Some(self.keep_alive_since + timeout)
+            }
+            _ => None,
+        }
+    }
+
+    fn keep_alive_timed_out(&self) -> anyhow::Error {
+        anyhow!(
+            "timed out: no keep-alive from the server for {:?}",
+            self.keep_alive_since.elapsed()
+        )
+    }
+
+    /// Bounds each read of a frame by the keep-alive watchdog as well as the read timeout, so
+    /// that a server going quiet partway through a frame cannot block us past the deadline
+    fn limit_frame_read(&self) -> Result<()> {
+        let timeout = match self.keep_alive_deadline() {
+            Some(deadline) => {
+                // a zero timeout is rejected, and means the deadline has already passed anyway
+                let left = deadline
+                    .saturating_duration_since(Instant::now())
+                    .max(Duration::from_millis(1));
+                Some(self.timeouts.read.map_or(left, |read| read.min(left)))
+            }
+            None => self.timeouts.read,
+        };
+        self.reader.get_ref().set_read_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Says why a read partway through a frame timed out, passing other errors through
+    fn frame_read_failed(&self, err: anyhow::Error) -> anyhow::Error {
+        let timed_out = err
+            .chain()
+            .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+            .any(|err| matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut));
+        if !timed_out {
+            err
+        } else if self
+            .keep_alive_deadline()
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.keep_alive_timed_out()
+        } else {
+            anyhow!("timed out waiting for the server")
+        }
+    }
+
+    /// Blocks until the server sends something, giving up when the read timeout passes or the
+    /// keep-alive watchdog runs out.
+    fn wait_for_data(&mut self) -> Result<()> {
+        self.wait_for_data_until(Instant::now(), None).map(|_| ())
+    }
+
+    /// Like `wait_for_data`, but also stops waiting at `until`, returning false if nothing
+    /// arrived by then. The read timeout counts from `since`, so that it still applies across
+    /// waits cut short for ticks.
+    fn wait_for_data_until(&mut self, since: Instant, until: Option<Instant>) -> Result<bool> {
+        let keep_alive_deadline = self.keep_alive_deadline();
+        let read_deadline = self.timeouts.read.map(|read| since + read);
+        let timeout = [keep_alive_deadline, read_deadline, until]
+            .into_iter()
+            .flatten()
+            .min()
+            .map(|deadline| {
+                // a zero timeout is rejected, and means the deadline has already passed anyway
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .max(Duration::from_millis(1))
+            });
+
+        self.reader.get_ref().set_read_timeout(timeout)?;
+        let result = self.reader.fill_buf().map(|buf| buf.is_empty());
+        self.reader.get_ref().set_read_timeout(self.timeouts.read)?;
+        match result {
+            Ok(false) => Ok(true),
+            Ok(true) => Err(anyhow!("connection closed by the server")),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                let now = Instant::now();
+                match keep_alive_deadline {
+                    Some(deadline) if now >= deadline => Err(self.keep_alive_timed_out()),
+                    _ if until.is_some() && read_deadline.is_none_or(|deadline| now < deadline) => {
+                        Ok(false)
+                    }
+                    _ => Err(anyhow!("timed out waiting for the server")),
+                }
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Reads the body of the packet into the reused frame buffer
+    fn recv_frame(&mut self, header: &PacketHeader) -> Result<&[u8]> {
+        self.limit_frame_read()?;
+        self.frame.resize(header.body_len(), 0);
+        if let Err(err) = self.reader.read_exact(&mut self.frame) {
+            return Err(self.frame_read_failed(err.into()));
+        }
+        Ok(&self.frame)
+    }
+
+    /// Reads the whole body first so that nothing in it can make us read past the frame
+    fn recv_packet<P: Packet>(&mut self, header: &PacketHeader) -> Result<P> {
+        let version = self.version;
+        let body = self.recv_frame(header)?;
+        decode_packet_body(body, version)
     }
 
-    fn recv_packet<P: Packet>(&mut self) -> Result<P> {
-        P::decode_packet(&mut self.reader)
+    /// Like `recv_packet`, but the packet may borrow from the frame buffer until the next one
+    /// is received.
+    pub fn recv_packet_ref<'a, P: MinecraftDataRef<'a>>(
+        &'a mut self,
+        header: &PacketHeader,
+    ) -> Result<P> {
+        let version = self.version;
+        let body = self.recv_frame(header)?;
+        decode_packet_body_ref(body, version)
     }
 
     fn recv_packet_raw(&mut self, header: &PacketHeader) -> Result<Vec<u8>> {
-        let mut res = vec![0u8; header.len.0 as usize - header.id.num_bytes()];
-        self.reader.read_exact(&mut res)?;
-        Ok(res)
+        Ok(self.recv_frame(header)?.to_vec())
     }
 
     /// Takes self because this closes the connection
@@ -77,22 +738,25 @@ impl Connection {
         })?;
         self.send_packet(StatusRequestPacket)?;
 
-        // TODO: maybe read first and then read exactly enough bytes?
-        //       or at least validate this explicitly
-        let _resp_header = self.recv_packet_header()?;
+        let resp_header = self.recv_packet_header()?;
         // eprintln!("{resp_header:?}");
-        let resp = self.recv_packet::<StatusResponsePacket>()?;
+        let resp = self.recv_packet::<StatusResponsePacket>(&resp_header)?;
         // eprintln!("{resp:?}");
         Ok(resp.json_response.to_string())
     }
 
     pub fn login(&mut self) -> Result<()> {
+        self.login_with_intent(HandshakeIntent::Login)
+    }
+
+    fn login_with_intent(&mut self, intent: HandshakeIntent) -> Result<()> {
         self.send_packet(HandshakePacket {
-            protocol_version: VarInt(773),
+            protocol_version: VarInt(self.version.protocol()),
             server_address: self.host.clone().try_into()?,
             server_port: self.port,
-            intent: HandshakeIntent::Login,
+            intent,
         })?;
+        self.set_state(ConnectionState::Login);
         self.send_packet(LoginStartPacket {
             name: "robotabc773".to_string().try_into()?,
             // uuid: UUID(0xcf766be42bed41bdb40ae0c22ac798f1),
@@ -102,7 +766,7 @@ impl Connection {
         // TODO: enable online mode and use authentication and encryption
         // let resp_header = self.recv_packet_header()?;
         // eprintln!("{:?}", resp_header);
-        // let resp = self.recv_packet::<EncryptionRequestPacket>()?;
+        // let resp = self.recv_packet::<EncryptionRequestPacket>(&resp_header)?;
         // eprintln!("{:?}", resp);
 
         // let key = RsaPublicKey::from_public_key_der(&resp.public_key)?;
@@ -110,61 +774,191 @@ impl Connection {
 
         // TODO: enable compression
 
-        let resp_header = self.recv_packet_header()?;
-        eprintln!("{:?}", resp_header);
-        let resp = self.recv_packet::<LoginSuccessPacket>()?;
-        eprintln!("{:?}", resp);
-        self.send_packet(LoginAcknowledgedPacket)?;
+        loop {
+            let resp_header = self.recv_packet_header()?;
+            eprintln!("{:?}", resp_header);
+            match resp_header.id {
+                val if val == LoginSuccessPacket::ID => {
+                    let resp = self.recv_packet::<LoginSuccessPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.send_packet(LoginAcknowledgedPacket)?;
+                    self.set_state(ConnectionState::Configuration);
+                    break;
+                }
+                val if val == LoginCookieRequestPacket::ID => {
+                    let resp = self.recv_packet::<LoginCookieRequestPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    let payload = self.cookies.get(&resp.key).map(<[u8]>::to_vec);
+                    self.send_packet(LoginCookieResponsePacket {
+                        key: resp.key,
+                        payload,
+                    })?;
+                }
+                val if val == LoginDisconnectPacket::ID => {
+                    let resp = self.recv_packet::<LoginDisconnectPacket>(&resp_header)?;
+                    return Err(Disconnected {
+                        reason: resp.reason.into_inner(),
+                    }
+                    .into());
+                }
+                val if val == LoginPluginRequestPacket::ID => {
+                    let resp = self.recv_packet::<LoginPluginRequestPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    let data = self.login_plugins.handle(&resp.channel, &resp.data.0);
+                    self.send_packet(LoginPluginResponsePacket {
+                        message_id: resp.message_id,
+                        data: data.map(RemainingBytes),
+                    })?;
+                }
+                _ => {
+                    let resp = self.recv_packet_raw(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    return Err(anyhow!("unexpected packet during login: {resp_header:?}"));
+                }
+            }
+        }
 
         Ok(())
     }
 
+    fn store_cookie(&mut self, cookie: Cookie) -> Result<()> {
+        self.cookies.insert(cookie)
+    }
+
+    /// Runs configuration, starting with our brand, plugin channels and client settings as
+    /// vanilla does.
     pub fn configure(&mut self) -> Result<()> {
+        self.registries.clear();
+        self.send_plugin_message(PluginChannelData::MinecraftBrand(
+            self.brand.clone().try_into()?,
+        ))?;
+        self.announce_plugin_channels()?;
+        self.send_client_settings()?;
+
         loop {
             let resp_header = self.recv_packet_header()?;
             match resp_header.id {
                 val if val == ClientboundConfigurationPluginMessagePacket::ID => {
-                    let resp = self.recv_packet::<ClientboundConfigurationPluginMessagePacket>()?;
+                    let resp = self
+                        .recv_packet::<ClientboundConfigurationPluginMessagePacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
+                    self.handle_plugin_message(resp.data)?;
                 }
                 val if val == FeatureFlagsPacket::ID => {
-                    let resp = self.recv_packet::<FeatureFlagsPacket>()?;
+                    let resp = self.recv_packet::<FeatureFlagsPacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
                 }
                 val if val == ClientboundKnownPacksPacket::ID => {
-                    let resp = self.recv_packet::<ClientboundKnownPacksPacket>()?;
+                    let resp = self.recv_packet::<ClientboundKnownPacksPacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
-                    self.send_packet(ServerboundKnownPacksPacket {
-                        known_packs: resp.known_packs,
-                    })?;
+                    // only claim what we can fill in, or the server leaves out data we need
+                    let known_packs = self.data_packs.negotiate(&resp.known_packs);
+                    self.registries.set_known_packs(known_packs.clone());
+                    self.send_packet(ServerboundKnownPacksPacket { known_packs })?;
                 }
                 val if val == ConfigurationKeepAlivePacket::ID => {
-                    let resp = self.recv_packet::<ConfigurationKeepAlivePacket>()?;
+                    let resp = self.recv_packet::<ConfigurationKeepAlivePacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
+                    let received = self.keep_alive_received();
                     self.send_packet(ConfigurationKeepAlivePacket {
                         keep_alive_id: resp.keep_alive_id,
                     })?;
+                    self.keep_alive_answered(received);
                 }
                 val if val == RegistryDataPacket::ID => {
-                    // let resp = self.recv_packet::<RegistryDataPacket>()?;
-                    let _resp = self.recv_packet_raw(&resp_header)?;
+                    let resp = self.recv_packet_ref::<RegistryDataPacketRef>(&resp_header)?;
                     // eprintln!("{:?}", resp);
+                    let registry_id = resp.registry_id.to_owned();
+                    let mut entries = resp
+                        .entries
+                        .iter()
+                        .map(|entry| {
+                            Ok(RegistryEntry {
+                                id: entry.id.to_owned(),
+                                data: entry
+                                    .data
+                                    .as_ref()
+                                    .map(|data| data.to_owned())
+                                    .transpose()?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    for entry in entries.iter_mut().filter(|entry| entry.data.is_none()) {
+                        let data = self
+                            .data_packs
+                            .resolve(
+                                self.registries.known_packs(),
+                                registry_id.as_str(),
+                                entry.id.as_str(),
+                            )
+                            .ok_or_else(|| anyhow!("no data for {} in {registry_id}", entry.id))?;
+                        entry.data = Some(data.clone());
+                    }
+                    self.registries.insert(registry_id, entries);
                 }
                 val if val == ConfigurationUpdateTagsPacket::ID => {
-                    let _resp = self.recv_packet::<ConfigurationUpdateTagsPacket>()?;
+                    let resp = self.recv_packet::<ConfigurationUpdateTagsPacket>(&resp_header)?;
                     // eprintln!("{:?}", resp);
+                    for tagged in resp.tagged_registries {
+                        self.registries.insert_tags(tagged.registry, tagged.tags);
+                    }
+                }
+                val if val == ConfigurationCookieRequestPacket::ID => {
+                    let resp =
+                        self.recv_packet::<ConfigurationCookieRequestPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    let payload = self.cookies.get(&resp.key).map(<[u8]>::to_vec);
+                    self.send_packet(ConfigurationCookieResponsePacket {
+                        key: resp.key,
+                        payload,
+                    })?;
+                }
+                val if val == ConfigurationStoreCookiePacket::ID => {
+                    let resp = self.recv_packet::<ConfigurationStoreCookiePacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.store_cookie(resp.cookie)?;
+                }
+                val if val == ConfigurationTransferPacket::ID => {
+                    let resp = self.recv_packet::<ConfigurationTransferPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    // the new server's configuration has finished once this returns
+                    self.transfer(
+                        resp.target.host.into_inner(),
+                        u16::try_from(resp.target.port.0)?,
+                    )?;
+                    break;
+                }
+                val if val == ConfigurationDisconnectPacket::ID => {
+                    let resp = self.recv_packet::<ConfigurationDisconnectPacket>(&resp_header)?;
+                    return Err(Disconnected {
+                        reason: plain_text(&resp.reason),
+                    }
+                    .into());
+                }
+                val if val == ConfigurationAddResourcePackPacket::ID => {
+                    let resp =
+                        self.recv_packet::<ConfigurationAddResourcePackPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.push_resource_pack(resp.pack)?;
+                }
+                val if val == ConfigurationRemoveResourcePackPacket::ID => {
+                    let resp =
+                        self.recv_packet::<ConfigurationRemoveResourcePackPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.resource_packs.pop(resp.uuid);
                 }
                 val if val == FinishConfigurationPacket::ID => {
-                    let resp = self.recv_packet::<FinishConfigurationPacket>()?;
+                    let resp = self.recv_packet::<FinishConfigurationPacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
                     self.send_packet(AcknowledgeFinishConfigurationPacket)?;
+                    self.set_state(ConnectionState::Play);
                     break;
                 }
                 _ => {
-                    eprintln!("{:?}", resp_header);
-                    let resp = self.recv_packet_raw(&resp_header)?;
-                    eprintln!("{:?}", resp);
-                    break;
+                    // eprintln!("{:?}", resp_header);
+                    // packets we do not handle yet are skipped, configuration only ends when
+                    // the server finishes it
+                    self.recv_packet_raw(&resp_header)?;
                 }
             }
         }
@@ -172,50 +966,326 @@ impl Connection {
         Ok(())
     }
 
+    /// Forgets our player until the server places it again, after login or respawn
+    fn reset_player(&mut self) {
+        self.player = None;
+        self.movement = MovementReporter::new();
+        self.player_loaded = false;
+        self.navigator = None;
+    }
+
+    /// Drops the player, world and entities of the server we are leaving play on, which sends
+    /// them again after the next Login
+    fn leave_play(&mut self) {
+        self.reset_player();
+        self.world.clear();
+        self.entities = Entities::new();
+    }
+
+    /// Moves our entity to where our player is
+    fn update_local_entity(&mut self) {
+        if let Some(player) = self.player
+            && let Some(entity) = self.entities.local_player_mut()
+        {
+            entity.position = player.position;
+            entity.velocity = player.velocity;
+            entity.yaw = player.yaw;
+            entity.pitch = player.pitch;
+            entity.head_yaw = player.yaw;
+            entity.on_ground = player.on_ground;
+        }
+    }
+
+    /// Runs a tick if one is due, skipping any that were missed rather than catching up
+    fn tick_if_due(&mut self) -> Result<()> {
+        let now = Instant::now();
+        if now < self.next_tick {
+            return Ok(());
+        }
+        self.next_tick += TICK;
+        if self.next_tick <= now {
+            self.next_tick = now + TICK;
+        }
+        self.tick()
+    }
+
+    /// One tick of the play loop: the tick hook, then physics and movement packets for our
+    /// player. Like vanilla, the player stays put until the chunk it is in has arrived.
+    fn tick(&mut self) -> Result<()> {
+        if let Some(mut on_tick) = self.on_tick.take() {
+            let result = on_tick(self);
+            self.on_tick.get_or_insert(on_tick);
+            result?;
+        }
+
+        let Some(mut player) = self.player else {
+            return Ok(());
+        };
+        let (chunk_x, chunk_z) = chunk_of(player.block_position());
+        if self.world.chunk(chunk_x, chunk_z).is_none() {
+            return Ok(());
+        }
+        if !self.player_loaded {
+            self.send_packet(PlayerLoadedPacket)?;
+            self.player_loaded = true;
+        }
+        if !self.blocks_known() {
+            // we would collide with the wrong blocks
+            return Ok(());
+        }
+
+        if let Some(navigator) = &mut self.navigator
+            && navigator.status() == NavigationStatus::Moving
+        {
+            self.input = navigator.steer(&mut player, &self.world);
+        }
+        player.tick(&self.input, &self.world);
+        self.player = Some(player);
+        self.update_local_entity();
+
+        if self.movement.input_changed(&self.input) {
+            self.send_packet(PlayerInputPacket {
+                flags: self.input.flags(),
+            })?;
+        }
+        if self.movement.sprint_changed(player.sprinting) {
+            self.send_packet(PlayerCommandPacket {
+                entity_id: VarInt(self.entity_id().unwrap_or_default()),
+                action: if player.sprinting {
+                    PlayerAction::StartSprinting
+                } else {
+                    PlayerAction::StopSprinting
+                },
+                jump_boost: VarInt(0),
+            })?;
+        }
+        match self.movement.report(&player) {
+            Some(MovementPacket::Position(packet)) => self.send_packet(packet),
+            Some(MovementPacket::PositionAndRotation(packet)) => self.send_packet(packet),
+            Some(MovementPacket::Rotation(packet)) => self.send_packet(packet),
+            Some(MovementPacket::Flags(packet)) => self.send_packet(packet),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the play loop. Only returns with an error: the server disconnecting us, the tick hook
+    /// failing, or the connection going away.
     pub fn play(&mut self) -> Result<()> {
+        self.next_tick = Instant::now() + TICK;
+        let mut waiting_since = Instant::now();
         loop {
+            self.tick_if_due()?;
+            if self.reader.buffer().is_empty()
+                && !self.wait_for_data_until(waiting_since, Some(self.next_tick))?
+            {
+                continue;
+            }
             let resp_header = self.recv_packet_header()?;
+            waiting_since = Instant::now();
             match resp_header.id {
                 val if val == ClientboundPlayKeepAlivePacket::ID => {
-                    let resp = self.recv_packet::<ClientboundPlayKeepAlivePacket>()?;
+                    let resp = self.recv_packet::<ClientboundPlayKeepAlivePacket>(&resp_header)?;
                     // eprintln!("{:?}", resp);
+                    let received = self.keep_alive_received();
                     self.send_packet(ServerboundPlayKeepAlivePacket {
                         keep_alive_id: resp.keep_alive_id,
                     })?;
+                    self.keep_alive_answered(received);
+                }
+                val if val == ClientboundPlayPluginMessagePacket::ID => {
+                    let resp =
+                        self.recv_packet::<ClientboundPlayPluginMessagePacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.handle_plugin_message(resp.data)?;
                 }
                 val if val == PlayLoginPacket::ID => {
-                    let resp = self.recv_packet::<PlayLoginPacket>()?;
+                    let resp = self.recv_packet::<PlayLoginPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.reset_player();
+                    self.entities.reset(resp.entity_id);
+                    self.world.set_dimension(Dimension::from_registries(
+                        &self.registries,
+                        resp.dimension_type.0 as usize,
+                    )?);
+                }
+                val if val == RespawnPacket::ID => {
+                    let resp = self.recv_packet::<RespawnPacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
+                    self.reset_player();
+                    self.entities.clear();
+                    self.world.set_dimension(Dimension::from_registries(
+                        &self.registries,
+                        resp.dimension_type.0 as usize,
+                    )?);
+                }
+                val if val == SynchronizePlayerPositionPacket::ID => {
+                    let resp = self.recv_packet::<SynchronizePlayerPositionPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    let player = self.player.get_or_insert_default();
+                    player.synchronize(&resp);
+                    let player = *player;
+                    self.send_packet(ConfirmTeleportationPacket {
+                        teleport_id: resp.teleport_id,
+                    })?;
+                    // like vanilla, always claim to be in the air after a teleport
+                    let flags = MovementFlags::new(false, false);
+                    self.send_packet(SetPlayerPositionAndRotationPacket {
+                        position: player.position,
+                        yaw: player.yaw,
+                        pitch: player.pitch,
+                        flags,
+                    })?;
+                    self.movement.sent(&player, flags);
+                    self.update_local_entity();
+                }
+                val if val == SpawnEntityPacket::ID => {
+                    let resp = self.recv_packet::<SpawnEntityPacket>(&resp_header)?;
+                    self.entities.spawn(resp, self.version);
+                }
+                val if val == UpdateEntityPositionPacket::ID => {
+                    let resp = self.recv_packet::<UpdateEntityPositionPacket>(&resp_header)?;
+                    self.entities.update_position(&resp);
+                }
+                val if val == UpdateEntityPositionAndRotationPacket::ID => {
+                    let resp =
+                        self.recv_packet::<UpdateEntityPositionAndRotationPacket>(&resp_header)?;
+                    self.entities.update_position_and_rotation(&resp);
+                }
+                val if val == UpdateEntityRotationPacket::ID => {
+                    let resp = self.recv_packet::<UpdateEntityRotationPacket>(&resp_header)?;
+                    self.entities.update_rotation(&resp);
+                }
+                val if val == TeleportEntityPacket::ID => {
+                    let resp = self.recv_packet::<TeleportEntityPacket>(&resp_header)?;
+                    self.entities.teleport(&resp);
+                }
+                val if val == SetEntityVelocityPacket::ID => {
+                    let resp = self.recv_packet::<SetEntityVelocityPacket>(&resp_header)?;
+                    // knockback and the like, which our own physics carries on from
+                    if Some(resp.entity_id.0) == self.entity_id()
+                        && let Some(player) = &mut self.player
+                    {
+                        player.velocity = resp.velocity();
+                    }
+                    self.entities.set_velocity(&resp);
+                }
+                val if val == SetEntityMetadataPacket::ID => {
+                    let resp = self.recv_packet::<SetEntityMetadataPacket>(&resp_header)?;
+                    self.entities.set_metadata(resp);
+                }
+                val if val == SetHeadRotationPacket::ID => {
+                    let resp = self.recv_packet::<SetHeadRotationPacket>(&resp_header)?;
+                    self.entities.set_head_rotation(&resp);
+                }
+                val if val == RemoveEntitiesPacket::ID => {
+                    let resp = self.recv_packet::<RemoveEntitiesPacket>(&resp_header)?;
+                    self.entities.remove(&resp);
+                }
+                val if val == ChunkDataAndUpdateLightPacket::ID => {
+                    let resp = self.recv_packet::<ChunkDataAndUpdateLightPacket>(&resp_header)?;
+                    self.world.load(resp)?;
+                }
+                val if val == UpdateLightPacket::ID => {
+                    let resp = self.recv_packet::<UpdateLightPacket>(&resp_header)?;
+                    self.world.update_light(resp);
+                }
+                val if val == UnloadChunkPacket::ID => {
+                    let resp = self.recv_packet::<UnloadChunkPacket>(&resp_header)?;
+                    self.world.unload(resp.chunk_x, resp.chunk_z);
+                }
+                val if val == BlockUpdatePacket::ID => {
+                    let resp = self.recv_packet::<BlockUpdatePacket>(&resp_header)?;
+                    self.world.set_block(resp.location, resp.block_state.0);
+                }
+                val if val == SectionBlocksUpdatePacket::ID => {
+                    let resp = self.recv_packet::<SectionBlocksUpdatePacket>(&resp_header)?;
+                    self.world.apply_section_update(&resp);
+                }
+                val if val == ChunkBatchStartPacket::ID => {
+                    self.recv_packet::<ChunkBatchStartPacket>(&resp_header)?;
+                    self.world.batch_started();
+                }
+                val if val == ChunkBatchFinishedPacket::ID => {
+                    let resp = self.recv_packet::<ChunkBatchFinishedPacket>(&resp_header)?;
+                    self.world.batch_finished(resp.batch_size.0.max(0) as usize);
+                    self.send_packet(ChunkBatchReceivedPacket {
+                        chunks_per_tick: self.world.chunk_rate().desired_chunks_per_tick(),
+                    })?;
                 }
                 val if val == ChangeDifficultyPacket::ID => {
-                    let resp = self.recv_packet::<ChangeDifficultyPacket>()?;
+                    let resp = self.recv_packet::<ChangeDifficultyPacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
                 }
                 val if val == ClientboundPlayerAbilitiesPacket::ID => {
-                    let resp = self.recv_packet::<ClientboundPlayerAbilitiesPacket>()?;
+                    let resp =
+                        self.recv_packet::<ClientboundPlayerAbilitiesPacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
                 }
                 val if val == SetHealthPacket::ID => {
-                    let resp = self.recv_packet::<SetHealthPacket>()?;
+                    let resp = self.recv_packet::<SetHealthPacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
                 }
                 val if val == ClientboundSetHeldItemPacket::ID => {
-                    let resp = self.recv_packet::<ClientboundSetHeldItemPacket>()?;
+                    let resp = self.recv_packet::<ClientboundSetHeldItemPacket>(&resp_header)?;
                     eprintln!("{:?}", resp);
                 }
                 val if val == UpdateRecipesPacket::ID => {
-                    let _resp = self.recv_packet::<UpdateRecipesPacket>()?;
+                    let _resp = self.recv_packet::<UpdateRecipesPacket>(&resp_header)?;
                     // eprintln!("{:?}", resp);
                 }
+                val if val == PlayCookieRequestPacket::ID => {
+                    let resp = self.recv_packet::<PlayCookieRequestPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    let payload = self.cookies.get(&resp.key).map(<[u8]>::to_vec);
+                    self.send_packet(PlayCookieResponsePacket {
+                        key: resp.key,
+                        payload,
+                    })?;
+                }
+                val if val == PlayStoreCookiePacket::ID => {
+                    let resp = self.recv_packet::<PlayStoreCookiePacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.store_cookie(resp.cookie)?;
+                }
+                val if val == PlayDisconnectPacket::ID => {
+                    let resp = self.recv_packet::<PlayDisconnectPacket>(&resp_header)?;
+                    return Err(Disconnected {
+                        reason: plain_text(&resp.reason),
+                    }
+                    .into());
+                }
+                val if val == PlayAddResourcePackPacket::ID => {
+                    let resp = self.recv_packet::<PlayAddResourcePackPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.push_resource_pack(resp.pack)?;
+                }
+                val if val == PlayRemoveResourcePackPacket::ID => {
+                    let resp = self.recv_packet::<PlayRemoveResourcePackPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.resource_packs.pop(resp.uuid);
+                }
+                val if val == StartConfigurationPacket::ID => {
+                    let resp = self.recv_packet::<StartConfigurationPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.send_packet(ConfigurationAcknowledgedPacket)?;
+                    self.set_state(ConnectionState::Configuration);
+                    self.leave_play();
+                    self.configure()?;
+                }
+                val if val == PlayTransferPacket::ID => {
+                    let resp = self.recv_packet::<PlayTransferPacket>(&resp_header)?;
+                    eprintln!("{:?}", resp);
+                    self.transfer(
+                        resp.target.host.into_inner(),
+                        u16::try_from(resp.target.port.0)?,
+                    )?;
+                }
                 _ => {
-                    eprintln!("{:?}", resp_header);
-                    let _resp = self.recv_packet_raw(&resp_header)?;
-                    // eprintln!("{:?}", resp);
-                    break;
+                    // eprintln!("{:?}", resp_header);
+                    // packets we do not handle yet are skipped, not a reason to leave
+                    self.recv_packet_raw(&resp_header)?;
                 }
             }
         }
-
-        Ok(())
     }
 }
@@ -0,0 +1,121 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::anyhow;
+
+use crate::datatypes::{Error, Identifier, MinecraftData, MinecraftRead};
+
+/// Largest cookie payload vanilla will store or send
+pub const MAX_COOKIE_LEN: usize = 5120;
+
+#[derive(Debug, Clone, PartialEq, Eq, MinecraftData)]
+pub struct Cookie {
+    pub key: Identifier,
+    #[mc(max_len = MAX_COOKIE_LEN)]
+    pub payload: Vec<u8>,
+}
+
+/// Cookies stored by servers with Store Cookie, to be handed back when they send a Cookie Request.
+///
+/// The jar outlives a single server so that cookies set before a transfer can be read after it,
+/// and it can be saved to disk to keep them between runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieJar {
+    cookies: HashMap<Identifier, Vec<u8>>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &Identifier) -> Option<&[u8]> {
+        self.cookies.get(key).map(Vec::as_slice)
+    }
+
+    /// Stores a cookie, replacing any previous one with the same key
+    pub fn insert(&mut self, cookie: Cookie) -> Result<(), Error> {
+        if cookie.payload.len() > MAX_COOKIE_LEN {
+            return Err(anyhow!(
+                "cookie {} is {} bytes, more than the maximum of {MAX_COOKIE_LEN}",
+                cookie.key,
+                cookie.payload.len()
+            ));
+        }
+        self.cookies.insert(cookie.key, cookie.payload);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &Identifier) -> Option<Vec<u8>> {
+        self.cookies.remove(key)
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &[u8])> {
+        self.cookies.iter().map(|(k, v)| (k, v.as_slice()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let jar = Self::decode(&mut reader)?;
+        if reader.read(&mut [0u8])? != 0 {
+            return Err(anyhow!("trailing data after cookie jar"));
+        }
+        Ok(jar)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.clone().encode(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn to_cookies(&self) -> Vec<Cookie> {
+        let mut cookies = self
+            .cookies
+            .iter()
+            .map(|(key, payload)| Cookie {
+                key: key.clone(),
+                payload: payload.clone(),
+            })
+            .collect::<Vec<_>>();
+        // so that saving the same jar twice gives the same file
+        cookies.sort_by(|a, b| a.key.as_str().cmp(b.key.as_str()));
+        cookies
+    }
+}
+
+/// Saved as a list of cookies, in the same encoding as on the wire
+impl MinecraftData for CookieJar {
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let mut jar = CookieJar::new();
+        for cookie in Vec::<Cookie>::decode(reader)? {
+            jar.insert(cookie)?;
+        }
+        Ok(jar)
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        self.to_cookies().encode(writer)
+    }
+
+    fn num_bytes(&self) -> usize {
+        self.to_cookies().num_bytes()
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    datatypes::{Error, Identifier, MinecraftData, MinecraftRead},
+    nbt::Nbt,
+    packet::{DataPack, RegistryDataPacket, RegistryEntry},
+    registry::Registries,
+};
+
+/// Registry contents of one data pack we have locally, so the server can leave them out of
+/// Registry Data when we both know the pack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalDataPack {
+    pub info: DataPack,
+    registries: HashMap<Identifier, HashMap<Identifier, Nbt>>,
+}
+
+impl LocalDataPack {
+    pub fn new(info: DataPack) -> Self {
+        Self {
+            info,
+            registries: HashMap::new(),
+        }
+    }
+
+    /// Captures registries that a server sent in full, for example after a session in which we
+    /// claimed no packs, so that later sessions can claim `info` instead.
+    pub fn from_registries(info: DataPack, registries: &Registries) -> Self {
+        let mut pack = Self::new(info);
+        for registry in registries.registry_ids() {
+            for entry in registries.registry(registry.as_str()).unwrap_or_default() {
+                if let Some(data) = &entry.data {
+                    pack.insert(registry.clone(), entry.id.clone(), data.clone());
+                }
+            }
+        }
+        pack
+    }
+
+    pub fn insert(&mut self, registry: Identifier, id: Identifier, data: Nbt) {
+        self.registries
+            .entry(registry)
+            .or_default()
+            .insert(id, data);
+    }
+
+    pub fn get(&self, registry: &str, id: &str) -> Option<&Nbt> {
+        self.registries.get(registry)?.get(id)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let pack = Self::decode(&mut reader)?;
+        if reader.read(&mut [0u8])? != 0 {
+            return Err(anyhow!("trailing data after data pack"));
+        }
+        Ok(pack)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.clone().encode(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn to_registries(&self) -> Vec<RegistryDataPacket> {
+        let mut registries = self
+            .registries
+            .iter()
+            .map(|(registry_id, entries)| {
+                let mut entries = entries
+                    .iter()
+                    .map(|(id, data)| RegistryEntry {
+                        id: id.clone(),
+                        data: Some(data.clone()),
+                    })
+                    .collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.id.as_str().cmp(b.id.as_str()));
+                RegistryDataPacket {
+                    registry_id: registry_id.clone(),
+                    entries,
+                }
+            })
+            .collect::<Vec<_>>();
+        // so that saving the same pack twice gives the same file
+        registries.sort_by(|a, b| a.registry_id.as_str().cmp(b.registry_id.as_str()));
+        registries
+    }
+}
+
+/// Saved as the pack info followed by its registries, in the same encoding as on the wire
+impl MinecraftData for LocalDataPack {
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let mut pack = Self::new(DataPack::decode(reader)?);
+        for registry in Vec::<RegistryDataPacket>::decode(reader)? {
+            for entry in registry.entries {
+                let data = entry
+                    .data
+                    .ok_or_else(|| anyhow!("{} has no data in the saved pack", entry.id))?;
+                pack.insert(registry.registry_id.clone(), entry.id, data);
+            }
+        }
+        Ok(pack)
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        let registries = self.to_registries();
+        self.info.encode(writer)?;
+        registries.encode(writer)
+    }
+
+    fn num_bytes(&self) -> usize {
+        self.info.num_bytes() + self.to_registries().num_bytes()
+    }
+}
+
+/// The data packs we can resolve locally. Only these are claimed in Known Packs.
+///
+/// Empty by default, so servers send every registry entry inline. A pack added here must hold
+/// every entry it defines in the registries the server syncs, for example one captured with
+/// [`LocalDataPack::from_registries`], since the server leaves all of them out once it is
+/// claimed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DataPackCatalog {
+    packs: Vec<LocalDataPack>,
+}
+
+impl DataPackCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces any pack with the same namespace, id and version
+    pub fn add(&mut self, pack: LocalDataPack) {
+        self.packs.retain(|existing| existing.info != pack.info);
+        self.packs.push(pack);
+    }
+
+    pub fn packs(&self) -> &[LocalDataPack] {
+        &self.packs
+    }
+
+    /// The packs out of those the server offered that we have, in the server's order
+    pub fn negotiate(&self, offered: &[DataPack]) -> Vec<DataPack> {
+        offered
+            .iter()
+            .filter(|info| self.packs.iter().any(|pack| &pack.info == *info))
+            .cloned()
+            .collect()
+    }
+
+    /// Looks an entry up in the known packs, later packs overriding earlier ones as they do on
+    /// the server
+    pub fn resolve(&self, known: &[DataPack], registry: &str, id: &str) -> Option<&Nbt> {
+        known.iter().rev().find_map(|info| {
+            self.packs
+                .iter()
+                .find(|pack| &pack.info == info)?
+                .get(registry, id)
+        })
+    }
+}
@@ -1,22 +1,53 @@
 use anyhow::anyhow;
-use minecraft_derive::MinecraftData;
 use std::{
     fmt::{Debug, Display},
-    io::{Read, Write},
+    io::{BufReader, Cursor, Read, Take, Write},
+    net::TcpStream,
 };
 
+pub use minecraft_derive::MinecraftData;
+
+use crate::protocol::ProtocolVersion;
+
 pub type Error = anyhow::Error;
 
 pub trait MinecraftData: Sized + Debug {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error>;
+    /// Fewest bytes any value takes on the wire, which lets collections reject a length that
+    /// cannot fit in what is left of the frame. 0 when there is no useful lower bound.
+    const MIN_BYTES: usize = 0;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error>;
     fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error>;
     fn num_bytes(&self) -> usize;
 }
 
+/// Decoding straight out of a frame buffer, so that strings, byte arrays and NBT can borrow from
+/// it instead of being copied.
+///
+/// Owned types implement this by decoding normally, which lets them be nested in borrowed ones.
+pub trait MinecraftDataRef<'a>: Sized + Debug {
+    /// The same as [`MinecraftData::MIN_BYTES`]
+    const MIN_BYTES: usize = 0;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error>;
+}
+
+macro_rules! impl_minecraft_data_ref_via_decode {
+    ($ty:ty) => {
+        impl<'a> MinecraftDataRef<'a> for $ty {
+            const MIN_BYTES: usize = <$ty as MinecraftData>::MIN_BYTES;
+
+            fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+                Self::decode(buf)
+            }
+        }
+    };
+}
+
 #[derive(Debug, Clone, Copy)]
 struct UnimplementedData;
 impl MinecraftData for UnimplementedData {
-    fn decode<R: Read>(_reader: &mut R) -> Result<Self, Error> {
+    fn decode<R: MinecraftRead>(_reader: &mut R) -> Result<Self, Error> {
         unimplemented!("decode UnimplementedData")
     }
 
@@ -29,6 +60,8 @@ impl MinecraftData for UnimplementedData {
     }
 }
 
+impl_minecraft_data_ref_via_decode!(UnimplementedData);
+
 const SEGMENT_BITS: u8 = 0x7F;
 const CONTINUE_BIT: u8 = 0x80;
 
@@ -36,7 +69,9 @@ const CONTINUE_BIT: u8 = 0x80;
 pub struct VarInt(pub i32);
 
 impl MinecraftData for VarInt {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
         let mut value: i32 = 0;
         let mut position = 0;
         let mut buf = [0u8];
@@ -75,45 +110,148 @@ impl MinecraftData for VarInt {
     }
 }
 
-#[derive(Debug, Clone)]
+impl_minecraft_data_ref_via_decode!(VarInt);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VarLong(pub i64);
+
+impl MinecraftData for VarLong {
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let mut value: i64 = 0;
+        let mut position = 0;
+        let mut buf = [0u8];
+        while position < 64 {
+            reader.read_exact(&mut buf)?;
+            let curr_byte = buf[0];
+            value |= ((curr_byte & SEGMENT_BITS) as i64) << position;
+            if (curr_byte & CONTINUE_BIT) == 0 {
+                return Ok(VarLong(value));
+            }
+            position += 7;
+        }
+        Err(anyhow!("varlong too big"))
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        let mut value = self.0 as u64;
+        loop {
+            if (value & !(SEGMENT_BITS as u64)) == 0 {
+                writer.write_all(&[value as u8])?;
+                return Ok(());
+            }
+
+            writer.write_all(&[(value as u8 & SEGMENT_BITS) | CONTINUE_BIT])?;
+
+            value >>= 7;
+        }
+    }
+
+    fn num_bytes(&self) -> usize {
+        if self.0 == 0 {
+            return 1;
+        }
+        let bits = (self.0 as u64).ilog2() + 1;
+        bits.div_ceil(7) as usize
+    }
+}
+
+impl_minecraft_data_ref_via_decode!(VarLong);
+
+/// A string of at most `N` UTF-16 code units, matching the limits vanilla uses.
+///
+/// The length prefix on the wire counts UTF-8 bytes, which may be up to `3 * N`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MString<const N: usize>(String);
 
+impl<const N: usize> MString<N> {
+    /// Largest number of UTF-8 bytes a valid string can encode to
+    pub const MAX_BYTES: usize = N * 3;
+
+    fn validate(value: &str) -> Result<(), Error> {
+        if value.len() > Self::MAX_BYTES {
+            return Err(anyhow!(
+                "string is too long! ({} bytes, max {})",
+                value.len(),
+                Self::MAX_BYTES
+            ));
+        }
+        // every UTF-16 code unit takes at least one byte, so short strings can skip counting
+        if value.len() > N {
+            let units = value.encode_utf16().count();
+            if units > N {
+                return Err(anyhow!(
+                    "string is too long! ({units} UTF-16 code units, max {N})"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
 impl<const N: usize> TryFrom<String> for MString<N> {
     type Error = anyhow::Error;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        if value.len() > N {
-            return Err(anyhow!("string is too long!"));
-        }
+        Self::validate(&value)?;
         Ok(MString(value))
     }
 }
 
+impl<const N: usize> TryFrom<&str> for MString<N> {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::try_from(value.to_owned())
+    }
+}
+
 impl<const N: usize> Display for MString<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Display::fmt(&self.0, f)
     }
 }
 
+/// Lets maps keyed by `MString` be looked up with a plain `&str`
+impl<const N: usize> std::borrow::Borrow<str> for MString<N> {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
 impl<const N: usize> MinecraftData for MString<N> {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
         let len = VarInt::decode(reader)?.0;
         if len < 0 {
             return Err(anyhow!("cannot have negative length string"));
         }
         let len = len as usize;
-        if len > N {
-            return Err(anyhow!("string is too long!"));
+        if len > Self::MAX_BYTES {
+            return Err(anyhow!(
+                "string is too long! ({len} bytes, max {})",
+                Self::MAX_BYTES
+            ));
         }
         let mut buf = vec![0u8; len];
         reader.read_exact(&mut buf)?;
-        Ok(MString(String::from_utf8(buf)?))
+        let value = String::from_utf8(buf)?;
+        Self::validate(&value)?;
+        Ok(MString(value))
     }
 
     fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
-        if self.0.len() > N {
-            return Err(anyhow!("string is too long!"));
-        }
+        Self::validate(&self.0)?;
         VarInt(self.0.len() as i32).encode(writer)?;
         writer.write_all(self.0.as_bytes())?;
 
@@ -125,12 +263,124 @@ impl<const N: usize> MinecraftData for MString<N> {
     }
 }
 
+impl<'a, const N: usize> MinecraftDataRef<'a> for MString<N> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        Self::decode(buf)
+    }
+}
+
 pub type Identifier = MString<32767>;
 
+/// Splits `len` bytes off the front of `buf`
+fn take_bytes<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if buf.len() < len {
+        return Err(anyhow!(
+            "{len} bytes requested but only {} left in the frame",
+            buf.len()
+        ));
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+/// A borrowed [`MString`], with the same limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MStr<'a, const N: usize>(&'a str);
+
+impl<'a, const N: usize> MStr<'a, N> {
+    pub fn as_str(&self) -> &'a str {
+        self.0
+    }
+
+    pub fn to_owned(&self) -> MString<N> {
+        MString(self.0.to_owned())
+    }
+}
+
+impl<const N: usize> Display for MStr<'_, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self.0, f)
+    }
+}
+
+impl<'a, const N: usize> MinecraftDataRef<'a> for MStr<'a, N> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        let len = decode_len(buf, MString::<N>::MAX_BYTES)?;
+        let value = std::str::from_utf8(take_bytes(buf, len)?)?;
+        MString::<N>::validate(value)?;
+        Ok(MStr(value))
+    }
+}
+
+pub type IdentifierRef<'a> = MStr<'a, 32767>;
+
+/// A borrowed `VarInt` length-prefixed byte array, the counterpart of `Vec<u8>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSlice<'a>(pub &'a [u8]);
+
+impl<'a> MinecraftDataRef<'a> for ByteSlice<'a> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        Self::decode_ref_with_max_len(buf, Self::DEFAULT_MAX_LEN)
+    }
+}
+
+impl<'a> LengthPrefixedRef<'a> for ByteSlice<'a> {
+    fn decode_ref_with_max_len(buf: &mut &'a [u8], max_len: usize) -> Result<Self, Error> {
+        let len = decode_len(buf, max_len)?;
+        Ok(ByteSlice(take_bytes(buf, len)?))
+    }
+}
+
+/// Everything left in the packet, up to `N` bytes, with no length prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemainingBytes<const N: usize>(pub Vec<u8>);
+
+impl<const N: usize> MinecraftData for RemainingBytes<N> {
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let mut buf = Vec::new();
+        // read one past the limit to tell a full buffer apart from an oversized one
+        reader.take(N as u64 + 1).read_to_end(&mut buf)?;
+        if buf.len() > N {
+            return Err(anyhow!("more than {N} bytes left in the packet"));
+        }
+        Ok(RemainingBytes(buf))
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        if self.0.len() > N {
+            return Err(anyhow!(
+                "{} bytes is more than the maximum of {N}",
+                self.0.len()
+            ));
+        }
+        writer.write_all(&self.0)?;
+        Ok(())
+    }
+
+    fn num_bytes(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<'a, const N: usize> MinecraftDataRef<'a> for RemainingBytes<N> {
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        Self::decode(buf)
+    }
+}
+
 macro_rules! impl_minecraft_data_for_num {
     ($num:ty, $bytes:expr) => {
         impl MinecraftData for $num {
-            fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
+            const MIN_BYTES: usize = $bytes;
+
+            fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
                 let mut buf = [0u8; $bytes];
                 reader.read_exact(&mut buf)?;
                 Ok(<$num>::from_be_bytes(buf))
@@ -145,6 +395,8 @@ macro_rules! impl_minecraft_data_for_num {
                 $bytes
             }
         }
+
+        impl_minecraft_data_ref_via_decode!($num);
     };
 }
 
@@ -167,11 +419,13 @@ impl_minecraft_data_for_int!(i128);
 impl_minecraft_data_for_num!(f32, 4);
 impl_minecraft_data_for_num!(f64, 8);
 
-#[derive(Debug, Clone, Copy, MinecraftData)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, MinecraftData)]
 pub struct UUID(pub u128);
 
 impl MinecraftData for bool {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
         let mut buf = [0u8; 1];
         reader.read_exact(&mut buf)?;
         match buf[0] {
@@ -194,14 +448,148 @@ impl MinecraftData for bool {
     }
 }
 
-fn decode_array<R: Read, T: MinecraftData>(len: usize, reader: &mut R) -> Result<Vec<T>, Error> {
-    let mut res = Vec::with_capacity(len);
+impl_minecraft_data_ref_via_decode!(bool);
+
+/// Largest length a length-prefixed collection may claim unless its type or field says otherwise.
+///
+/// Every element takes at least a byte on the wire, so this is also a bit over the largest frame.
+pub const DEFAULT_MAX_LEN: usize = 1 << 21;
+
+/// Upper bound on how many bytes are reserved before any elements have actually been read
+const MAX_PREALLOCATION: usize = 1 << 16;
+
+/// A type whose encoding starts with a `VarInt` element count, which a hostile peer controls.
+pub trait LengthPrefixed: MinecraftData {
+    const DEFAULT_MAX_LEN: usize = DEFAULT_MAX_LEN;
+
+    /// Decodes the value, rejecting it if the length prefix exceeds `max_len`
+    fn decode_with_max_len<R: MinecraftRead>(reader: &mut R, max_len: usize)
+        -> Result<Self, Error>;
+}
+
+/// The borrowed counterpart of [`LengthPrefixed`].
+pub trait LengthPrefixedRef<'a>: MinecraftDataRef<'a> {
+    const DEFAULT_MAX_LEN: usize = DEFAULT_MAX_LEN;
+
+    fn decode_ref_with_max_len(buf: &mut &'a [u8], max_len: usize) -> Result<Self, Error>;
+}
+
+/// Reads a `VarInt` length prefix, rejecting negative values and anything above `max_len`
+pub fn decode_len<R: MinecraftRead>(reader: &mut R, max_len: usize) -> Result<usize, Error> {
+    let len = VarInt::decode(reader)?.0;
+    if len < 0 {
+        return Err(anyhow!("cannot have negative length ({len})"));
+    }
+    let len = len as usize;
+    if len > max_len {
+        return Err(anyhow!(
+            "length {len} is more than the maximum of {max_len}"
+        ));
+    }
+    Ok(len)
+}
+
+/// A reader that values are decoded from, which can say how many bytes it has left so that
+/// collections can reject lengths that could not fit before reading any elements.
+///
+/// Wrappers around another reader should forward [`MinecraftRead::remaining`] to it, otherwise
+/// decoding through them only catches a lying length when it runs into the end of the data.
+pub trait MinecraftRead: Read {
+    /// The bytes left to read, or `None` when that is not known
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl MinecraftRead for &[u8] {
+    fn remaining(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T: AsRef<[u8]>> MinecraftRead for Cursor<T> {
+    fn remaining(&self) -> Option<usize> {
+        let len = self.get_ref().as_ref().len() as u64;
+        Some(len.saturating_sub(self.position()) as usize)
+    }
+}
+
+impl<R: MinecraftRead + ?Sized> MinecraftRead for &mut R {
+    fn remaining(&self) -> Option<usize> {
+        (**self).remaining()
+    }
+}
+
+impl<R: MinecraftRead + ?Sized> MinecraftRead for Box<R> {
+    fn remaining(&self) -> Option<usize> {
+        (**self).remaining()
+    }
+}
+
+impl<R: MinecraftRead> MinecraftRead for Take<R> {
+    fn remaining(&self) -> Option<usize> {
+        let limit = usize::try_from(self.limit()).unwrap_or(usize::MAX);
+        Some(match self.get_ref().remaining() {
+            Some(inner) => inner.min(limit),
+            None => limit,
+        })
+    }
+}
+
+impl<R: Read> MinecraftRead for BufReader<R> {}
+
+impl MinecraftRead for TcpStream {}
+
+/// Decodes a value from a frame body as `version` sends it, advancing `body` past it.
+/// Collections reject lengths that could not fit in the bytes left before reading any elements.
+pub fn decode_frame<T: MinecraftData>(
+    body: &mut &[u8],
+    version: ProtocolVersion,
+) -> Result<T, Error> {
+    version.decode(body)
+}
+
+/// Rejects a length that claims more elements than the bytes left could hold
+pub(crate) fn check_len_fits(
+    len: usize,
+    min_bytes: usize,
+    remaining: Option<usize>,
+) -> Result<(), Error> {
+    match remaining {
+        Some(remaining) if len.saturating_mul(min_bytes) > remaining => Err(anyhow!(
+            "length {len} does not fit in the {remaining} bytes left in the frame"
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn decode_array<R: MinecraftRead, T: MinecraftData>(
+    len: usize,
+    reader: &mut R,
+) -> Result<Vec<T>, Error> {
+    check_len_fits(len, T::MIN_BYTES, reader.remaining())?;
+    // for readers that do not know their length, or for elements that may be empty, a lying
+    // length is only caught when it runs into the end of the data, so only reserve a bounded
+    // amount up front
+    let mut res = Vec::with_capacity(len.min(MAX_PREALLOCATION / size_of::<T>().max(1)));
     for _ in 0..len {
         res.push(T::decode(reader)?)
     }
     Ok(res)
 }
 
+fn decode_array_ref<'a, T: MinecraftDataRef<'a>>(
+    len: usize,
+    buf: &mut &'a [u8],
+) -> Result<Vec<T>, Error> {
+    check_len_fits(len, T::MIN_BYTES, Some(buf.len()))?;
+    let mut res = Vec::with_capacity(len.min(MAX_PREALLOCATION / size_of::<T>().max(1)));
+    for _ in 0..len {
+        res.push(T::decode_ref(buf)?)
+    }
+    Ok(res)
+}
+
 fn encode_array<W: Write, T: MinecraftData, I: IntoIterator<Item = T>>(
     data: I,
     writer: &mut W,
@@ -219,7 +607,9 @@ fn num_bytes_array<'a, T: MinecraftData + 'a, I: IntoIterator<Item = &'a T>>(dat
 }
 
 impl<T: MinecraftData, const N: usize> MinecraftData for [T; N] {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
+    const MIN_BYTES: usize = N * T::MIN_BYTES;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
         // cannot fail bc we know we put the right number of elements in
         Ok(decode_array(N, reader)?.try_into().unwrap())
     }
@@ -233,10 +623,20 @@ impl<T: MinecraftData, const N: usize> MinecraftData for [T; N] {
     }
 }
 
+impl<'a, T: MinecraftDataRef<'a>, const N: usize> MinecraftDataRef<'a> for [T; N] {
+    const MIN_BYTES: usize = N * T::MIN_BYTES;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        // cannot fail bc we know we put the right number of elements in
+        Ok(decode_array_ref(N, buf)?.try_into().unwrap())
+    }
+}
+
 impl<T: MinecraftData> MinecraftData for Vec<T> {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
-        let len = VarInt::decode(reader)?.0 as usize;
-        decode_array(len, reader)
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        Self::decode_with_max_len(reader, <Self as LengthPrefixed>::DEFAULT_MAX_LEN)
     }
 
     fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
@@ -249,8 +649,35 @@ impl<T: MinecraftData> MinecraftData for Vec<T> {
     }
 }
 
+impl<T: MinecraftData> LengthPrefixed for Vec<T> {
+    fn decode_with_max_len<R: MinecraftRead>(
+        reader: &mut R,
+        max_len: usize,
+    ) -> Result<Self, Error> {
+        let len = decode_len(reader, max_len)?;
+        decode_array(len, reader)
+    }
+}
+
+impl<'a, T: MinecraftDataRef<'a>> MinecraftDataRef<'a> for Vec<T> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        Self::decode_ref_with_max_len(buf, <Self as LengthPrefixedRef>::DEFAULT_MAX_LEN)
+    }
+}
+
+impl<'a, T: MinecraftDataRef<'a>> LengthPrefixedRef<'a> for Vec<T> {
+    fn decode_ref_with_max_len(buf: &mut &'a [u8], max_len: usize) -> Result<Self, Error> {
+        let len = decode_len(buf, max_len)?;
+        decode_array_ref(len, buf)
+    }
+}
+
 impl<T: MinecraftData> MinecraftData for Option<T> {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
         let is_present = bool::decode(reader)?;
         if is_present {
             Ok(Some(T::decode(reader)?))
@@ -277,8 +704,46 @@ impl<T: MinecraftData> MinecraftData for Option<T> {
     }
 }
 
+impl<'a, T: MinecraftDataRef<'a>> MinecraftDataRef<'a> for Option<T> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        if bool::decode(buf)? {
+            Ok(Some(T::decode_ref(buf)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Caps the inner value's length when it is present
+impl<T: LengthPrefixed> LengthPrefixed for Option<T> {
+    fn decode_with_max_len<R: MinecraftRead>(
+        reader: &mut R,
+        max_len: usize,
+    ) -> Result<Self, Error> {
+        if bool::decode(reader)? {
+            Ok(Some(T::decode_with_max_len(reader, max_len)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a, T: LengthPrefixedRef<'a>> LengthPrefixedRef<'a> for Option<T> {
+    fn decode_ref_with_max_len(buf: &mut &'a [u8], max_len: usize) -> Result<Self, Error> {
+        if bool::decode(buf)? {
+            Ok(Some(T::decode_ref_with_max_len(buf, max_len)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
 impl<T: MinecraftData> MinecraftData for Box<T> {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
+    const MIN_BYTES: usize = T::MIN_BYTES;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
         Ok(Box::new(T::decode(reader)?))
     }
 
@@ -291,6 +756,14 @@ impl<T: MinecraftData> MinecraftData for Box<T> {
     }
 }
 
+impl<'a, T: MinecraftDataRef<'a>> MinecraftDataRef<'a> for Box<T> {
+    const MIN_BYTES: usize = T::MIN_BYTES;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        Ok(Box::new(T::decode_ref(buf)?))
+    }
+}
+
 #[derive(Debug, Clone, MinecraftData)]
 pub struct GameProfileProperty {
     pub name: MString<64>,
@@ -302,16 +775,17 @@ pub struct GameProfileProperty {
 pub struct GameProfile {
     pub uuid: UUID,
     pub username: MString<16>,
+    #[mc(max_len = 16)]
     pub properties: Vec<GameProfileProperty>,
 }
 
 #[derive(Debug, Clone, MinecraftData)]
 pub struct Tag {
-    name: Identifier,
-    entries: Vec<VarInt>,
+    pub name: Identifier,
+    pub entries: Vec<VarInt>,
 }
 
-#[derive(Debug, Clone, Copy, MinecraftData)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, MinecraftData)]
 pub struct Position(i64);
 
 impl Position {
@@ -339,13 +813,10 @@ pub enum IDSet {
 }
 
 impl MinecraftData for IDSet {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
-        let len = VarInt::decode(reader)?.0 as usize;
-        if len == 0 {
-            Ok(Self::Named(Identifier::decode(reader)?))
-        } else {
-            Ok(Self::Enumerated(decode_array(len - 1, reader)?))
-        }
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        Self::decode_with_max_len(reader, <Self as LengthPrefixed>::DEFAULT_MAX_LEN)
     }
 
     fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
@@ -372,6 +843,29 @@ impl MinecraftData for IDSet {
     }
 }
 
+impl LengthPrefixed for IDSet {
+    fn decode_with_max_len<R: MinecraftRead>(
+        reader: &mut R,
+        max_len: usize,
+    ) -> Result<Self, Error> {
+        // the prefix is one more than the number of ids, with 0 meaning a tag follows instead
+        let len = decode_len(reader, max_len.saturating_add(1))?;
+        if len == 0 {
+            Ok(Self::Named(Identifier::decode(reader)?))
+        } else {
+            Ok(Self::Enumerated(decode_array(len - 1, reader)?))
+        }
+    }
+}
+
+impl_minecraft_data_ref_via_decode!(IDSet);
+
+impl<'a> LengthPrefixedRef<'a> for IDSet {
+    fn decode_ref_with_max_len(buf: &mut &'a [u8], max_len: usize) -> Result<Self, Error> {
+        Self::decode_with_max_len(buf, max_len)
+    }
+}
+
 #[derive(Debug, Clone, MinecraftData)]
 pub struct Slot {
     count: VarInt,
@@ -413,3 +907,268 @@ pub enum SlotDisplay {
     #[mc_repr(VarInt(7))]
     Composite { options: Vec<SlotDisplay> },
 }
+
+/// A length-prefixed bit set, packed into longs with bit `i` stored in `longs[i / 64]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, MinecraftData)]
+pub struct BitSet(pub Vec<i64>);
+
+impl LengthPrefixed for BitSet {
+    fn decode_with_max_len<R: MinecraftRead>(
+        reader: &mut R,
+        max_len: usize,
+    ) -> Result<Self, Error> {
+        Ok(BitSet(Vec::decode_with_max_len(reader, max_len)?))
+    }
+}
+
+impl<'a> LengthPrefixedRef<'a> for BitSet {
+    fn decode_ref_with_max_len(buf: &mut &'a [u8], max_len: usize) -> Result<Self, Error> {
+        Self::decode_with_max_len(buf, max_len)
+    }
+}
+
+impl BitSet {
+    pub fn get(&self, index: usize) -> bool {
+        self.0
+            .get(index / 64)
+            .is_some_and(|long| (long >> (index % 64)) & 1 == 1)
+    }
+
+    pub fn set(&mut self, index: usize, value: bool) {
+        if index / 64 >= self.0.len() {
+            if !value {
+                return;
+            }
+            self.0.resize(index / 64 + 1, 0);
+        }
+        if value {
+            self.0[index / 64] |= 1 << (index % 64);
+        } else {
+            self.0[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    /// Number of bits that can be set without growing the backing storage, which is a multiple
+    /// of 64 and says nothing about how many are set
+    pub fn bit_len(&self) -> usize {
+        self.0.len() * 64
+    }
+
+    /// Whether no bit is set, however many longs back the set
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|long| *long == 0)
+    }
+
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.bit_len()).filter(|&i| self.get(i))
+    }
+}
+
+/// A bit set of exactly `N` bits, sent as `ceil(N / 8)` bytes with no length prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedBitSet<const N: usize>(Vec<u8>);
+
+impl<const N: usize> FixedBitSet<N> {
+    pub fn new() -> Self {
+        FixedBitSet(vec![0u8; N.div_ceil(8)])
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        index < N && (self.0[index / 8] >> (index % 8)) & 1 == 1
+    }
+
+    pub fn set(&mut self, index: usize, value: bool) {
+        assert!(index < N, "bit {index} out of range for FixedBitSet<{N}>");
+        if value {
+            self.0[index / 8] |= 1 << (index % 8);
+        } else {
+            self.0[index / 8] &= !(1 << (index % 8));
+        }
+    }
+}
+
+impl<'a, const N: usize> MinecraftDataRef<'a> for FixedBitSet<N> {
+    const MIN_BYTES: usize = N.div_ceil(8);
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        Self::decode(buf)
+    }
+}
+
+impl<const N: usize> Default for FixedBitSet<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> MinecraftData for FixedBitSet<N> {
+    const MIN_BYTES: usize = N.div_ceil(8);
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let mut buf = vec![0u8; N.div_ceil(8)];
+        reader.read_exact(&mut buf)?;
+        Ok(FixedBitSet(buf))
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(&self.0)?;
+        Ok(())
+    }
+
+    fn num_bytes(&self) -> usize {
+        N.div_ceil(8)
+    }
+}
+
+/// A rotation in steps of 1/256 of a full turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+pub struct Angle(pub u8);
+
+impl Angle {
+    pub fn from_degrees(degrees: f32) -> Self {
+        Angle((degrees * 256.0 / 360.0).rem_euclid(256.0) as u8)
+    }
+
+    pub fn to_degrees(self) -> f32 {
+        self.0 as f32 * 360.0 / 256.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, MinecraftData)]
+pub struct Vec3d {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, MinecraftData)]
+pub struct Vec3f {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl From<Vec3f> for Vec3d {
+    fn from(value: Vec3f) -> Self {
+        Vec3d {
+            x: value.x as f64,
+            y: value.y as f64,
+            z: value.z as f64,
+        }
+    }
+}
+
+/// Low precision vector used for velocities since 1.21.9.
+///
+/// Each component is quantized to 15 bits relative to a shared scale, which is the smallest
+/// integer no less than the largest absolute component. The zero vector is sent as a single byte.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LpVec3(pub Vec3d);
+
+impl LpVec3 {
+    const MAX_QUANTIZED: f64 = 32766.0;
+    const ABS_MAX: f64 = 1.7179869183e10;
+    const ABS_MIN: f64 = 3.051944088384301e-5;
+
+    fn sanitize(value: f64) -> f64 {
+        if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(-Self::ABS_MAX, Self::ABS_MAX)
+        }
+    }
+
+    fn pack(value: f64) -> u64 {
+        ((value * 0.5 + 0.5) * Self::MAX_QUANTIZED).round() as u64
+    }
+
+    fn unpack(value: u64) -> f64 {
+        ((value & 0x7FFF) as f64).min(Self::MAX_QUANTIZED) * 2.0 / Self::MAX_QUANTIZED - 1.0
+    }
+
+    /// Returns the packed low 48 bits and the scale overflow that is sent as a trailing VarInt
+    fn packed(&self) -> Option<(u64, Option<VarInt>)> {
+        let x = Self::sanitize(self.0.x);
+        let y = Self::sanitize(self.0.y);
+        let z = Self::sanitize(self.0.z);
+        let max = x.abs().max(y.abs()).max(z.abs());
+        if max < Self::ABS_MIN {
+            return None;
+        }
+        let scale = max.ceil() as u64;
+        let needs_continuation = scale & 3 != scale;
+        let scale_bits = if needs_continuation {
+            scale & 3 | 4
+        } else {
+            scale
+        };
+        let scale = scale as f64;
+        let packed = scale_bits
+            | Self::pack(x / scale) << 3
+            | Self::pack(y / scale) << 18
+            | Self::pack(z / scale) << 33;
+        let continuation = needs_continuation.then(|| VarInt((max.ceil() as u64 >> 2) as i32));
+        Some((packed, continuation))
+    }
+}
+
+impl From<LpVec3> for Vec3d {
+    fn from(value: LpVec3) -> Self {
+        value.0
+    }
+}
+
+impl From<Vec3d> for LpVec3 {
+    fn from(value: Vec3d) -> Self {
+        LpVec3(value)
+    }
+}
+
+impl MinecraftData for LpVec3 {
+    /// A zero vector is a single byte
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let first = u8::decode(reader)? as u64;
+        if first == 0 {
+            return Ok(LpVec3::default());
+        }
+        let second = u8::decode(reader)? as u64;
+        let rest = u32::decode(reader)? as u64;
+        let packed = rest << 16 | second << 8 | first;
+        let mut scale = first & 3;
+        if first & 4 == 4 {
+            scale |= (VarInt::decode(reader)?.0 as u32 as u64) << 2;
+        }
+        let scale = scale as f64;
+        Ok(LpVec3(Vec3d {
+            x: Self::unpack(packed >> 3) * scale,
+            y: Self::unpack(packed >> 18) * scale,
+            z: Self::unpack(packed >> 33) * scale,
+        }))
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        match self.packed() {
+            None => 0u8.encode(writer),
+            Some((packed, continuation)) => {
+                (packed as u8).encode(writer)?;
+                ((packed >> 8) as u8).encode(writer)?;
+                ((packed >> 16) as u32).encode(writer)?;
+                if let Some(continuation) = continuation {
+                    continuation.encode(writer)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn num_bytes(&self) -> usize {
+        match self.packed() {
+            None => 1,
+            Some((_, continuation)) => 6 + continuation.map_or(0, |c| c.num_bytes()),
+        }
+    }
+}
+
+impl_minecraft_data_ref_via_decode!(LpVec3);
@@ -0,0 +1,474 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    datatypes::{
+        Error, MString, MinecraftData, MinecraftRead, Position, RemainingBytes, VarInt, VarLong,
+        Vec3d, Vec3f, UUID,
+    },
+    nbt::Nbt,
+    packet::{
+        RemoveEntitiesPacket, SetEntityMetadataPacket, SetEntityVelocityPacket,
+        SetHeadRotationPacket, SpawnEntityPacket, TeleportEntityPacket,
+        UpdateEntityPositionAndRotationPacket, UpdateEntityPositionPacket,
+        UpdateEntityRotationPacket, MAX_PACKET_LEN,
+    },
+    protocol::ProtocolVersion,
+};
+
+include!(concat!(env!("OUT_DIR"), "/entity_types.rs"));
+
+/// The version whose type ids the bundled entity type table uses
+pub const ENTITY_TYPES_VERSION: ProtocolVersion = ProtocolVersion::V1_21_9;
+
+/// Every entity type in the bundled table, without the `minecraft:` namespace, indexed by id
+pub fn entity_types() -> &'static [&'static str] {
+    ENTITY_TYPES
+}
+
+/// The id of an entity type in the bundled table
+pub fn entity_type_id(name: &str) -> Option<i32> {
+    ENTITY_TYPES
+        .iter()
+        .position(|&entity_type| entity_type == name)
+        .map(|id| id as i32)
+}
+
+/// A value in entity metadata.
+///
+/// Only the types whose encoding has not changed across the supported versions are decoded.
+/// Items, particles and the per-mob variant types are not, and end decoding of the rest of the
+/// metadata; see [`EntityMetadata::skipped`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    Byte(i8),
+    VarInt(i32),
+    VarLong(i64),
+    Float(f32),
+    String(MString<32767>),
+    Text(Nbt),
+    OptionalText(Option<Nbt>),
+    Boolean(bool),
+    Rotations(Vec3f),
+    Position(Position),
+    OptionalPosition(Option<Position>),
+    Direction(i32),
+    OptionalUuid(Option<UUID>),
+    BlockState(i32),
+    /// Air is sent as absent
+    OptionalBlockState(Option<i32>),
+    VillagerData {
+        kind: i32,
+        profession: i32,
+        level: i32,
+    },
+    OptionalVarInt(Option<i32>),
+    Pose(i32),
+}
+
+impl MetadataValue {
+    pub fn type_id(&self) -> i32 {
+        match self {
+            Self::Byte(_) => 0,
+            Self::VarInt(_) => 1,
+            Self::VarLong(_) => 2,
+            Self::Float(_) => 3,
+            Self::String(_) => 4,
+            Self::Text(_) => 5,
+            Self::OptionalText(_) => 6,
+            Self::Boolean(_) => 8,
+            Self::Rotations(_) => 9,
+            Self::Position(_) => 10,
+            Self::OptionalPosition(_) => 11,
+            Self::Direction(_) => 12,
+            Self::OptionalUuid(_) => 13,
+            Self::BlockState(_) => 14,
+            Self::OptionalBlockState(_) => 15,
+            Self::VillagerData { .. } => 18,
+            Self::OptionalVarInt(_) => 19,
+            Self::Pose(_) => 20,
+        }
+    }
+
+    /// Decodes a value of a type, or returns `None` for types this client does not decode
+    fn decode_type<R: MinecraftRead>(type_id: i32, reader: &mut R) -> Result<Option<Self>, Error> {
+        let varint = |reader: &mut R| VarInt::decode(reader).map(|v| v.0);
+        Ok(Some(match type_id {
+            0 => Self::Byte(i8::decode(reader)?),
+            1 => Self::VarInt(varint(reader)?),
+            2 => Self::VarLong(VarLong::decode(reader)?.0),
+            3 => Self::Float(f32::decode(reader)?),
+            4 => Self::String(MString::decode(reader)?),
+            5 => Self::Text(Nbt::decode(reader)?),
+            6 => Self::OptionalText(Option::decode(reader)?),
+            8 => Self::Boolean(bool::decode(reader)?),
+            9 => Self::Rotations(Vec3f::decode(reader)?),
+            10 => Self::Position(Position::decode(reader)?),
+            11 => Self::OptionalPosition(Option::decode(reader)?),
+            12 => Self::Direction(varint(reader)?),
+            13 => Self::OptionalUuid(Option::decode(reader)?),
+            14 => Self::BlockState(varint(reader)?),
+            15 => Self::OptionalBlockState(Some(varint(reader)?).filter(|&state| state != 0)),
+            18 => Self::VillagerData {
+                kind: varint(reader)?,
+                profession: varint(reader)?,
+                level: varint(reader)?,
+            },
+            19 => Self::OptionalVarInt(varint(reader)?.checked_sub(1).filter(|&v| v >= 0)),
+            20 => Self::Pose(varint(reader)?),
+            _ => return Ok(None),
+        }))
+    }
+
+    fn encode_value<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        match self {
+            Self::Byte(v) => v.encode(writer),
+            Self::VarInt(v)
+            | Self::Direction(v)
+            | Self::BlockState(v)
+            | Self::Pose(v)
+            | Self::OptionalBlockState(Some(v)) => VarInt(v).encode(writer),
+            Self::VarLong(v) => VarLong(v).encode(writer),
+            Self::Float(v) => v.encode(writer),
+            Self::String(v) => v.encode(writer),
+            Self::Text(v) => v.encode(writer),
+            Self::OptionalText(v) => v.encode(writer),
+            Self::Boolean(v) => v.encode(writer),
+            Self::Rotations(v) => v.encode(writer),
+            Self::Position(v) => v.encode(writer),
+            Self::OptionalPosition(v) => v.encode(writer),
+            Self::OptionalUuid(v) => v.encode(writer),
+            Self::OptionalBlockState(None) => VarInt(0).encode(writer),
+            Self::VillagerData {
+                kind,
+                profession,
+                level,
+            } => {
+                VarInt(kind).encode(writer)?;
+                VarInt(profession).encode(writer)?;
+                VarInt(level).encode(writer)
+            }
+            Self::OptionalVarInt(v) => VarInt(v.map_or(0, |v| v + 1)).encode(writer),
+        }
+    }
+
+    fn value_bytes(&self) -> usize {
+        match self {
+            Self::Byte(_) | Self::Boolean(_) => 1,
+            Self::VarInt(v)
+            | Self::Direction(v)
+            | Self::BlockState(v)
+            | Self::Pose(v)
+            | Self::OptionalBlockState(Some(v)) => VarInt(*v).num_bytes(),
+            Self::VarLong(v) => VarLong(*v).num_bytes(),
+            Self::Float(_) => 4,
+            Self::String(v) => v.num_bytes(),
+            Self::Text(v) => v.num_bytes(),
+            Self::OptionalText(v) => v.num_bytes(),
+            Self::Rotations(v) => v.num_bytes(),
+            Self::Position(v) => v.num_bytes(),
+            Self::OptionalPosition(v) => v.num_bytes(),
+            Self::OptionalUuid(v) => v.num_bytes(),
+            Self::OptionalBlockState(None) => 1,
+            Self::VillagerData {
+                kind,
+                profession,
+                level,
+            } => {
+                VarInt(*kind).num_bytes()
+                    + VarInt(*profession).num_bytes()
+                    + VarInt(*level).num_bytes()
+            }
+            Self::OptionalVarInt(v) => VarInt(v.map_or(0, |v| v + 1)).num_bytes(),
+        }
+    }
+}
+
+/// The entries of Set Entity Metadata, by index
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntityMetadata {
+    pub entries: Vec<(u8, MetadataValue)>,
+    /// The type of the first entry that could not be decoded, which is dropped along with every
+    /// entry after it
+    pub skipped: Option<i32>,
+}
+
+impl EntityMetadata {
+    const END: u8 = 0xFF;
+}
+
+impl MinecraftData for EntityMetadata {
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let mut metadata = EntityMetadata::default();
+        loop {
+            let index = u8::decode(reader)?;
+            if index == Self::END {
+                return Ok(metadata);
+            }
+            let type_id = VarInt::decode(reader)?.0;
+            match MetadataValue::decode_type(type_id, reader)? {
+                Some(value) => metadata.entries.push((index, value)),
+                None => {
+                    // there is no telling where an unknown value ends, so nothing after it can
+                    // be read; metadata is the last field of every packet that carries it
+                    RemainingBytes::<MAX_PACKET_LEN>::decode(reader)?;
+                    metadata.skipped = Some(type_id);
+                    return Ok(metadata);
+                }
+            }
+        }
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        if let Some(type_id) = self.skipped {
+            return Err(anyhow!(
+                "cannot encode metadata with a skipped {type_id} value"
+            ));
+        }
+        for (index, value) in self.entries {
+            index.encode(writer)?;
+            VarInt(value.type_id()).encode(writer)?;
+            value.encode_value(writer)?;
+        }
+        Self::END.encode(writer)
+    }
+
+    fn num_bytes(&self) -> usize {
+        let entries: usize = self
+            .entries
+            .iter()
+            .map(|(_, value)| 1 + VarInt(value.type_id()).num_bytes() + value.value_bytes())
+            .sum();
+        entries + 1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub id: i32,
+    pub uuid: UUID,
+    /// Id in the `minecraft:entity_type` registry, `None` for our own player, which is never
+    /// spawned
+    pub kind: Option<i32>,
+    /// The name of the type from the bundled table, without the `minecraft:` namespace, when
+    /// the entity was spawned in [`ENTITY_TYPES_VERSION`] or is our own player
+    pub type_name: Option<&'static str>,
+    pub position: Vec3d,
+    /// Blocks per tick
+    pub velocity: Vec3d,
+    /// Degrees
+    pub yaw: f32,
+    pub pitch: f32,
+    pub head_yaw: f32,
+    pub on_ground: bool,
+    /// From Spawn Entity, with a meaning that depends on the type
+    pub data: i32,
+    /// The latest value at each index
+    pub metadata: BTreeMap<u8, MetadataValue>,
+}
+
+impl Entity {
+    fn new(id: i32) -> Self {
+        Self {
+            id,
+            uuid: UUID(0),
+            kind: None,
+            type_name: None,
+            position: Vec3d::default(),
+            velocity: Vec3d::default(),
+            yaw: 0.0,
+            pitch: 0.0,
+            head_yaw: 0.0,
+            on_ground: false,
+            data: 0,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Moves by a delta in 1/4096 of a block
+    fn move_by(&mut self, dx: i16, dy: i16, dz: i16) {
+        self.position.x += dx as f64 / 4096.0;
+        self.position.y += dy as f64 / 4096.0;
+        self.position.z += dz as f64 / 4096.0;
+    }
+}
+
+/// The entities the server has told us about, keyed by entity id.
+///
+/// Filled in by the play loop. Our own player is kept here too, under the id from Login in
+/// play, with its position following the one the connection simulates.
+#[derive(Debug, Clone, Default)]
+pub struct Entities {
+    entities: HashMap<i32, Entity>,
+    local_player: Option<i32>,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets every entity and starts tracking our own player under a new id, as after Login
+    /// in play
+    pub fn reset(&mut self, local_player: i32) {
+        self.entities.clear();
+        let mut player = Entity::new(local_player);
+        player.type_name = Some("player");
+        self.entities.insert(local_player, player);
+        self.local_player = Some(local_player);
+    }
+
+    /// Forgets every entity but our own player, as after a respawn
+    pub fn clear(&mut self) {
+        let player = self.local_player.and_then(|id| self.entities.remove(&id));
+        self.entities.clear();
+        if let Some(player) = player {
+            self.entities.insert(player.id, player);
+        }
+    }
+
+    pub fn get(&self, id: i32) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: i32) -> Option<&mut Entity> {
+        self.entities.get_mut(&id)
+    }
+
+    /// Entities in no particular order, our own player included
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn local_player_id(&self) -> Option<i32> {
+        self.local_player
+    }
+
+    pub fn local_player(&self) -> Option<&Entity> {
+        self.get(self.local_player?)
+    }
+
+    pub fn local_player_mut(&mut self) -> Option<&mut Entity> {
+        self.entities.get_mut(&self.local_player?)
+    }
+
+    /// Adds an entity spawned by a server speaking `version`, replacing any with the same id
+    pub fn spawn(&mut self, packet: SpawnEntityPacket, version: ProtocolVersion) -> &Entity {
+        let id = packet.entity_id.0;
+        let kind = packet.kind.0;
+        let type_name = usize::try_from(kind)
+            .ok()
+            .and_then(|id| ENTITY_TYPES.get(id).copied())
+            // other versions number types differently
+            .filter(|_| version == ENTITY_TYPES_VERSION);
+        let entity = Entity {
+            id,
+            uuid: packet.uuid,
+            kind: Some(kind),
+            type_name,
+            position: packet.position,
+            velocity: packet.velocity(),
+            yaw: packet.yaw.to_degrees(),
+            pitch: packet.pitch.to_degrees(),
+            head_yaw: packet.head_yaw.to_degrees(),
+            on_ground: false,
+            data: packet.data.0,
+            metadata: BTreeMap::new(),
+        };
+        let slot = self.entities.entry(id).insert_entry(entity);
+        slot.into_mut()
+    }
+
+    /// Applies Update Entity Position, returning false if the entity is unknown
+    pub fn update_position(&mut self, packet: &UpdateEntityPositionPacket) -> bool {
+        let Some(entity) = self.get_mut(packet.entity_id.0) else {
+            return false;
+        };
+        entity.move_by(packet.delta_x, packet.delta_y, packet.delta_z);
+        entity.on_ground = packet.on_ground;
+        true
+    }
+
+    pub fn update_position_and_rotation(
+        &mut self,
+        packet: &UpdateEntityPositionAndRotationPacket,
+    ) -> bool {
+        let Some(entity) = self.get_mut(packet.entity_id.0) else {
+            return false;
+        };
+        entity.move_by(packet.delta_x, packet.delta_y, packet.delta_z);
+        entity.yaw = packet.yaw.to_degrees();
+        entity.pitch = packet.pitch.to_degrees();
+        entity.on_ground = packet.on_ground;
+        true
+    }
+
+    pub fn update_rotation(&mut self, packet: &UpdateEntityRotationPacket) -> bool {
+        let Some(entity) = self.get_mut(packet.entity_id.0) else {
+            return false;
+        };
+        entity.yaw = packet.yaw.to_degrees();
+        entity.pitch = packet.pitch.to_degrees();
+        entity.on_ground = packet.on_ground;
+        true
+    }
+
+    pub fn teleport(&mut self, packet: &TeleportEntityPacket) -> bool {
+        let Some(entity) = self.get_mut(packet.entity_id.0) else {
+            return false;
+        };
+        entity.position = packet.position;
+        entity.velocity = packet.velocity;
+        entity.yaw = packet.yaw;
+        entity.pitch = packet.pitch;
+        entity.on_ground = packet.on_ground;
+        true
+    }
+
+    pub fn set_velocity(&mut self, packet: &SetEntityVelocityPacket) -> bool {
+        let Some(entity) = self.get_mut(packet.entity_id.0) else {
+            return false;
+        };
+        entity.velocity = packet.velocity();
+        true
+    }
+
+    /// Merges in the entries of Set Entity Metadata, which only carries the ones that changed
+    pub fn set_metadata(&mut self, packet: SetEntityMetadataPacket) -> bool {
+        let Some(entity) = self.get_mut(packet.entity_id.0) else {
+            return false;
+        };
+        entity.metadata.extend(packet.metadata.entries);
+        true
+    }
+
+    pub fn set_head_rotation(&mut self, packet: &SetHeadRotationPacket) -> bool {
+        let Some(entity) = self.get_mut(packet.entity_id.0) else {
+            return false;
+        };
+        entity.head_yaw = packet.head_yaw.to_degrees();
+        true
+    }
+
+    /// Removes the entities, except our own player, returning how many were known
+    pub fn remove(&mut self, packet: &RemoveEntitiesPacket) -> usize {
+        packet
+            .entity_ids
+            .iter()
+            .filter(|id| Some(id.0) != self.local_player)
+            .filter(|id| self.entities.remove(&id.0).is_some())
+            .count()
+    }
+}
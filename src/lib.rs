@@ -1,3 +1,21 @@
+// lets the paths emitted by `#[derive(MinecraftData)]` resolve inside this crate too
+extern crate self as minecraft_client;
+
+pub mod block;
+pub mod chunk;
 pub mod connection;
+pub mod cookie;
+pub mod data_pack;
 pub mod datatypes;
+pub mod entity;
+pub mod nbt;
 pub mod packet;
+pub mod pathfinding;
+pub mod player;
+pub mod plugin;
+pub mod protocol;
+pub mod reconnect;
+pub mod registry;
+pub mod resource_pack;
+pub mod text;
+pub mod world;
@@ -1,18 +1,25 @@
 use anyhow::Result;
-use minecraft_client::connection::Connection;
+use minecraft_client::reconnect::{ClientEvent, ReconnectingClient};
 
 fn main() -> Result<()> {
     let host = "localhost";
     // let host = "play.budpe.com";
     let port = 25565;
-    let mut conn = Connection::connect(host.to_string(), port)?;
 
-    // let status = conn.get_status()?;
+    // let status = Connection::connect(host.to_string(), port)?.get_status()?;
     // println!("{}", status);
 
-    conn.login()?;
-    conn.configure()?;
-    conn.play()?;
-
-    Ok(())
+    let mut client = ReconnectingClient::new(host.to_string(), port);
+    client.set_version(None);
+    client.on_event(|event| match event {
+        ClientEvent::VersionChosen {
+            server_protocol,
+            version,
+        } if version.protocol() != *server_protocol => eprintln!(
+            "server speaks protocol {server_protocol}, using {} instead",
+            version.protocol()
+        ),
+        _ => eprintln!("{event:?}"),
+    });
+    client.run()
 }
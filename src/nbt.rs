@@ -0,0 +1,440 @@
+use std::io::Write;
+
+use anyhow::anyhow;
+
+use crate::datatypes::{check_len_fits, Error, MinecraftData, MinecraftDataRef, MinecraftRead};
+
+/// Deepest nesting of lists and compounds we will follow, same as vanilla
+const MAX_DEPTH: usize = 512;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// An NBT tag in the network format, where the root tag has a type but no name.
+///
+/// Compounds keep their entries in the order they were received.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nbt {
+    End,
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Nbt>),
+    Compound(Vec<(String, Nbt)>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Nbt {
+    fn tag_type(&self) -> u8 {
+        match self {
+            Nbt::End => TAG_END,
+            Nbt::Byte(_) => TAG_BYTE,
+            Nbt::Short(_) => TAG_SHORT,
+            Nbt::Int(_) => TAG_INT,
+            Nbt::Long(_) => TAG_LONG,
+            Nbt::Float(_) => TAG_FLOAT,
+            Nbt::Double(_) => TAG_DOUBLE,
+            Nbt::ByteArray(_) => TAG_BYTE_ARRAY,
+            Nbt::String(_) => TAG_STRING,
+            Nbt::List(_) => TAG_LIST,
+            Nbt::Compound(_) => TAG_COMPOUND,
+            Nbt::IntArray(_) => TAG_INT_ARRAY,
+            Nbt::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    /// Looks up an entry of a compound
+    pub fn get(&self, key: &str) -> Option<&Nbt> {
+        match self {
+            Nbt::Compound(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Any numeric tag widened to an i64, which is how vanilla reads ints out of compounds
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Nbt::Byte(v) => Some(v as i64),
+            Nbt::Short(v) => Some(v as i64),
+            Nbt::Int(v) => Some(v as i64),
+            Nbt::Long(v) => Some(v),
+            Nbt::Float(v) => Some(v as i64),
+            Nbt::Double(v) => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Nbt::Float(v) => Some(v as f64),
+            Nbt::Double(v) => Some(v),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Nbt::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Nbt]> {
+        match self {
+            Nbt::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    fn decode_payload<R: MinecraftRead>(
+        tag_type: u8,
+        reader: &mut R,
+        depth: usize,
+    ) -> Result<Self, Error> {
+        if depth > MAX_DEPTH {
+            return Err(anyhow!("NBT nested deeper than {MAX_DEPTH}"));
+        }
+        Ok(match tag_type {
+            TAG_END => Nbt::End,
+            TAG_BYTE => Nbt::Byte(i8::decode(reader)?),
+            TAG_SHORT => Nbt::Short(i16::decode(reader)?),
+            TAG_INT => Nbt::Int(i32::decode(reader)?),
+            TAG_LONG => Nbt::Long(i64::decode(reader)?),
+            TAG_FLOAT => Nbt::Float(f32::decode(reader)?),
+            TAG_DOUBLE => Nbt::Double(f64::decode(reader)?),
+            TAG_BYTE_ARRAY => Nbt::ByteArray(decode_nbt_array(reader)?),
+            TAG_STRING => Nbt::String(decode_nbt_string(reader)?),
+            TAG_LIST => {
+                let elem_type = u8::decode(reader)?;
+                let len = decode_list_len(elem_type, reader)?;
+                check_len_fits(len, payload_min_bytes(elem_type), reader.remaining())?;
+                let mut list = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    list.push(Self::decode_payload(elem_type, reader, depth + 1)?);
+                }
+                Nbt::List(list)
+            }
+            TAG_COMPOUND => {
+                let mut entries = Vec::new();
+                loop {
+                    let entry_type = u8::decode(reader)?;
+                    if entry_type == TAG_END {
+                        break;
+                    }
+                    let key = decode_nbt_string(reader)?;
+                    entries.push((key, Self::decode_payload(entry_type, reader, depth + 1)?));
+                }
+                Nbt::Compound(entries)
+            }
+            TAG_INT_ARRAY => Nbt::IntArray(decode_nbt_array(reader)?),
+            TAG_LONG_ARRAY => Nbt::LongArray(decode_nbt_array(reader)?),
+            _ => return Err(anyhow!("invalid NBT tag type {tag_type}")),
+        })
+    }
+
+    fn encode_payload<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        match self {
+            Nbt::End => {}
+            Nbt::Byte(v) => v.encode(writer)?,
+            Nbt::Short(v) => v.encode(writer)?,
+            Nbt::Int(v) => v.encode(writer)?,
+            Nbt::Long(v) => v.encode(writer)?,
+            Nbt::Float(v) => v.encode(writer)?,
+            Nbt::Double(v) => v.encode(writer)?,
+            Nbt::ByteArray(v) => encode_nbt_array(v, writer)?,
+            Nbt::String(s) => encode_nbt_string(&s, writer)?,
+            Nbt::List(list) => {
+                let elem_type = list.first().map_or(TAG_END, Nbt::tag_type);
+                if list.iter().any(|elem| elem.tag_type() != elem_type) {
+                    return Err(anyhow!("NBT list elements must all have the same type"));
+                }
+                elem_type.encode(writer)?;
+                (list.len() as i32).encode(writer)?;
+                for elem in list {
+                    elem.encode_payload(writer)?;
+                }
+            }
+            Nbt::Compound(entries) => {
+                for (key, value) in entries {
+                    value.tag_type().encode(writer)?;
+                    encode_nbt_string(&key, writer)?;
+                    value.encode_payload(writer)?;
+                }
+                TAG_END.encode(writer)?;
+            }
+            Nbt::IntArray(v) => encode_nbt_array(v, writer)?,
+            Nbt::LongArray(v) => encode_nbt_array(v, writer)?,
+        }
+        Ok(())
+    }
+
+    fn payload_num_bytes(&self) -> usize {
+        match self {
+            Nbt::End => 0,
+            Nbt::Byte(_) => 1,
+            Nbt::Short(_) => 2,
+            Nbt::Int(_) | Nbt::Float(_) => 4,
+            Nbt::Long(_) | Nbt::Double(_) => 8,
+            Nbt::ByteArray(v) => 4 + v.len(),
+            Nbt::String(s) => nbt_string_num_bytes(s),
+            Nbt::List(list) => 5 + list.iter().map(Nbt::payload_num_bytes).sum::<usize>(),
+            Nbt::Compound(entries) => {
+                entries
+                    .iter()
+                    .map(|(key, value)| 1 + nbt_string_num_bytes(key) + value.payload_num_bytes())
+                    .sum::<usize>()
+                    + 1
+            }
+            Nbt::IntArray(v) => 4 + 4 * v.len(),
+            Nbt::LongArray(v) => 4 + 8 * v.len(),
+        }
+    }
+}
+
+impl MinecraftData for Nbt {
+    const MIN_BYTES: usize = 1;
+
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let tag_type = u8::decode(reader)?;
+        Self::decode_payload(tag_type, reader, 0)
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        self.tag_type().encode(writer)?;
+        self.encode_payload(writer)
+    }
+
+    fn num_bytes(&self) -> usize {
+        1 + self.payload_num_bytes()
+    }
+}
+
+impl<'a> MinecraftDataRef<'a> for Nbt {
+    const MIN_BYTES: usize = 1;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        Self::decode(buf)
+    }
+}
+
+/// An NBT tag borrowed from the frame it was received in, validated but not parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NbtRef<'a>(&'a [u8]);
+
+impl<'a> NbtRef<'a> {
+    /// The encoded tag, including its leading type byte
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.0[0] == TAG_END
+    }
+
+    pub fn to_owned(&self) -> Result<Nbt, Error> {
+        Nbt::decode(&mut &self.0[..])
+    }
+}
+
+impl<'a> MinecraftDataRef<'a> for NbtRef<'a> {
+    const MIN_BYTES: usize = 1;
+
+    fn decode_ref(buf: &mut &'a [u8]) -> Result<Self, Error> {
+        let start = *buf;
+        let tag_type = u8::decode(buf)?;
+        skip_payload(tag_type, buf, 0)?;
+        let len = start.len() - buf.len();
+        Ok(NbtRef(&start[..len]))
+    }
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if buf.len() < len {
+        return Err(anyhow!("NBT runs past the end of the frame"));
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+fn skip_payload(tag_type: u8, buf: &mut &[u8], depth: usize) -> Result<(), Error> {
+    if depth > MAX_DEPTH {
+        return Err(anyhow!("NBT nested deeper than {MAX_DEPTH}"));
+    }
+    match tag_type {
+        TAG_END => {}
+        TAG_BYTE => _ = take(buf, 1)?,
+        TAG_SHORT => _ = take(buf, 2)?,
+        TAG_INT | TAG_FLOAT => _ = take(buf, 4)?,
+        TAG_LONG | TAG_DOUBLE => _ = take(buf, 8)?,
+        TAG_BYTE_ARRAY | TAG_INT_ARRAY | TAG_LONG_ARRAY => {
+            let elem_size = match tag_type {
+                TAG_BYTE_ARRAY => 1,
+                TAG_INT_ARRAY => 4,
+                _ => 8,
+            };
+            let len = decode_nbt_len(buf)?;
+            let bytes = len
+                .checked_mul(elem_size)
+                .ok_or_else(|| anyhow!("NBT array too long"))?;
+            take(buf, bytes)?;
+        }
+        TAG_STRING => {
+            let len = u16::decode(buf)? as usize;
+            take(buf, len)?;
+        }
+        TAG_LIST => {
+            let elem_type = u8::decode(buf)?;
+            let len = decode_list_len(elem_type, buf)?;
+            check_len_fits(len, payload_min_bytes(elem_type), Some(buf.len()))?;
+            for _ in 0..len {
+                skip_payload(elem_type, buf, depth + 1)?;
+            }
+        }
+        TAG_COMPOUND => loop {
+            let entry_type = u8::decode(buf)?;
+            if entry_type == TAG_END {
+                break;
+            }
+            let key_len = u16::decode(buf)? as usize;
+            take(buf, key_len)?;
+            skip_payload(entry_type, buf, depth + 1)?;
+        },
+        _ => return Err(anyhow!("invalid NBT tag type {tag_type}")),
+    }
+    Ok(())
+}
+
+fn decode_nbt_len<R: MinecraftRead>(reader: &mut R) -> Result<usize, Error> {
+    let len = i32::decode(reader)?;
+    if len < 0 {
+        return Err(anyhow!("negative NBT length {len}"));
+    }
+    Ok(len as usize)
+}
+
+/// Reads the length of a list, which can only be empty if its elements are TAG_End, since
+/// those take no bytes and would let a five byte header claim billions of them
+fn decode_list_len<R: MinecraftRead>(elem_type: u8, reader: &mut R) -> Result<usize, Error> {
+    let len = decode_nbt_len(reader)?;
+    if elem_type == TAG_END && len > 0 {
+        return Err(anyhow!("NBT list of {len} TAG_End elements"));
+    }
+    Ok(len)
+}
+
+/// Fewest bytes a payload of the type takes
+fn payload_min_bytes(tag_type: u8) -> usize {
+    match tag_type {
+        TAG_END => 0,
+        TAG_BYTE | TAG_COMPOUND => 1,
+        TAG_SHORT | TAG_STRING => 2,
+        TAG_INT | TAG_FLOAT | TAG_BYTE_ARRAY | TAG_INT_ARRAY | TAG_LONG_ARRAY => 4,
+        TAG_LIST => 5,
+        _ => 8,
+    }
+}
+
+fn decode_nbt_array<R: MinecraftRead, T: MinecraftData>(reader: &mut R) -> Result<Vec<T>, Error> {
+    let len = decode_nbt_len(reader)?;
+    check_len_fits(len, T::MIN_BYTES, reader.remaining())?;
+    let mut res = Vec::with_capacity(len.min(4096));
+    for _ in 0..len {
+        res.push(T::decode(reader)?);
+    }
+    Ok(res)
+}
+
+fn encode_nbt_array<W: Write, T: MinecraftData>(data: Vec<T>, writer: &mut W) -> Result<(), Error> {
+    (data.len() as i32).encode(writer)?;
+    for elem in data {
+        elem.encode(writer)?;
+    }
+    Ok(())
+}
+
+/// NBT strings are Java's modified UTF-8: NUL is two bytes and characters outside the BMP are
+/// written as a surrogate pair of three byte sequences.
+fn decode_nbt_string<R: MinecraftRead>(reader: &mut R) -> Result<String, Error> {
+    let len = u16::decode(reader)? as usize;
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    // modified UTF-8 only differs from UTF-8 in sequences that plain UTF-8 rejects
+    if let Ok(s) = std::str::from_utf8(&buf) {
+        return Ok(s.to_owned());
+    }
+    let mut units = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < buf.len() {
+        let b = buf[i] as u16;
+        let continuation = |j: usize| -> Result<u16, Error> {
+            match buf.get(j) {
+                Some(&c) if c & 0xC0 == 0x80 => Ok((c & 0x3F) as u16),
+                _ => Err(anyhow!("invalid modified UTF-8 in NBT string")),
+            }
+        };
+        if b & 0x80 == 0 {
+            units.push(b);
+            i += 1;
+        } else if b & 0xE0 == 0xC0 {
+            units.push((b & 0x1F) << 6 | continuation(i + 1)?);
+            i += 2;
+        } else if b & 0xF0 == 0xE0 {
+            units.push((b & 0x0F) << 12 | continuation(i + 1)? << 6 | continuation(i + 2)?);
+            i += 3;
+        } else {
+            return Err(anyhow!("invalid modified UTF-8 in NBT string"));
+        }
+    }
+    Ok(String::from_utf16(&units)?)
+}
+
+fn encode_nbt_string<W: Write>(s: &str, writer: &mut W) -> Result<(), Error> {
+    let len = nbt_string_num_bytes(s) - 2;
+    if len > u16::MAX as usize {
+        return Err(anyhow!("NBT string is too long! ({len} bytes)"));
+    }
+    (len as u16).encode(writer)?;
+    for unit in s.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => writer.write_all(&[unit as u8])?,
+            0x0000 | 0x0080..=0x07FF => {
+                writer.write_all(&[0xC0 | (unit >> 6) as u8, 0x80 | (unit & 0x3F) as u8])?
+            }
+            _ => writer.write_all(&[
+                0xE0 | (unit >> 12) as u8,
+                0x80 | ((unit >> 6) & 0x3F) as u8,
+                0x80 | (unit & 0x3F) as u8,
+            ])?,
+        }
+    }
+    Ok(())
+}
+
+fn nbt_string_num_bytes(s: &str) -> usize {
+    2 + s
+        .encode_utf16()
+        .map(|unit| match unit {
+            0x0001..=0x007F => 1,
+            0x0000 | 0x0080..=0x07FF => 2,
+            _ => 3,
+        })
+        .sum::<usize>()
+}
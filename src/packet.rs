@@ -1,40 +1,118 @@
-use std::io::{Read, Write};
+use std::io::Write;
 
 use anyhow::anyhow;
-use minecraft_derive::MinecraftData;
 
-use crate::datatypes::{
-    Error, GameProfile, IDSet, Identifier, MString, MinecraftData, Position, SlotDisplay, Tag,
-    VarInt, UUID,
+use crate::{
+    chunk::{BlockEntity, BlockEntityRef, Heightmap, LightData},
+    cookie::{Cookie, MAX_COOKIE_LEN},
+    datatypes::{
+        decode_frame, Angle, ByteSlice, Error, GameProfile, IDSet, Identifier, IdentifierRef,
+        LpVec3, MString, MinecraftData, MinecraftDataRef, MinecraftRead, Position, RemainingBytes,
+        SlotDisplay, Tag, VarInt, VarLong, Vec3d, UUID,
+    },
+    entity::EntityMetadata,
+    nbt::{Nbt, NbtRef},
+    protocol::ProtocolVersion,
 };
 
 pub trait Packet: MinecraftData {
     const ID: VarInt;
 
     /// wrapper around Self::decode so that the interface is more symmetric
-    fn decode_packet<R: Read>(reader: &mut R) -> Result<Self, Error> {
+    fn decode_packet<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
         Self::decode(reader)
     }
 
+    /// Encodes the packet as the latest protocol version has it
     fn encode_packet<W: Write>(self, writer: &mut W) -> Result<(), Error> {
-        let len = Self::ID.num_bytes() + self.num_bytes();
+        self.encode_packet_with_id(ProtocolVersion::LATEST, Self::ID, writer)
+    }
+
+    /// Encodes the packet for `version`, under the id that version numbers it with
+    fn encode_packet_with_id<W: Write>(
+        self,
+        version: ProtocolVersion,
+        id: VarInt,
+        writer: &mut W,
+    ) -> Result<(), Error> {
+        let len = id.num_bytes() + version.num_bytes(&self);
         VarInt(len as i32).encode(writer)?;
-        Self::ID.encode(writer)?;
-        self.encode(writer)?;
+        id.encode(writer)?;
+        version.encode(self, writer)?;
         Ok(())
     }
 }
 
-pub fn decode_packet_header<R: Read>(reader: &mut R) -> Result<PacketHeader, Error> {
+/// Largest frame vanilla accepts, the most a 3 byte `VarInt` length can describe
+pub const MAX_PACKET_LEN: usize = 2097151;
+
+pub fn decode_packet_header<R: MinecraftRead>(reader: &mut R) -> Result<PacketHeader, Error> {
     let len = VarInt::decode(reader)?;
+    if len.0 <= 0 || len.0 as usize > MAX_PACKET_LEN {
+        return Err(anyhow!("invalid packet length {}", len.0));
+    }
     let id = VarInt::decode(reader)?;
-    Ok(PacketHeader { len, id })
+    if id.num_bytes() > len.0 as usize {
+        return Err(anyhow!(
+            "packet id {:?} does not fit in length {}",
+            id,
+            len.0
+        ));
+    }
+    Ok(PacketHeader {
+        len,
+        id,
+        body_len: len.0 as usize - id.num_bytes(),
+    })
+}
+
+/// Decodes a packet from its complete body as `version` sends it, rejecting it if any bytes are
+/// left over.
+pub fn decode_packet_body<P: Packet>(
+    mut body: &[u8],
+    version: ProtocolVersion,
+) -> Result<P, Error> {
+    let packet = decode_frame::<P>(&mut body, version)?;
+    if !body.is_empty() {
+        return Err(anyhow!(
+            "{} unread bytes at the end of {}",
+            body.len(),
+            std::any::type_name::<P>()
+        ));
+    }
+    Ok(packet)
+}
+
+/// Decodes a packet that borrows from its body as `version` sends it, rejecting it if any bytes
+/// are left over.
+pub fn decode_packet_body_ref<'a, P: MinecraftDataRef<'a>>(
+    mut body: &'a [u8],
+    version: ProtocolVersion,
+) -> Result<P, Error> {
+    let packet = version.with_current(|| P::decode_ref(&mut body))?;
+    if !body.is_empty() {
+        return Err(anyhow!(
+            "{} unread bytes at the end of {}",
+            body.len(),
+            std::any::type_name::<P>()
+        ));
+    }
+    Ok(packet)
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct PacketHeader {
     pub len: VarInt,
+    /// The id in the latest protocol version, whatever the server sent
     pub id: VarInt,
+    body_len: usize,
+}
+
+impl PacketHeader {
+    /// Length of the packet body that follows the id
+    pub fn body_len(&self) -> usize {
+        self.body_len
+    }
 }
 
 #[derive(Debug, Clone, Copy, MinecraftData)]
@@ -85,6 +163,16 @@ impl Packet for LoginStartPacket {
     const ID: VarInt = VarInt(0x00);
 }
 
+/// `reason` is a JSON text component
+#[derive(Debug, Clone, MinecraftData)]
+pub struct LoginDisconnectPacket {
+    pub reason: MString<262144>,
+}
+
+impl Packet for LoginDisconnectPacket {
+    const ID: VarInt = VarInt(0x00);
+}
+
 #[derive(Debug, Clone, MinecraftData)]
 pub struct EncryptionRequestPacket {
     pub server_id: MString<20>,
@@ -113,60 +201,305 @@ impl Packet for LoginAcknowledgedPacket {
     const ID: VarInt = VarInt(0x03);
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, MinecraftData)]
+pub struct LoginCookieRequestPacket {
+    pub key: Identifier,
+}
+
+impl Packet for LoginCookieRequestPacket {
+    const ID: VarInt = VarInt(0x05);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct LoginCookieResponsePacket {
+    pub key: Identifier,
+    #[mc(max_len = MAX_COOKIE_LEN)]
+    pub payload: Option<Vec<u8>>,
+}
+
+impl Packet for LoginCookieResponsePacket {
+    const ID: VarInt = VarInt(0x04);
+}
+
+/// Largest login plugin payload vanilla accepts in either direction
+pub const MAX_LOGIN_PLUGIN_LEN: usize = 1048576;
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct LoginPluginRequestPacket {
+    pub message_id: VarInt,
+    pub channel: Identifier,
+    pub data: RemainingBytes<MAX_LOGIN_PLUGIN_LEN>,
+}
+
+impl Packet for LoginPluginRequestPacket {
+    const ID: VarInt = VarInt(0x04);
+}
+
+/// `data` is `None` when the client does not understand the channel
+#[derive(Debug, Clone, MinecraftData)]
+pub struct LoginPluginResponsePacket {
+    pub message_id: VarInt,
+    pub data: Option<RemainingBytes<MAX_LOGIN_PLUGIN_LEN>>,
+}
+
+impl Packet for LoginPluginResponsePacket {
+    const ID: VarInt = VarInt(0x02);
+}
+
+/// Largest plugin message payload vanilla accepts from a server
+pub const MAX_PLUGIN_MESSAGE_LEN: usize = 1048576;
+
+/// The `minecraft:register` and `minecraft:unregister` payload: channel names separated by NUL
+/// bytes, running to the end of the packet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelList(pub Vec<Identifier>);
+
+impl MinecraftData for ChannelList {
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let bytes = RemainingBytes::<MAX_PLUGIN_MESSAGE_LEN>::decode(reader)?.0;
+        let mut channels = Vec::new();
+        for name in bytes.split(|&b| b == 0).filter(|name| !name.is_empty()) {
+            channels.push(Identifier::try_from(String::from_utf8(name.to_vec())?)?);
+        }
+        Ok(ChannelList(channels))
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        for (i, channel) in self.0.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(&[0])?;
+            }
+            writer.write_all(channel.as_str().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn num_bytes(&self) -> usize {
+        let names: usize = self.0.iter().map(|channel| channel.as_str().len()).sum();
+        names + self.0.len().saturating_sub(1)
+    }
+}
+
+/// A plugin message's channel and payload. Channels the client has no type for keep their raw
+/// payload so the message can be re-encoded unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PluginChannelData {
     MinecraftBrand(MString<32767>),
-    Unknown(Identifier),
+    Register(ChannelList),
+    Unregister(ChannelList),
+    Unknown(Identifier, Vec<u8>),
 }
 
 impl PluginChannelData {
-    fn identifier(&self) -> Identifier {
+    pub const BRAND: &str = "minecraft:brand";
+    pub const REGISTER: &str = "minecraft:register";
+    pub const UNREGISTER: &str = "minecraft:unregister";
+
+    pub fn identifier(&self) -> Identifier {
         match self {
-            Self::MinecraftBrand(_) => Identifier::try_from("minecraft:brand".to_owned()).unwrap(),
-            Self::Unknown(id) => id.clone(),
+            Self::MinecraftBrand(_) => Identifier::try_from(Self::BRAND).unwrap(),
+            Self::Register(_) => Identifier::try_from(Self::REGISTER).unwrap(),
+            Self::Unregister(_) => Identifier::try_from(Self::UNREGISTER).unwrap(),
+            Self::Unknown(id, _) => id.clone(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct ClientboundConfigurationPluginMessagePacket {
-    pub data: PluginChannelData,
-}
-
-impl MinecraftData for ClientboundConfigurationPluginMessagePacket {
-    fn decode<R: Read>(reader: &mut R) -> Result<Self, Error> {
+impl MinecraftData for PluginChannelData {
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
         let channel = Identifier::decode(reader)?;
-        let data = match channel.to_string().as_str() {
-            "minecraft:brand" => PluginChannelData::MinecraftBrand(MString::decode(reader)?),
-            _ => PluginChannelData::Unknown(channel),
-        };
-        Ok(Self { data })
+        Ok(match channel.as_str() {
+            Self::BRAND => Self::MinecraftBrand(MString::decode(reader)?),
+            Self::REGISTER => Self::Register(ChannelList::decode(reader)?),
+            Self::UNREGISTER => Self::Unregister(ChannelList::decode(reader)?),
+            _ => Self::Unknown(
+                channel,
+                RemainingBytes::<MAX_PLUGIN_MESSAGE_LEN>::decode(reader)?.0,
+            ),
+        })
     }
 
     fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
-        self.data.identifier().encode(writer)?;
-        match self.data {
-            PluginChannelData::MinecraftBrand(brand) => {
-                brand.encode(writer)?;
-            }
-            PluginChannelData::Unknown(_) => {}
+        self.identifier().encode(writer)?;
+        match self {
+            Self::MinecraftBrand(brand) => brand.encode(writer),
+            Self::Register(channels) | Self::Unregister(channels) => channels.encode(writer),
+            Self::Unknown(_, data) => RemainingBytes::<MAX_PLUGIN_MESSAGE_LEN>(data).encode(writer),
         }
-        Ok(())
     }
 
     fn num_bytes(&self) -> usize {
-        self.data.identifier().num_bytes()
-            + match &self.data {
-                PluginChannelData::MinecraftBrand(brand) => brand.num_bytes(),
-                PluginChannelData::Unknown(_) => 0,
+        self.identifier().num_bytes()
+            + match self {
+                Self::MinecraftBrand(brand) => brand.num_bytes(),
+                Self::Register(channels) | Self::Unregister(channels) => channels.num_bytes(),
+                Self::Unknown(_, data) => data.len(),
             }
     }
 }
 
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ClientboundConfigurationPluginMessagePacket {
+    pub data: PluginChannelData,
+}
+
 impl Packet for ClientboundConfigurationPluginMessagePacket {
     const ID: VarInt = VarInt(0x01);
 }
 
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ServerboundConfigurationPluginMessagePacket {
+    pub data: PluginChannelData,
+}
+
+impl Packet for ServerboundConfigurationPluginMessagePacket {
+    const ID: VarInt = VarInt(0x02);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+pub enum ChatMode {
+    #[mc_repr(VarInt(0))]
+    Enabled,
+    #[mc_repr(VarInt(1))]
+    CommandsOnly,
+    #[mc_repr(VarInt(2))]
+    Hidden,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+pub enum MainHand {
+    #[mc_repr(VarInt(0))]
+    Left,
+    #[mc_repr(VarInt(1))]
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+pub enum ParticleStatus {
+    #[mc_repr(VarInt(0))]
+    All,
+    #[mc_repr(VarInt(1))]
+    Decreased,
+    #[mc_repr(VarInt(2))]
+    Minimal,
+}
+
+/// What we tell the server in Client Information
+#[derive(Debug, Clone, PartialEq, Eq, MinecraftData)]
+pub struct ClientSettings {
+    /// For example `en_us`
+    pub locale: MString<16>,
+    /// In chunks, the server uses the smaller of this and its own view distance
+    pub view_distance: i8,
+    pub chat_mode: ChatMode,
+    pub chat_colors: bool,
+    /// Bit mask of the `SKIN_*` flags
+    pub displayed_skin_parts: u8,
+    pub main_hand: MainHand,
+    pub enable_text_filtering: bool,
+    /// Whether we show up in the player sample of the server's status
+    pub allow_server_listings: bool,
+    pub particle_status: ParticleStatus,
+}
+
+impl ClientSettings {
+    pub const SKIN_CAPE: u8 = 0x01;
+    pub const SKIN_JACKET: u8 = 0x02;
+    pub const SKIN_LEFT_SLEEVE: u8 = 0x04;
+    pub const SKIN_RIGHT_SLEEVE: u8 = 0x08;
+    pub const SKIN_LEFT_PANTS_LEG: u8 = 0x10;
+    pub const SKIN_RIGHT_PANTS_LEG: u8 = 0x20;
+    pub const SKIN_HAT: u8 = 0x40;
+    pub const SKIN_ALL: u8 = 0x7F;
+}
+
+/// The vanilla defaults
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            locale: MString::try_from("en_us").unwrap(),
+            view_distance: 12,
+            chat_mode: ChatMode::Enabled,
+            chat_colors: true,
+            displayed_skin_parts: Self::SKIN_ALL,
+            main_hand: MainHand::Right,
+            enable_text_filtering: false,
+            allow_server_listings: true,
+            particle_status: ParticleStatus::All,
+        }
+    }
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationClientInformationPacket {
+    pub settings: ClientSettings,
+}
+
+impl Packet for ConfigurationClientInformationPacket {
+    const ID: VarInt = VarInt(0x00);
+}
+
+/// A resource pack the server wants us to load, as sent in Add Resource Pack
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ResourcePackPush {
+    pub uuid: UUID,
+    pub url: MString<32767>,
+    /// Hex SHA-1 of the pack, or empty if the server did not give one
+    pub hash: MString<40>,
+    pub forced: bool,
+    /// Text component shown in the prompt
+    pub prompt: Option<Nbt>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+pub enum ResourcePackStatus {
+    #[mc_repr(VarInt(0))]
+    SuccessfullyLoaded,
+    #[mc_repr(VarInt(1))]
+    Declined,
+    #[mc_repr(VarInt(2))]
+    FailedDownload,
+    #[mc_repr(VarInt(3))]
+    Accepted,
+    #[mc_repr(VarInt(4))]
+    Downloaded,
+    #[mc_repr(VarInt(5))]
+    InvalidUrl,
+    #[mc_repr(VarInt(6))]
+    FailedReload,
+    #[mc_repr(VarInt(7))]
+    Discarded,
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationRemoveResourcePackPacket {
+    /// `None` removes every pack
+    pub uuid: Option<UUID>,
+}
+
+impl Packet for ConfigurationRemoveResourcePackPacket {
+    const ID: VarInt = VarInt(0x08);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationAddResourcePackPacket {
+    pub pack: ResourcePackPush,
+}
+
+impl Packet for ConfigurationAddResourcePackPacket {
+    const ID: VarInt = VarInt(0x09);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationResourcePackResponsePacket {
+    pub uuid: UUID,
+    pub result: ResourcePackStatus,
+}
+
+impl Packet for ConfigurationResourcePackResponsePacket {
+    const ID: VarInt = VarInt(0x06);
+}
+
 #[derive(Debug, Clone, MinecraftData)]
 pub struct FeatureFlagsPacket {
     feature_flags: Vec<Identifier>,
@@ -176,7 +509,7 @@ impl Packet for FeatureFlagsPacket {
     const ID: VarInt = VarInt(0x0C);
 }
 
-#[derive(Debug, Clone, MinecraftData)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, MinecraftData)]
 pub struct DataPack {
     pub namespace: MString<32767>,
     pub id: MString<32767>,
@@ -213,7 +546,7 @@ impl Packet for ConfigurationKeepAlivePacket {
 #[derive(Debug, Clone, MinecraftData)]
 pub struct RegistryEntry {
     pub id: Identifier,
-    pub data: Option<u8>, // TODO: actually NBT
+    pub data: Option<Nbt>,
 }
 
 #[derive(Debug, Clone, MinecraftData)]
@@ -226,6 +559,21 @@ impl Packet for RegistryDataPacket {
     const ID: VarInt = VarInt(0x07);
 }
 
+#[derive(Debug, Clone, MinecraftData)]
+#[mc(borrowed)]
+pub struct RegistryEntryRef<'a> {
+    pub id: IdentifierRef<'a>,
+    pub data: Option<NbtRef<'a>>,
+}
+
+/// [`RegistryDataPacket`] borrowing its ids and NBT from the frame
+#[derive(Debug, Clone, MinecraftData)]
+#[mc(borrowed)]
+pub struct RegistryDataPacketRef<'a> {
+    pub registry_id: IdentifierRef<'a>,
+    pub entries: Vec<RegistryEntryRef<'a>>,
+}
+
 #[derive(Debug, Clone, MinecraftData)]
 pub struct TaggedRegistry {
     pub registry: Identifier,
@@ -234,13 +582,22 @@ pub struct TaggedRegistry {
 
 #[derive(Debug, Clone, MinecraftData)]
 pub struct ConfigurationUpdateTagsPacket {
-    tagged_registries: Vec<TaggedRegistry>,
+    pub tagged_registries: Vec<TaggedRegistry>,
 }
 
 impl Packet for ConfigurationUpdateTagsPacket {
     const ID: VarInt = VarInt(0x0D);
 }
 
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationDisconnectPacket {
+    pub reason: Nbt,
+}
+
+impl Packet for ConfigurationDisconnectPacket {
+    const ID: VarInt = VarInt(0x02);
+}
+
 #[derive(Debug, Clone, Copy, MinecraftData)]
 pub struct FinishConfigurationPacket;
 
@@ -255,6 +612,50 @@ impl Packet for AcknowledgeFinishConfigurationPacket {
     const ID: VarInt = VarInt(0x03);
 }
 
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationCookieRequestPacket {
+    pub key: Identifier,
+}
+
+impl Packet for ConfigurationCookieRequestPacket {
+    const ID: VarInt = VarInt(0x00);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationCookieResponsePacket {
+    pub key: Identifier,
+    #[mc(max_len = MAX_COOKIE_LEN)]
+    pub payload: Option<Vec<u8>>,
+}
+
+impl Packet for ConfigurationCookieResponsePacket {
+    const ID: VarInt = VarInt(0x01);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationStoreCookiePacket {
+    pub cookie: Cookie,
+}
+
+impl Packet for ConfigurationStoreCookiePacket {
+    const ID: VarInt = VarInt(0x0A);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct TransferTarget {
+    pub host: MString<32767>,
+    pub port: VarInt,
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfigurationTransferPacket {
+    pub target: TransferTarget,
+}
+
+impl Packet for ConfigurationTransferPacket {
+    const ID: VarInt = VarInt(0x0B);
+}
+
 #[derive(Debug, Clone, MinecraftData)]
 pub struct ClientboundPlayKeepAlivePacket {
     pub keep_alive_id: i64,
@@ -273,32 +674,111 @@ impl Packet for ServerboundPlayKeepAlivePacket {
     const ID: VarInt = VarInt(0x1B);
 }
 
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ClientboundPlayPluginMessagePacket {
+    pub data: PluginChannelData,
+}
+
+impl Packet for ClientboundPlayPluginMessagePacket {
+    const ID: VarInt = VarInt(0x18);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ServerboundPlayPluginMessagePacket {
+    pub data: PluginChannelData,
+}
+
+impl Packet for ServerboundPlayPluginMessagePacket {
+    const ID: VarInt = VarInt(0x15);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayClientInformationPacket {
+    pub settings: ClientSettings,
+}
+
+impl Packet for PlayClientInformationPacket {
+    const ID: VarInt = VarInt(0x0D);
+}
+
+/// Sends the client back to configuration, for example when a proxy switches servers
+#[derive(Debug, Clone, Copy, MinecraftData)]
+pub struct StartConfigurationPacket;
+
+impl Packet for StartConfigurationPacket {
+    const ID: VarInt = VarInt(0x74);
+}
+
+#[derive(Debug, Clone, Copy, MinecraftData)]
+pub struct ConfigurationAcknowledgedPacket;
+
+impl Packet for ConfigurationAcknowledgedPacket {
+    const ID: VarInt = VarInt(0x0F);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayRemoveResourcePackPacket {
+    pub uuid: Option<UUID>,
+}
+
+impl Packet for PlayRemoveResourcePackPacket {
+    const ID: VarInt = VarInt(0x4E);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayAddResourcePackPacket {
+    pub pack: ResourcePackPush,
+}
+
+impl Packet for PlayAddResourcePackPacket {
+    const ID: VarInt = VarInt(0x4F);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayResourcePackResponsePacket {
+    pub uuid: UUID,
+    pub result: ResourcePackStatus,
+}
+
+impl Packet for PlayResourcePackResponsePacket {
+    const ID: VarInt = VarInt(0x30);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayDisconnectPacket {
+    pub reason: Nbt,
+}
+
+impl Packet for PlayDisconnectPacket {
+    const ID: VarInt = VarInt(0x20);
+}
+
 #[derive(Debug, Clone, MinecraftData)]
 pub struct PlayLoginPacket {
-    entity_id: i32,
-    is_hardcore: bool,
-    dimension_names: Vec<Identifier>,
-    max_players: VarInt,
-    view_distance: VarInt,
-    simulation_distance: VarInt,
-    reduced_debug_info: bool,
-    enable_respawn_screen: bool,
-    do_limited_crafting: bool,
-    dimension_type: VarInt,
-    dimension_name: Identifier,
-    hashed_seed: i64,
-    game_mode: u8,
-    previous_game_mode: i8,
-    is_debug: bool,
-    is_flat: bool,
-    has_death_location: bool,
+    pub entity_id: i32,
+    pub is_hardcore: bool,
+    pub dimension_names: Vec<Identifier>,
+    pub max_players: VarInt,
+    pub view_distance: VarInt,
+    pub simulation_distance: VarInt,
+    pub reduced_debug_info: bool,
+    pub enable_respawn_screen: bool,
+    pub do_limited_crafting: bool,
+    pub dimension_type: VarInt,
+    pub dimension_name: Identifier,
+    pub hashed_seed: i64,
+    pub game_mode: u8,
+    pub previous_game_mode: i8,
+    pub is_debug: bool,
+    pub is_flat: bool,
+    pub has_death_location: bool,
     #[present_if(has_death_location)]
-    death_dimention_name: Option<Identifier>,
+    pub death_dimention_name: Option<Identifier>,
     #[present_if(has_death_location)]
-    death_location: Option<Position>,
-    portal_cooldown: VarInt,
-    sea_level: VarInt,
-    enforced_secure_chat: bool,
+    pub death_location: Option<Position>,
+    pub portal_cooldown: VarInt,
+    pub sea_level: VarInt,
+    pub enforced_secure_chat: bool,
 }
 
 impl Packet for PlayLoginPacket {
@@ -379,3 +859,570 @@ pub struct UpdateRecipesPacket {
 impl Packet for UpdateRecipesPacket {
     const ID: VarInt = VarInt(0x83);
 }
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayCookieRequestPacket {
+    pub key: Identifier,
+}
+
+impl Packet for PlayCookieRequestPacket {
+    const ID: VarInt = VarInt(0x15);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayCookieResponsePacket {
+    pub key: Identifier,
+    #[mc(max_len = MAX_COOKIE_LEN)]
+    pub payload: Option<Vec<u8>>,
+}
+
+impl Packet for PlayCookieResponsePacket {
+    const ID: VarInt = VarInt(0x14);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayStoreCookiePacket {
+    pub cookie: Cookie,
+}
+
+impl Packet for PlayStoreCookiePacket {
+    const ID: VarInt = VarInt(0x76);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayTransferPacket {
+    pub target: TransferTarget,
+}
+
+impl Packet for PlayTransferPacket {
+    const ID: VarInt = VarInt(0x7F);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ChunkDataAndUpdateLightPacket {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub heightmaps: Vec<Heightmap>,
+    /// The sections, which can only be decoded knowing the height of the dimension, see
+    /// [`ChunkSection::decode_all`](crate::chunk::ChunkSection::decode_all)
+    #[mc(max_len = MAX_PACKET_LEN)]
+    pub data: Vec<u8>,
+    pub block_entities: Vec<BlockEntity>,
+    pub light: LightData,
+}
+
+impl Packet for ChunkDataAndUpdateLightPacket {
+    const ID: VarInt = VarInt(0x2C);
+}
+
+/// [`ChunkDataAndUpdateLightPacket`] borrowing its sections and block entity NBT from the frame
+#[derive(Debug, Clone, MinecraftData)]
+#[mc(borrowed)]
+pub struct ChunkDataAndUpdateLightPacketRef<'a> {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub heightmaps: Vec<Heightmap>,
+    #[mc(max_len = MAX_PACKET_LEN)]
+    pub data: ByteSlice<'a>,
+    pub block_entities: Vec<BlockEntityRef<'a>>,
+    pub light: LightData,
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct UpdateLightPacket {
+    pub chunk_x: VarInt,
+    pub chunk_z: VarInt,
+    pub light: LightData,
+}
+
+impl Packet for UpdateLightPacket {
+    const ID: VarInt = VarInt(0x2F);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct UnloadChunkPacket {
+    // z comes first on the wire
+    pub chunk_z: i32,
+    pub chunk_x: i32,
+}
+
+impl Packet for UnloadChunkPacket {
+    const ID: VarInt = VarInt(0x25);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct BlockUpdatePacket {
+    pub location: Position,
+    pub block_state: VarInt,
+}
+
+impl Packet for BlockUpdatePacket {
+    const ID: VarInt = VarInt(0x08);
+}
+
+/// Many block changes within one chunk section
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SectionBlocksUpdatePacket {
+    /// x and z in the top 22 bits each, y in the low 20, in sections
+    pub section_position: i64,
+    /// The block state shifted left by 12, over x, z and y within the section in 4 bits each
+    #[mc(max_len = 4096)]
+    pub blocks: Vec<VarLong>,
+}
+
+impl Packet for SectionBlocksUpdatePacket {
+    const ID: VarInt = VarInt(0x52);
+}
+
+impl SectionBlocksUpdatePacket {
+    /// Section x, y and z
+    pub fn section(&self) -> (i32, i32, i32) {
+        let position = self.section_position;
+        (
+            (position >> 42) as i32,
+            (position << 44 >> 44) as i32,
+            (position << 22 >> 42) as i32,
+        )
+    }
+
+    /// Each change as x, y and z within the section and the new block state
+    pub fn changes(&self) -> impl Iterator<Item = (usize, usize, usize, i32)> + '_ {
+        self.blocks.iter().map(|block| {
+            let block = block.0;
+            (
+                (block >> 8 & 0xF) as usize,
+                (block & 0xF) as usize,
+                (block >> 4 & 0xF) as usize,
+                (block >> 12) as i32,
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ChunkBatchStartPacket;
+
+impl Packet for ChunkBatchStartPacket {
+    const ID: VarInt = VarInt(0x0C);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ChunkBatchFinishedPacket {
+    pub batch_size: VarInt,
+}
+
+impl Packet for ChunkBatchFinishedPacket {
+    const ID: VarInt = VarInt(0x0B);
+}
+
+/// Sent after every Chunk Batch Finished; the server waits for it before sending more batches
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ChunkBatchReceivedPacket {
+    pub chunks_per_tick: f32,
+}
+
+impl Packet for ChunkBatchReceivedPacket {
+    const ID: VarInt = VarInt(0x0A);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct RespawnPacket {
+    pub dimension_type: VarInt,
+    pub dimension_name: Identifier,
+    pub hashed_seed: i64,
+    pub game_mode: u8,
+    pub previous_game_mode: i8,
+    pub is_debug: bool,
+    pub is_flat: bool,
+    pub has_death_location: bool,
+    #[present_if(has_death_location)]
+    pub death_dimension_name: Option<Identifier>,
+    #[present_if(has_death_location)]
+    pub death_location: Option<Position>,
+    pub portal_cooldown: VarInt,
+    pub sea_level: VarInt,
+    pub data_kept: u8,
+}
+
+impl Packet for RespawnPacket {
+    const ID: VarInt = VarInt(0x50);
+}
+
+/// Which fields of Synchronize Player Position are relative to the current value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+pub struct TeleportFlags(pub i32);
+
+impl TeleportFlags {
+    pub const X: i32 = 0x01;
+    pub const Y: i32 = 0x02;
+    pub const Z: i32 = 0x04;
+    pub const YAW: i32 = 0x08;
+    pub const PITCH: i32 = 0x10;
+    pub const VELOCITY_X: i32 = 0x20;
+    pub const VELOCITY_Y: i32 = 0x40;
+    pub const VELOCITY_Z: i32 = 0x80;
+    /// Rotate the current velocity by the change in rotation before adding to it
+    pub const ROTATE_VELOCITY: i32 = 0x100;
+
+    pub fn has(self, flag: i32) -> bool {
+        self.0 & flag == flag
+    }
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SynchronizePlayerPositionPacket {
+    pub teleport_id: VarInt,
+    pub position: Vec3d,
+    pub velocity: Vec3d,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub flags: TeleportFlags,
+}
+
+impl Packet for SynchronizePlayerPositionPacket {
+    const ID: VarInt = VarInt(0x46);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct ConfirmTeleportationPacket {
+    pub teleport_id: VarInt,
+}
+
+impl Packet for ConfirmTeleportationPacket {
+    const ID: VarInt = VarInt(0x00);
+}
+
+/// Flags sent with every movement packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, MinecraftData)]
+pub struct MovementFlags(pub u8);
+
+impl MovementFlags {
+    pub const ON_GROUND: u8 = 0x01;
+    pub const PUSHING_AGAINST_WALL: u8 = 0x02;
+
+    pub fn new(on_ground: bool, pushing_against_wall: bool) -> Self {
+        let mut flags = 0;
+        if on_ground {
+            flags |= Self::ON_GROUND;
+        }
+        if pushing_against_wall {
+            flags |= Self::PUSHING_AGAINST_WALL;
+        }
+        Self(flags)
+    }
+
+    pub fn on_ground(self) -> bool {
+        self.0 & Self::ON_GROUND != 0
+    }
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SetPlayerPositionPacket {
+    /// Feet position
+    pub position: Vec3d,
+    pub flags: MovementFlags,
+}
+
+impl Packet for SetPlayerPositionPacket {
+    const ID: VarInt = VarInt(0x1D);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SetPlayerPositionAndRotationPacket {
+    /// Feet position
+    pub position: Vec3d,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub flags: MovementFlags,
+}
+
+impl Packet for SetPlayerPositionAndRotationPacket {
+    const ID: VarInt = VarInt(0x1E);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SetPlayerRotationPacket {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub flags: MovementFlags,
+}
+
+impl Packet for SetPlayerRotationPacket {
+    const ID: VarInt = VarInt(0x1F);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SetPlayerMovementFlagsPacket {
+    pub flags: MovementFlags,
+}
+
+impl Packet for SetPlayerMovementFlagsPacket {
+    const ID: VarInt = VarInt(0x20);
+}
+
+/// Which movement keys are held, sent whenever that changes
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayerInputPacket {
+    pub flags: u8,
+}
+
+impl PlayerInputPacket {
+    pub const FORWARD: u8 = 0x01;
+    pub const BACKWARD: u8 = 0x02;
+    pub const LEFT: u8 = 0x04;
+    pub const RIGHT: u8 = 0x08;
+    pub const JUMP: u8 = 0x10;
+    pub const SNEAK: u8 = 0x20;
+    pub const SPRINT: u8 = 0x40;
+}
+
+impl Packet for PlayerInputPacket {
+    const ID: VarInt = VarInt(0x2A);
+}
+
+/// An action of Player Command. Before 1.21.6 the list started with starting and stopping
+/// sneaking, which Player Input has taken over since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerAction {
+    LeaveBed,
+    StartSprinting,
+    StopSprinting,
+    StartHorseJump,
+    StopHorseJump,
+    OpenVehicleInventory,
+    StartFlyingWithElytra,
+}
+
+impl PlayerAction {
+    const ALL: [Self; 7] = [
+        Self::LeaveBed,
+        Self::StartSprinting,
+        Self::StopSprinting,
+        Self::StartHorseJump,
+        Self::StopHorseJump,
+        Self::OpenVehicleInventory,
+        Self::StartFlyingWithElytra,
+    ];
+
+    fn id_offset() -> i32 {
+        if ProtocolVersion::current() < ProtocolVersion::V1_21_6 {
+            2
+        } else {
+            0
+        }
+    }
+
+    fn id(self) -> VarInt {
+        VarInt(self as i32 + Self::id_offset())
+    }
+}
+
+impl MinecraftData for PlayerAction {
+    fn decode<R: MinecraftRead>(reader: &mut R) -> Result<Self, Error> {
+        let id = VarInt::decode(reader)?.0;
+        usize::try_from(id - Self::id_offset())
+            .ok()
+            .and_then(|index| Self::ALL.get(index).copied())
+            .ok_or_else(|| anyhow!("unknown player action {id}"))
+    }
+
+    fn encode<W: Write>(self, writer: &mut W) -> Result<(), Error> {
+        self.id().encode(writer)
+    }
+
+    fn num_bytes(&self) -> usize {
+        self.id().num_bytes()
+    }
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayerCommandPacket {
+    pub entity_id: VarInt,
+    pub action: PlayerAction,
+    /// 0 to 100, only for starting a horse jump
+    pub jump_boost: VarInt,
+}
+
+impl Packet for PlayerCommandPacket {
+    const ID: VarInt = VarInt(0x29);
+}
+
+/// Tells the server the world around us has loaded, ending the invulnerability after joining or
+/// respawning
+#[derive(Debug, Clone, MinecraftData)]
+pub struct PlayerLoadedPacket;
+
+impl Packet for PlayerLoadedPacket {
+    const ID: VarInt = VarInt(0x2B);
+}
+
+/// Velocity before 1.21.9, in 1/8000 of a block per tick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, MinecraftData)]
+pub struct ShortVelocity {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+impl From<ShortVelocity> for Vec3d {
+    fn from(value: ShortVelocity) -> Self {
+        Vec3d {
+            x: value.x as f64 / 8000.0,
+            y: value.y as f64 / 8000.0,
+            z: value.z as f64 / 8000.0,
+        }
+    }
+}
+
+impl From<Vec3d> for ShortVelocity {
+    fn from(value: Vec3d) -> Self {
+        let short = |v: f64| (v.clamp(-3.9, 3.9) * 8000.0) as i16;
+        ShortVelocity {
+            x: short(value.x),
+            y: short(value.y),
+            z: short(value.z),
+        }
+    }
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SpawnEntityPacket {
+    pub entity_id: VarInt,
+    pub uuid: UUID,
+    /// Id in the `minecraft:entity_type` registry
+    pub kind: VarInt,
+    pub position: Vec3d,
+    #[mc(since = ProtocolVersion::V1_21_9)]
+    pub velocity: Option<LpVec3>,
+    pub pitch: Angle,
+    pub yaw: Angle,
+    pub head_yaw: Angle,
+    /// Meaning depends on the type, like the block state of a falling block
+    pub data: VarInt,
+    #[mc(before = ProtocolVersion::V1_21_9)]
+    pub short_velocity: Option<ShortVelocity>,
+}
+
+impl SpawnEntityPacket {
+    /// The velocity in whichever form the version sent it
+    pub fn velocity(&self) -> Vec3d {
+        match (self.velocity, self.short_velocity) {
+            (Some(velocity), _) => velocity.into(),
+            (None, Some(velocity)) => velocity.into(),
+            (None, None) => Vec3d::default(),
+        }
+    }
+}
+
+impl Packet for SpawnEntityPacket {
+    const ID: VarInt = VarInt(0x01);
+}
+
+/// Moves an entity by less than 8 blocks, in 1/4096 of a block
+#[derive(Debug, Clone, MinecraftData)]
+pub struct UpdateEntityPositionPacket {
+    pub entity_id: VarInt,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub on_ground: bool,
+}
+
+impl Packet for UpdateEntityPositionPacket {
+    const ID: VarInt = VarInt(0x33);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct UpdateEntityPositionAndRotationPacket {
+    pub entity_id: VarInt,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub yaw: Angle,
+    pub pitch: Angle,
+    pub on_ground: bool,
+}
+
+impl Packet for UpdateEntityPositionAndRotationPacket {
+    const ID: VarInt = VarInt(0x34);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct UpdateEntityRotationPacket {
+    pub entity_id: VarInt,
+    pub yaw: Angle,
+    pub pitch: Angle,
+    pub on_ground: bool,
+}
+
+impl Packet for UpdateEntityRotationPacket {
+    const ID: VarInt = VarInt(0x36);
+}
+
+/// Places an entity exactly, for moves too long for Update Entity Position and to correct
+/// drift
+#[derive(Debug, Clone, MinecraftData)]
+pub struct TeleportEntityPacket {
+    pub entity_id: VarInt,
+    pub position: Vec3d,
+    pub velocity: Vec3d,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl Packet for TeleportEntityPacket {
+    const ID: VarInt = VarInt(0x23);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SetEntityVelocityPacket {
+    pub entity_id: VarInt,
+    #[mc(since = ProtocolVersion::V1_21_9)]
+    pub velocity: Option<LpVec3>,
+    #[mc(before = ProtocolVersion::V1_21_9)]
+    pub short_velocity: Option<ShortVelocity>,
+}
+
+impl SetEntityVelocityPacket {
+    pub fn velocity(&self) -> Vec3d {
+        match (self.velocity, self.short_velocity) {
+            (Some(velocity), _) => velocity.into(),
+            (None, Some(velocity)) => velocity.into(),
+            (None, None) => Vec3d::default(),
+        }
+    }
+}
+
+impl Packet for SetEntityVelocityPacket {
+    const ID: VarInt = VarInt(0x63);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SetEntityMetadataPacket {
+    pub entity_id: VarInt,
+    pub metadata: EntityMetadata,
+}
+
+impl Packet for SetEntityMetadataPacket {
+    const ID: VarInt = VarInt(0x61);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct SetHeadRotationPacket {
+    pub entity_id: VarInt,
+    pub head_yaw: Angle,
+}
+
+impl Packet for SetHeadRotationPacket {
+    const ID: VarInt = VarInt(0x51);
+}
+
+#[derive(Debug, Clone, MinecraftData)]
+pub struct RemoveEntitiesPacket {
+    pub entity_ids: Vec<VarInt>,
+}
+
+impl Packet for RemoveEntitiesPacket {
+    const ID: VarInt = VarInt(0x4B);
+}
@@ -0,0 +1,370 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    f64::consts::SQRT_2,
+};
+
+use crate::{
+    block::BlockState,
+    datatypes::{Error, Position},
+    player::{Input, PlayerState},
+    world::World,
+};
+
+/// What the search charges for each kind of move, in about the time it takes to walk a block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathCosts {
+    /// Walking one block along an axis; diagonals cost `walk * √2`
+    pub walk: f64,
+    /// Added to `walk` for jumping up a block
+    pub jump: f64,
+    /// Added to `walk` for each block dropped when walking off an edge
+    pub fall: f64,
+    /// Highest drop the path may take, 3 being the most that does no fall damage
+    pub max_fall: u32,
+    /// Positions to explore before giving up on a goal
+    pub max_nodes: usize,
+}
+
+impl Default for PathCosts {
+    fn default() -> Self {
+        Self {
+            walk: 1.0,
+            jump: 2.0,
+            fall: 0.5,
+            max_fall: 3,
+            max_nodes: 20_000,
+        }
+    }
+}
+
+/// The error returned, inside an `anyhow::Error`, when there is no path to a goal over the
+/// loaded chunks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unreachable {
+    pub goal: Position,
+    /// Positions explored before giving up
+    pub explored: usize,
+}
+
+impl std::fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let goal = self.goal;
+        write!(
+            f,
+            "no path to {} {} {} after exploring {} positions",
+            goal.x(),
+            goal.y(),
+            goal.z(),
+            self.explored
+        )
+    }
+}
+
+impl std::error::Error for Unreachable {}
+
+fn offset(position: Position, dx: i32, dy: i32, dz: i32) -> Position {
+    Position::new(position.x() + dx, position.y() + dy, position.z() + dz)
+}
+
+fn is_passable(world: &World, position: Position) -> bool {
+    world
+        .block_at(position)
+        .is_some_and(|state| BlockState(state).is_passable())
+}
+
+/// The position a player stands at: the block their feet are in, or the one above it when the
+/// feet are sunk into a block lower than a full cube, like a path block or carpet
+fn standing_position(world: &World, player: &PlayerState) -> Position {
+    let feet = player.block_position();
+    match world.block_at(feet) {
+        Some(state) if !BlockState(state).is_passable() => offset(feet, 0, 1, 0),
+        _ => feet,
+    }
+}
+
+/// Whether a player fits at a position, standing or not
+fn is_open(world: &World, position: Position) -> bool {
+    is_passable(world, position) && is_passable(world, offset(position, 0, 1, 0))
+}
+
+/// The positions one move away, with what moving there costs
+fn neighbours(world: &World, from: Position, costs: &PathCosts) -> Vec<(Position, f64)> {
+    let mut moves = Vec::new();
+    for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+        let side = offset(from, dx, 0, dz);
+        if world.is_walkable(side) {
+            moves.push((side, costs.walk));
+            continue;
+        }
+
+        let up = offset(side, 0, 1, 0);
+        if is_passable(world, offset(from, 0, 2, 0)) && world.is_walkable(up) {
+            moves.push((up, costs.walk + costs.jump));
+            continue;
+        }
+
+        if !is_open(world, side) {
+            continue;
+        }
+        for drop in 1..=costs.max_fall as i32 {
+            let below = offset(side, 0, -drop, 0);
+            if world.is_walkable(below) {
+                moves.push((below, costs.walk + costs.fall * drop as f64));
+                break;
+            }
+            if !is_passable(world, below) {
+                break;
+            }
+        }
+    }
+
+    // diagonals only where both corners are clear, so that the player does not clip one
+    for (dx, dz) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+        let corner = offset(from, dx, 0, dz);
+        if world.is_walkable(corner)
+            && is_open(world, offset(from, dx, 0, 0))
+            && is_open(world, offset(from, 0, 0, dz))
+        {
+            moves.push((corner, costs.walk * SQRT_2));
+        }
+    }
+    moves
+}
+
+/// A lower bound on the cost between two positions: every move covers at most a block of
+/// horizontal distance, jumps climb at most one block and falls pay for every block dropped
+fn estimate(from: Position, to: Position, costs: &PathCosts) -> f64 {
+    let dx = (to.x() - from.x()).abs() as f64;
+    let dz = (to.z() - from.z()).abs() as f64;
+    let dy = (to.y() - from.y()) as f64;
+    let horizontal = dx.max(dz) + (SQRT_2 - 1.0) * dx.min(dz);
+    horizontal * costs.walk + dy.max(0.0) * costs.jump + (-dy).max(0.0) * costs.fall
+}
+
+/// An entry of the open set, ordered so that the binary heap pops the lowest estimate first
+#[derive(Debug, PartialEq)]
+struct Open {
+    estimate: f64,
+    cost: f64,
+    position: Position,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| self.cost.total_cmp(&other.cost))
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Plans a path with A* from the block the feet are in to a position to stand at, over loaded
+/// chunks only. The path leaves out the start and ends with the goal.
+pub fn find_path(
+    world: &World,
+    start: Position,
+    goal: Position,
+    costs: &PathCosts,
+) -> Result<Vec<Position>, Error> {
+    let mut open = BinaryHeap::new();
+    // the cheapest known cost to each position and where it was reached from
+    let mut best: HashMap<Position, (f64, Option<Position>)> = HashMap::new();
+    open.push(Open {
+        estimate: estimate(start, goal, costs),
+        cost: 0.0,
+        position: start,
+    });
+    best.insert(start, (0.0, None));
+
+    let mut explored = 0;
+    while let Some(Open { cost, position, .. }) = open.pop() {
+        if position == goal {
+            let mut path = vec![goal];
+            let mut at = goal;
+            while let Some(&(_, Some(previous))) = best.get(&at) {
+                if previous != start {
+                    path.push(previous);
+                }
+                at = previous;
+            }
+            path.reverse();
+            return Ok(path);
+        }
+        if cost > best[&position].0 {
+            // already reached more cheaply since this entry was pushed
+            continue;
+        }
+        explored += 1;
+        if explored >= costs.max_nodes {
+            break;
+        }
+
+        for (next, step) in neighbours(world, position, costs) {
+            let cost = cost + step;
+            if best.get(&next).is_some_and(|&(known, _)| known <= cost) {
+                continue;
+            }
+            best.insert(next, (cost, Some(position)));
+            open.push(Open {
+                estimate: cost + estimate(next, goal, costs),
+                cost,
+                position: next,
+            });
+        }
+    }
+    Err(Unreachable { goal, explored }.into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationStatus {
+    Moving,
+    Arrived,
+    /// No path was left after the world changed or the player got stuck
+    Unreachable,
+}
+
+/// Follows a path by steering the player each tick, planning again when the blocks along it
+/// change or the player stops making progress
+#[derive(Debug, Clone)]
+pub struct Navigator {
+    goal: Position,
+    costs: PathCosts,
+    path: VecDeque<Position>,
+    status: NavigationStatus,
+    /// The world revision the path was last checked against
+    revision: u64,
+    ticks_since_progress: u32,
+    /// Why the last replan found no path
+    last_error: Option<Unreachable>,
+}
+
+impl Navigator {
+    /// Ticks without reaching the next position before planning again
+    const STUCK_TICKS: u32 = 40;
+    /// How close to the middle of a position counts as being there
+    const REACHED: f64 = 0.35;
+
+    /// Plans a path for the player to the goal, failing with [`Unreachable`] if there is none
+    pub fn new(
+        world: &World,
+        player: &PlayerState,
+        goal: Position,
+        costs: PathCosts,
+    ) -> Result<Self, Error> {
+        let path = find_path(world, standing_position(world, player), goal, &costs)?;
+        Ok(Self {
+            goal,
+            costs,
+            path: path.into(),
+            status: NavigationStatus::Moving,
+            revision: world.revision(),
+            ticks_since_progress: 0,
+            last_error: None,
+        })
+    }
+
+    pub fn goal(&self) -> Position {
+        self.goal
+    }
+
+    pub fn status(&self) -> NavigationStatus {
+        self.status
+    }
+
+    /// Why navigation became [`Unreachable`](NavigationStatus::Unreachable), when it did
+    pub fn last_error(&self) -> Option<&Unreachable> {
+        self.last_error.as_ref()
+    }
+
+    /// The positions still to go through, ending with the goal
+    pub fn path(&self) -> impl Iterator<Item = &Position> {
+        self.path.iter()
+    }
+
+    fn replan(&mut self, world: &World, player: &PlayerState) {
+        self.ticks_since_progress = 0;
+        match find_path(
+            world,
+            standing_position(world, player),
+            self.goal,
+            &self.costs,
+        ) {
+            Ok(path) => {
+                self.path = path.into();
+                self.last_error = None;
+            }
+            Err(err) => {
+                self.last_error = err.downcast_ref::<Unreachable>().cloned();
+                self.path.clear();
+                self.status = NavigationStatus::Unreachable;
+            }
+        }
+    }
+
+    /// Turns the player toward the next position on the path and returns the keys to hold for
+    /// this tick, which are all released once navigation is over
+    pub fn steer(&mut self, player: &mut PlayerState, world: &World) -> Input {
+        if self.status != NavigationStatus::Moving {
+            return Input::default();
+        }
+        if world.revision() != self.revision {
+            self.revision = world.revision();
+            if !self
+                .path
+                .iter()
+                .all(|&position| world.is_walkable(position))
+            {
+                self.replan(world, player);
+            }
+        }
+        if self.ticks_since_progress >= Self::STUCK_TICKS {
+            self.replan(world, player);
+        }
+
+        let reached = |position: Position| {
+            let dx = position.x() as f64 + 0.5 - player.position.x;
+            let dz = position.z() as f64 + 0.5 - player.position.z;
+            dx.abs() < Self::REACHED
+                && dz.abs() < Self::REACHED
+                && (player.position.y - position.y() as f64).abs() < 0.5
+        };
+        while self.path.front().is_some_and(|&next| reached(next)) {
+            self.path.pop_front();
+            self.ticks_since_progress = 0;
+        }
+        let Some(&next) = self.path.front() else {
+            if self.status == NavigationStatus::Moving {
+                self.status = NavigationStatus::Arrived;
+            }
+            return Input::default();
+        };
+        self.ticks_since_progress += 1;
+
+        let dx = next.x() as f64 + 0.5 - player.position.x;
+        let dz = next.z() as f64 + 0.5 - player.position.z;
+        player.yaw = (-dx).atan2(dz).to_degrees() as f32;
+        let distance = (dx * dx + dz * dz).sqrt();
+        // ease off towards the end so as not to overshoot it
+        let forward = if self.path.len() == 1 {
+            (distance * 2.0).min(1.0)
+        } else {
+            1.0
+        };
+        Input {
+            forward: forward as f32,
+            jump: next.y() > standing_position(world, player).y()
+                && player.on_ground
+                && distance < 1.5,
+            ..Input::default()
+        }
+    }
+}
@@ -0,0 +1,539 @@
+use crate::{
+    block::{Aabb, BlockState},
+    datatypes::{Position, Vec3d},
+    packet::{
+        MovementFlags, PlayerInputPacket, SetPlayerMovementFlagsPacket,
+        SetPlayerPositionAndRotationPacket, SetPlayerPositionPacket, SetPlayerRotationPacket,
+        SynchronizePlayerPositionPacket, TeleportFlags,
+    },
+    world::World,
+};
+
+const WIDTH: f64 = 0.6;
+const HEIGHT: f64 = 1.8;
+/// Tallest ledge walking climbs without jumping
+const STEP_HEIGHT: f64 = 0.6;
+const GRAVITY: f64 = 0.08;
+/// The movement speed attribute of a player
+const WALK_SPEED: f64 = 0.1;
+const SPRINT_FACTOR: f64 = 1.3;
+const SNEAK_FACTOR: f64 = 0.3;
+/// Slipperiness of everything but ice and slime, which the block table does not tell apart
+const BLOCK_FRICTION: f64 = 0.6;
+const JUMP_POWER: f64 = 0.42;
+const SPRINT_JUMP_BOOST: f64 = 0.2;
+/// Ticks between jumps while jump is held
+const JUMP_DELAY: u32 = 10;
+/// Velocity components smaller than this are dropped every tick
+const MIN_VELOCITY: f64 = 0.003;
+const COLLISION_EPSILON: f64 = 1e-7;
+
+/// The movement keys, as set with `Connection::set_input`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Input {
+    /// -1 to 1, positive walking forward
+    pub forward: f32,
+    /// -1 to 1, positive walking left
+    pub strafe: f32,
+    pub jump: bool,
+    pub sneak: bool,
+    pub sprint: bool,
+}
+
+impl Input {
+    /// The keys to report in Player Input
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+        for (held, flag) in [
+            (self.forward > 0.0, PlayerInputPacket::FORWARD),
+            (self.forward < 0.0, PlayerInputPacket::BACKWARD),
+            (self.strafe > 0.0, PlayerInputPacket::LEFT),
+            (self.strafe < 0.0, PlayerInputPacket::RIGHT),
+            (self.jump, PlayerInputPacket::JUMP),
+            (self.sneak, PlayerInputPacket::SNEAK),
+            (self.sprint, PlayerInputPacket::SPRINT),
+        ] {
+            if held {
+                flags |= flag;
+            }
+        }
+        flags
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fluid {
+    Water,
+    Lava,
+}
+
+/// Where our own player is, as last set by the server or moved by us
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlayerState {
+    /// Feet position
+    pub position: Vec3d,
+    /// Blocks per tick
+    pub velocity: Vec3d,
+    /// Degrees, 0 facing south and increasing clockwise
+    pub yaw: f32,
+    /// Degrees, -90 looking straight up
+    pub pitch: f32,
+    pub on_ground: bool,
+    /// Whether the last move was stopped by a wall
+    pub horizontal_collision: bool,
+    pub sprinting: bool,
+    jump_delay: u32,
+}
+
+impl PlayerState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves to where Synchronize Player Position says, keeping the current value for the
+    /// fields it flags as relative
+    pub fn synchronize(&mut self, packet: &SynchronizePlayerPositionPacket) {
+        let flags = packet.flags;
+        let relative = |flag: i32, current: f64, new: f64| {
+            if flags.has(flag) {
+                current + new
+            } else {
+                new
+            }
+        };
+
+        self.position = Vec3d {
+            x: relative(TeleportFlags::X, self.position.x, packet.position.x),
+            y: relative(TeleportFlags::Y, self.position.y, packet.position.y),
+            z: relative(TeleportFlags::Z, self.position.z, packet.position.z),
+        };
+
+        let yaw = relative(TeleportFlags::YAW, self.yaw as f64, packet.yaw as f64) as f32;
+        let pitch = relative(TeleportFlags::PITCH, self.pitch as f64, packet.pitch as f64)
+            .clamp(-90.0, 90.0) as f32;
+
+        let mut velocity = self.velocity;
+        if flags.has(TeleportFlags::ROTATE_VELOCITY) {
+            velocity = rotate(velocity, self.pitch - pitch, self.yaw - yaw);
+        }
+        self.velocity = Vec3d {
+            x: relative(TeleportFlags::VELOCITY_X, velocity.x, packet.velocity.x),
+            y: relative(TeleportFlags::VELOCITY_Y, velocity.y, packet.velocity.y),
+            z: relative(TeleportFlags::VELOCITY_Z, velocity.z, packet.velocity.z),
+        };
+
+        self.yaw = yaw;
+        self.pitch = pitch;
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        let Vec3d { x, y, z } = self.position;
+        Aabb::new(
+            Vec3d {
+                x: x - WIDTH / 2.0,
+                y,
+                z: z - WIDTH / 2.0,
+            },
+            Vec3d {
+                x: x + WIDTH / 2.0,
+                y: y + HEIGHT,
+                z: z + WIDTH / 2.0,
+            },
+        )
+    }
+
+    /// The block the feet are in
+    pub fn block_position(&self) -> Position {
+        Position::new(
+            self.position.x.floor() as i32,
+            self.position.y.floor() as i32,
+            self.position.z.floor() as i32,
+        )
+    }
+
+    /// Runs one tick of vanilla player physics: walking, jumping and swimming as the input says,
+    /// then gravity and drag
+    pub fn tick(&mut self, input: &Input, world: &World) {
+        let fluid = fluid_in(world, &self.bounding_box());
+        let mut forward = input.forward.clamp(-1.0, 1.0) as f64 * 0.98;
+        let mut strafe = input.strafe.clamp(-1.0, 1.0) as f64 * 0.98;
+        if input.sneak {
+            forward *= SNEAK_FACTOR;
+            strafe *= SNEAK_FACTOR;
+        }
+        self.sprinting = input.sprint && input.forward > 0.0 && !input.sneak;
+
+        self.jump_delay = self.jump_delay.saturating_sub(1);
+        for v in [
+            &mut self.velocity.x,
+            &mut self.velocity.y,
+            &mut self.velocity.z,
+        ] {
+            if v.abs() < MIN_VELOCITY {
+                *v = 0.0;
+            }
+        }
+        if input.jump {
+            if fluid.is_some() {
+                self.velocity.y += 0.04;
+            } else if self.on_ground && self.jump_delay == 0 {
+                self.jump();
+                self.jump_delay = JUMP_DELAY;
+            }
+        } else {
+            self.jump_delay = 0;
+        }
+
+        match fluid {
+            Some(fluid) => {
+                let start_y = self.position.y;
+                let (drag, gravity) = match fluid {
+                    Fluid::Water if self.sprinting => (0.9, GRAVITY / 16.0),
+                    Fluid::Water => (0.8, GRAVITY / 16.0),
+                    Fluid::Lava => (0.5, GRAVITY / 4.0),
+                };
+                self.accelerate(forward, strafe, 0.02);
+                self.move_by(world);
+                self.velocity.x *= drag;
+                self.velocity.y *= if fluid == Fluid::Water { 0.8 } else { 0.5 };
+                self.velocity.z *= drag;
+                self.velocity.y -= gravity;
+                // swimming into a ledge climbs out onto it
+                let climbed = self.bounding_box().offset(
+                    self.velocity.x,
+                    self.velocity.y + 0.6 - self.position.y + start_y,
+                    self.velocity.z,
+                );
+                if self.horizontal_collision
+                    && world.collisions(&climbed).is_empty()
+                    && fluid_in(world, &climbed).is_none()
+                {
+                    self.velocity.y = 0.3;
+                }
+            }
+            None => {
+                let slipperiness = if self.on_ground { BLOCK_FRICTION } else { 1.0 };
+                let speed = if self.on_ground {
+                    let walk = WALK_SPEED * if self.sprinting { SPRINT_FACTOR } else { 1.0 };
+                    walk * (0.21600002 / slipperiness.powi(3))
+                } else if self.sprinting {
+                    0.026
+                } else {
+                    0.02
+                };
+                self.accelerate(forward, strafe, speed);
+                self.move_by(world);
+                let friction = slipperiness * 0.91;
+                self.velocity.x *= friction;
+                self.velocity.y = (self.velocity.y - GRAVITY) * 0.98;
+                self.velocity.z *= friction;
+            }
+        }
+    }
+
+    fn jump(&mut self) {
+        self.velocity.y = self.velocity.y.max(JUMP_POWER);
+        if self.sprinting {
+            let (sin, cos) = (self.yaw as f64).to_radians().sin_cos();
+            self.velocity.x -= sin * SPRINT_JUMP_BOOST;
+            self.velocity.z += cos * SPRINT_JUMP_BOOST;
+        }
+    }
+
+    /// Adds walking input to the velocity, turned to where we face
+    fn accelerate(&mut self, forward: f64, strafe: f64, speed: f64) {
+        let length = (forward * forward + strafe * strafe).sqrt();
+        if length < 1e-7 {
+            return;
+        }
+        let scale = speed / length.max(1.0);
+        let (x, z) = (strafe * scale, forward * scale);
+        let (sin, cos) = (self.yaw as f64).to_radians().sin_cos();
+        self.velocity.x += x * cos - z * sin;
+        self.velocity.z += z * cos + x * sin;
+    }
+
+    /// Moves by the velocity as far as blocks allow, stepping up low ledges, and stops the
+    /// velocity along any axis that hit something
+    fn move_by(&mut self, world: &World) {
+        let delta = self.velocity;
+        let start = self.bounding_box();
+        let mut moved = collide(world, &start, delta);
+
+        // like vanilla, movement cut short by less than this does not count as a collision
+        let blocked = |moved: Vec3d| {
+            let differs = |a: f64, b: f64| (a - b).abs() > 1e-5;
+            (
+                differs(moved.x, delta.x),
+                differs(moved.y, delta.y),
+                differs(moved.z, delta.z),
+            )
+        };
+        let (blocked_x, blocked_y, blocked_z) = blocked(moved);
+        let landing = blocked_y && delta.y < 0.0;
+        if (self.on_ground || landing) && (blocked_x || blocked_z) {
+            let up = collide(world, &start, vec(0.0, STEP_HEIGHT, 0.0)).y;
+            let raised = start.offset(0.0, up, 0.0);
+            let across = collide(world, &raised, vec(delta.x, 0.0, delta.z));
+            let down = collide(
+                world,
+                &raised.offset(across.x, 0.0, across.z),
+                vec(0.0, delta.y - up, 0.0),
+            );
+            let stepped = vec(across.x, up + down.y, across.z);
+            if horizontal_length_squared(stepped) > horizontal_length_squared(moved) {
+                moved = stepped;
+            }
+        }
+
+        self.position.x += moved.x;
+        self.position.y += moved.y;
+        self.position.z += moved.z;
+        let (blocked_x, blocked_y, blocked_z) = blocked(moved);
+        self.horizontal_collision = blocked_x || blocked_z;
+        self.on_ground = blocked_y && delta.y < 0.0;
+        if blocked_x {
+            self.velocity.x = 0.0;
+        }
+        if blocked_y {
+            self.velocity.y = 0.0;
+        }
+        if blocked_z {
+            self.velocity.z = 0.0;
+        }
+    }
+}
+
+/// Picks the movement packets to send after each tick the way vanilla does: only what changed,
+/// with the position repeated at least once a second
+#[derive(Debug, Clone, Default)]
+pub struct MovementReporter {
+    last: Option<PlayerState>,
+    ticks_since_position: u32,
+    input: u8,
+    sprinting: bool,
+}
+
+/// One of the movement packets
+#[derive(Debug, Clone)]
+pub enum MovementPacket {
+    Position(SetPlayerPositionPacket),
+    PositionAndRotation(SetPlayerPositionAndRotationPacket),
+    Rotation(SetPlayerRotationPacket),
+    Flags(SetPlayerMovementFlagsPacket),
+}
+
+impl MovementReporter {
+    const POSITION_REMINDER_TICKS: u32 = 20;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a position and rotation sent outside of [`report`](Self::report), like the
+    /// answer to a teleport
+    pub fn sent(&mut self, state: &PlayerState, flags: MovementFlags) {
+        self.last = Some(PlayerState {
+            on_ground: flags.on_ground(),
+            horizontal_collision: flags.0 & MovementFlags::PUSHING_AGAINST_WALL != 0,
+            ..*state
+        });
+        self.ticks_since_position = 0;
+    }
+
+    /// The movement packet for the state after a tick, if anything changed
+    pub fn report(&mut self, state: &PlayerState) -> Option<MovementPacket> {
+        self.ticks_since_position += 1;
+        let flags = MovementFlags::new(state.on_ground, state.horizontal_collision);
+        let (moved, rotated, flags_changed) = match &self.last {
+            None => (true, true, true),
+            Some(last) => {
+                let d = vec(
+                    state.position.x - last.position.x,
+                    state.position.y - last.position.y,
+                    state.position.z - last.position.z,
+                );
+                (
+                    d.x * d.x + d.y * d.y + d.z * d.z > 2.0e-4 * 2.0e-4
+                        || self.ticks_since_position >= Self::POSITION_REMINDER_TICKS,
+                    last.yaw != state.yaw || last.pitch != state.pitch,
+                    last.on_ground != state.on_ground
+                        || last.horizontal_collision != state.horizontal_collision,
+                )
+            }
+        };
+
+        let last = self.last.get_or_insert(*state);
+        last.on_ground = state.on_ground;
+        last.horizontal_collision = state.horizontal_collision;
+        if moved {
+            last.position = state.position;
+            self.ticks_since_position = 0;
+        }
+        if rotated {
+            last.yaw = state.yaw;
+            last.pitch = state.pitch;
+        }
+
+        let position = state.position;
+        let (yaw, pitch) = (state.yaw, state.pitch);
+        match (moved, rotated) {
+            (true, true) => Some(MovementPacket::PositionAndRotation(
+                SetPlayerPositionAndRotationPacket {
+                    position,
+                    yaw,
+                    pitch,
+                    flags,
+                },
+            )),
+            (true, false) => Some(MovementPacket::Position(SetPlayerPositionPacket {
+                position,
+                flags,
+            })),
+            (false, true) => Some(MovementPacket::Rotation(SetPlayerRotationPacket {
+                yaw,
+                pitch,
+                flags,
+            })),
+            (false, false) if flags_changed => {
+                Some(MovementPacket::Flags(SetPlayerMovementFlagsPacket {
+                    flags,
+                }))
+            }
+            (false, false) => None,
+        }
+    }
+
+    /// Whether the keys held changed since last asked, meaning Player Input should be sent
+    pub fn input_changed(&mut self, input: &Input) -> bool {
+        let flags = input.flags();
+        std::mem::replace(&mut self.input, flags) != flags
+    }
+
+    /// Whether sprinting started or stopped since last asked, meaning Player Command should be
+    /// sent
+    pub fn sprint_changed(&mut self, sprinting: bool) -> bool {
+        std::mem::replace(&mut self.sprinting, sprinting) != sprinting
+    }
+}
+
+fn vec(x: f64, y: f64, z: f64) -> Vec3d {
+    Vec3d { x, y, z }
+}
+
+fn horizontal_length_squared(v: Vec3d) -> f64 {
+    v.x * v.x + v.z * v.z
+}
+
+/// Water if the box touches any, otherwise lava if it touches any
+fn fluid_in(world: &World, area: &Aabb) -> Option<Fluid> {
+    let area = Aabb::new(
+        vec(area.min.x + 0.001, area.min.y + 0.001, area.min.z + 0.001),
+        vec(area.max.x - 0.001, area.max.y - 0.001, area.max.z - 0.001),
+    );
+    let mut found = None;
+    for x in area.min.x.floor() as i32..=area.max.x.floor() as i32 {
+        for y in area.min.y.floor() as i32..=area.max.y.floor() as i32 {
+            for z in area.min.z.floor() as i32..=area.max.z.floor() as i32 {
+                let Some(state) = world.block_at(Position::new(x, y, z)).map(BlockState) else {
+                    continue;
+                };
+                if !state.is_liquid() {
+                    continue;
+                }
+                if state.name() == Some("minecraft:lava") {
+                    found = Some(Fluid::Lava);
+                } else {
+                    return Some(Fluid::Water);
+                }
+            }
+        }
+    }
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn of(self, v: Vec3d) -> f64 {
+        match self {
+            Axis::X => v.x,
+            Axis::Y => v.y,
+            Axis::Z => v.z,
+        }
+    }
+}
+
+/// How far a box can move by `delta` before hitting blocks: along y first, then along the
+/// larger of x and z, like vanilla
+fn collide(world: &World, start: &Aabb, delta: Vec3d) -> Vec3d {
+    let obstacles = world.collisions(&start.expand_towards(delta));
+    let mut moving = *start;
+    let mut moved = Vec3d::default();
+    let order = if delta.x.abs() < delta.z.abs() {
+        [Axis::Y, Axis::Z, Axis::X]
+    } else {
+        [Axis::Y, Axis::X, Axis::Z]
+    };
+    for axis in order {
+        let d = clip(axis, &obstacles, &moving, axis.of(delta));
+        match axis {
+            Axis::X => moved.x = d,
+            Axis::Y => moved.y = d,
+            Axis::Z => moved.z = d,
+        }
+        moving = moving.offset(
+            if axis == Axis::X { d } else { 0.0 },
+            if axis == Axis::Y { d } else { 0.0 },
+            if axis == Axis::Z { d } else { 0.0 },
+        );
+    }
+    moved
+}
+
+/// Shortens a movement along one axis so the box stops at the first obstacle in the way
+fn clip(axis: Axis, obstacles: &[Aabb], moving: &Aabb, mut d: f64) -> f64 {
+    if d == 0.0 {
+        return 0.0;
+    }
+    for obstacle in obstacles {
+        let in_the_way = [Axis::X, Axis::Y, Axis::Z]
+            .into_iter()
+            .filter(|&other| other != axis)
+            .all(|other| {
+                other.of(obstacle.min) < other.of(moving.max) - COLLISION_EPSILON
+                    && other.of(obstacle.max) > other.of(moving.min) + COLLISION_EPSILON
+            });
+        if !in_the_way {
+            continue;
+        }
+        if d > 0.0 && axis.of(obstacle.min) >= axis.of(moving.max) - COLLISION_EPSILON {
+            d = d.min(axis.of(obstacle.min) - axis.of(moving.max));
+        } else if d < 0.0 && axis.of(obstacle.max) <= axis.of(moving.min) + COLLISION_EPSILON {
+            d = d.max(axis.of(obstacle.max) - axis.of(moving.min));
+        }
+    }
+    d
+}
+
+/// Turns a vector about the x axis and then the y axis, in degrees, the way vanilla rotates
+/// velocity on teleport
+fn rotate(v: Vec3d, pitch: f32, yaw: f32) -> Vec3d {
+    let (sin, cos) = (pitch as f64).to_radians().sin_cos();
+    let v = Vec3d {
+        x: v.x,
+        y: v.y * cos + v.z * sin,
+        z: v.z * cos - v.y * sin,
+    };
+    let (sin, cos) = (yaw as f64).to_radians().sin_cos();
+    Vec3d {
+        x: v.x * cos + v.z * sin,
+        y: v.y,
+        z: v.z * cos - v.x * sin,
+    }
+}
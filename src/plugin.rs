@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::anyhow;
+
+use crate::datatypes::{Error, Identifier, MinecraftData};
+
+/// Answers Login Plugin Requests on one channel.
+///
+/// Returning `None` tells the server the channel is not understood, which is also what happens
+/// for channels without a handler.
+pub trait LoginPluginHandler: Send {
+    fn handle(&mut self, data: &[u8]) -> Option<Vec<u8>>;
+}
+
+impl<F: FnMut(&[u8]) -> Option<Vec<u8>> + Send> LoginPluginHandler for F {
+    fn handle(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        self(data)
+    }
+}
+
+/// Login plugin handlers by channel, such as `velocity:player_info` for Velocity's modern
+/// forwarding.
+#[derive(Default)]
+pub struct LoginPluginHandlers {
+    handlers: HashMap<Identifier, Box<dyn LoginPluginHandler>>,
+}
+
+impl LoginPluginHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces any handler already registered for the channel
+    pub fn register(&mut self, channel: Identifier, handler: impl LoginPluginHandler + 'static) {
+        self.handlers.insert(channel, Box::new(handler));
+    }
+
+    pub fn unregister(&mut self, channel: &Identifier) -> bool {
+        self.handlers.remove(channel).is_some()
+    }
+
+    pub fn is_registered(&self, channel: &Identifier) -> bool {
+        self.handlers.contains_key(channel)
+    }
+
+    /// The response payload for a request, or `None` if it is not understood
+    pub fn handle(&mut self, channel: &Identifier, data: &[u8]) -> Option<Vec<u8>> {
+        self.handlers.get_mut(channel)?.handle(data)
+    }
+}
+
+impl std::fmt::Debug for LoginPluginHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+/// A typed payload for one plugin channel
+pub trait ChannelMessage: MinecraftData {
+    const CHANNEL: &'static str;
+
+    fn channel() -> Identifier {
+        Identifier::try_from(Self::CHANNEL).unwrap()
+    }
+
+    /// Decodes the whole payload, rejecting it if any bytes are left over
+    fn decode_payload(mut data: &[u8]) -> Result<Self, Error> {
+        let message = Self::decode(&mut data)?;
+        if !data.is_empty() {
+            return Err(anyhow!(
+                "{} unread bytes at the end of a {} message",
+                data.len(),
+                Self::CHANNEL
+            ));
+        }
+        Ok(message)
+    }
+
+    fn encode_payload(self) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::with_capacity(self.num_bytes());
+        self.encode(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Handles plugin messages on one channel in configuration and play. A returned payload is sent
+/// back to the server on the same channel.
+pub trait PluginChannelHandler: Send {
+    fn handle(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+}
+
+impl<F: FnMut(&[u8]) -> Result<Option<Vec<u8>>, Error> + Send> PluginChannelHandler for F {
+    fn handle(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self(data)
+    }
+}
+
+/// Our plugin channel handlers, and the channels the server has said it listens on.
+///
+/// The channels with a handler are what we announce with `minecraft:register`.
+#[derive(Default)]
+pub struct PluginChannels {
+    handlers: HashMap<Identifier, Box<dyn PluginChannelHandler>>,
+    server_channels: HashSet<Identifier>,
+}
+
+impl PluginChannels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces any handler already registered for the channel
+    pub fn register(&mut self, channel: Identifier, handler: impl PluginChannelHandler + 'static) {
+        self.handlers.insert(channel, Box::new(handler));
+    }
+
+    /// Registers a handler that gets messages already decoded as `M`
+    pub fn register_typed<M, F>(&mut self, mut handler: F)
+    where
+        M: ChannelMessage + 'static,
+        F: FnMut(M) -> Option<M> + Send + 'static,
+    {
+        self.register(M::channel(), move |data: &[u8]| {
+            handler(M::decode_payload(data)?)
+                .map(M::encode_payload)
+                .transpose()
+        });
+    }
+
+    pub fn unregister(&mut self, channel: &Identifier) -> bool {
+        self.handlers.remove(channel).is_some()
+    }
+
+    /// The channels we have handlers for
+    pub fn channels(&self) -> impl Iterator<Item = &Identifier> {
+        self.handlers.keys()
+    }
+
+    /// Handles a message, returning the reply if there is one. Messages on channels without a
+    /// handler are ignored.
+    pub fn handle(&mut self, channel: &Identifier, data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        match self.handlers.get_mut(channel) {
+            Some(handler) => handler.handle(data),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether the server has registered the channel, so it expects messages on it
+    pub fn server_listens_on(&self, channel: &Identifier) -> bool {
+        self.server_channels.contains(channel)
+    }
+
+    pub fn server_channels(&self) -> impl Iterator<Item = &Identifier> {
+        self.server_channels.iter()
+    }
+
+    pub fn server_registered(&mut self, channels: impl IntoIterator<Item = Identifier>) {
+        self.server_channels.extend(channels);
+    }
+
+    pub fn server_unregistered(&mut self, channels: impl IntoIterator<Item = Identifier>) {
+        for channel in channels {
+            self.server_channels.remove(&channel);
+        }
+    }
+}
+
+impl std::fmt::Debug for PluginChannels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginChannels")
+            .field("handlers", &self.handlers.keys().collect::<Vec<_>>())
+            .field("server_channels", &self.server_channels)
+            .finish()
+    }
+}
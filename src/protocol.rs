@@ -0,0 +1,341 @@
+use std::{cell::Cell, io::Write};
+
+use crate::{
+    connection::ConnectionState,
+    datatypes::{Error, MinecraftData, MinecraftRead, VarInt},
+};
+
+/// A protocol version we can speak. Versions that share a protocol number share a variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+    V1_21_5,
+    V1_21_6,
+    /// 1.21.7 and 1.21.8
+    V1_21_7,
+    /// 1.21.9 and 1.21.10
+    V1_21_9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Clientbound,
+    Serverbound,
+}
+
+use ConnectionState::{Configuration, Play};
+use Direction::{Clientbound, Serverbound};
+use ProtocolVersion::*;
+
+/// Packets added in each version, by their id in the latest version.
+///
+/// Vanilla assigns ids in order, so adding a packet bumps the id of every packet after it by one.
+/// Knowing what was added when is enough to map ids between versions, as long as no packet in
+/// the supported range was removed or reordered.
+const ADDED: &[(ProtocolVersion, ConnectionState, Direction, &[i32])] = &[
+    // dialogs
+    (V1_21_6, Configuration, Clientbound, &[0x11, 0x12]),
+    (V1_21_6, Configuration, Serverbound, &[0x08]),
+    // waypoints and dialogs
+    (V1_21_6, Play, Clientbound, &[0x88, 0x89, 0x8A]),
+    // change game mode and custom click action
+    (V1_21_6, Play, Serverbound, &[0x04, 0x41]),
+    // code of conduct
+    (V1_21_9, Configuration, Clientbound, &[0x13]),
+    (V1_21_9, Configuration, Serverbound, &[0x09]),
+    // debug values and game test highlights
+    (V1_21_9, Play, Clientbound, &[0x1A, 0x1B, 0x1C, 0x1D, 0x27]),
+];
+
+thread_local! {
+    static CURRENT: Cell<ProtocolVersion> = const { Cell::new(ProtocolVersion::LATEST) };
+}
+
+impl ProtocolVersion {
+    pub const LATEST: Self = V1_21_9;
+    pub const ALL: [Self; 4] = [V1_21_5, V1_21_6, V1_21_7, V1_21_9];
+
+    pub fn protocol(self) -> i32 {
+        match self {
+            V1_21_5 => 770,
+            V1_21_6 => 771,
+            V1_21_7 => 772,
+            V1_21_9 => 773,
+        }
+    }
+
+    pub fn from_protocol(protocol: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.protocol() == protocol)
+    }
+
+    /// The supported version nearest to `protocol`: the newest one not newer than it, or the
+    /// oldest one if it is older than all of them
+    pub fn closest(protocol: i32) -> Self {
+        Self::ALL
+            .into_iter()
+            .rev()
+            .find(|v| v.protocol() <= protocol)
+            .unwrap_or(Self::ALL[0])
+    }
+
+    /// The version a server reports in its status response, if we support it
+    pub fn from_status(json: &str) -> Option<Self> {
+        Self::from_protocol(status_protocol(json)?)
+    }
+
+    /// The releases that use this protocol
+    pub fn names(self) -> &'static [&'static str] {
+        match self {
+            V1_21_5 => &["1.21.5"],
+            V1_21_6 => &["1.21.6"],
+            V1_21_7 => &["1.21.7", "1.21.8"],
+            V1_21_9 => &["1.21.9", "1.21.10"],
+        }
+    }
+
+    /// Packets added after this version, sorted by their latest id
+    fn added_after(self, state: ConnectionState, direction: Direction) -> Vec<i32> {
+        let mut ids: Vec<i32> = ADDED
+            .iter()
+            .filter(|(version, s, d, _)| *version > self && *s == state && *d == direction)
+            .flat_map(|(_, _, _, ids)| ids.iter().copied())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// The id this version uses for a packet, given its id in the latest version, or `None` if
+    /// this version does not have the packet
+    pub fn packet_id(
+        self,
+        state: ConnectionState,
+        direction: Direction,
+        latest: VarInt,
+    ) -> Option<VarInt> {
+        let added = self.added_after(state, direction);
+        if added.contains(&latest.0) {
+            return None;
+        }
+        let shift = added.iter().filter(|&&id| id < latest.0).count() as i32;
+        Some(VarInt(latest.0 - shift))
+    }
+
+    /// The inverse of [`packet_id`](Self::packet_id): the latest id of a packet this version
+    /// sent with `id`
+    pub fn latest_packet_id(
+        self,
+        state: ConnectionState,
+        direction: Direction,
+        id: VarInt,
+    ) -> VarInt {
+        let mut latest = id.0;
+        for added in self.added_after(state, direction) {
+            if added <= latest {
+                latest += 1;
+            } else {
+                break;
+            }
+        }
+        VarInt(latest)
+    }
+
+    /// Decodes a value as this version sends it, deciding which version-gated fields are
+    /// present. Decoding with [`MinecraftData::decode`] directly reads them as in
+    /// [`LATEST`](Self::LATEST).
+    pub fn decode<T: MinecraftData, R: MinecraftRead>(self, reader: &mut R) -> Result<T, Error> {
+        self.with_current(|| T::decode(reader))
+    }
+
+    /// Encodes a value as this version expects it, leaving out fields it does not have
+    pub fn encode<T: MinecraftData, W: Write>(self, value: T, writer: &mut W) -> Result<(), Error> {
+        self.with_current(|| value.encode(writer))
+    }
+
+    /// How many bytes [`encode`](Self::encode) writes for `value`
+    pub fn num_bytes<T: MinecraftData>(self, value: &T) -> usize {
+        self.with_current(|| value.num_bytes())
+    }
+
+    /// The version being decoded or encoded for by the entry points that take one, which
+    /// derived version-gated fields read. [`LATEST`](Self::LATEST) outside of them.
+    #[doc(hidden)]
+    pub fn current() -> Self {
+        CURRENT.with(Cell::get)
+    }
+
+    /// Runs `f` with this as the [`current`](Self::current) version
+    pub(crate) fn with_current<T>(self, f: impl FnOnce() -> T) -> T {
+        struct Restore(ProtocolVersion);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.set(self.0));
+            }
+        }
+        let _restore = Restore(CURRENT.with(|current| current.replace(self)));
+        f()
+    }
+}
+
+/// Reads the protocol number out of a status response, `{"version": {"protocol": 773, ...}, ...}`
+///
+/// Only the top-level `version` object counts, so a MOTD or player name mentioning `"version"`
+/// cannot be mistaken for it.
+pub fn status_protocol(json: &str) -> Option<i32> {
+    let mut json = Json {
+        rest: json,
+        depth: 0,
+    };
+    let mut protocol = None;
+    json.object(|json, key| {
+        if key != "version" {
+            return json.skip_value();
+        }
+        json.object(|json, key| {
+            if key != "protocol" {
+                return json.skip_value();
+            }
+            protocol = json.number()?.parse().ok();
+            Some(())
+        })
+    })?;
+    if !json.rest.trim_start().is_empty() {
+        return None;
+    }
+    protocol
+}
+
+/// Just enough of a JSON reader to walk a status response: values we are not after are checked
+/// for well-formedness and skipped.
+struct Json<'a> {
+    rest: &'a str,
+    /// Objects and arrays currently open, bounded so hostile nesting cannot exhaust the stack
+    depth: usize,
+}
+
+/// Deeper than any status response needs; `MString<32767>` could otherwise nest 32k levels
+const MAX_JSON_DEPTH: usize = 64;
+
+impl Json<'_> {
+    fn eat(&mut self, token: char) -> Option<()> {
+        self.rest = self.rest.trim_start().strip_prefix(token)?;
+        Some(())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.trim_start().chars().next()
+    }
+
+    /// Runs `inner` one nesting level deeper, failing past [`MAX_JSON_DEPTH`]
+    fn nested(&mut self, inner: impl FnOnce(&mut Self) -> Option<()>) -> Option<()> {
+        if self.depth == MAX_JSON_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let result = inner(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Calls `field` with every key of an object, which must consume the value
+    fn object(&mut self, field: impl FnMut(&mut Self, &str) -> Option<()>) -> Option<()> {
+        self.nested(|json| json.object_fields(field))
+    }
+
+    fn object_fields(
+        &mut self,
+        mut field: impl FnMut(&mut Self, &str) -> Option<()>,
+    ) -> Option<()> {
+        self.eat('{')?;
+        if self.peek() == Some('}') {
+            return self.eat('}');
+        }
+        loop {
+            let key = self.string()?;
+            self.eat(':')?;
+            field(self, &key)?;
+            match self.peek()? {
+                ',' => self.eat(',')?,
+                _ => return self.eat('}'),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Option<()> {
+        self.nested(Self::array_items)
+    }
+
+    fn array_items(&mut self) -> Option<()> {
+        self.eat('[')?;
+        if self.peek() == Some(']') {
+            return self.eat(']');
+        }
+        loop {
+            self.skip_value()?;
+            match self.peek()? {
+                ',' => self.eat(',')?,
+                _ => return self.eat(']'),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.eat('"')?;
+        let mut out = String::new();
+        let mut chars = self.rest.char_indices();
+        loop {
+            match chars.next()? {
+                (i, '"') => {
+                    self.rest = &self.rest[i + 1..];
+                    return Some(out);
+                }
+                (_, '\\') => match chars.next()?.1 {
+                    'n' => out.push('\n'),
+                    't' => out.push('\t'),
+                    'r' => out.push('\r'),
+                    'b' => out.push('\u{8}'),
+                    'f' => out.push('\u{c}'),
+                    'u' => {
+                        let hex: String = (0..4)
+                            .map(|_| chars.next().map(|(_, c)| c))
+                            .collect::<Option<_>>()?;
+                        // surrogate pairs only matter for display, not for finding keys
+                        out.push(
+                            char::from_u32(u32::from_str_radix(&hex, 16).ok()?)
+                                .unwrap_or('\u{fffd}'),
+                        );
+                    }
+                    c @ ('"' | '\\' | '/') => out.push(c),
+                    _ => return None,
+                },
+                (_, c) => out.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<&str> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(self.rest.len());
+        let (number, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        (!number.is_empty()).then_some(number)
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        match self.peek()? {
+            '{' => self.object(|json, _| json.skip_value()),
+            '[' => self.array(),
+            '"' => self.string().map(|_| ()),
+            't' | 'f' | 'n' => {
+                self.rest = self.rest.trim_start();
+                let literal = ["true", "false", "null"]
+                    .into_iter()
+                    .find(|literal| self.rest.starts_with(literal))?;
+                self.rest = &self.rest[literal.len()..];
+                Some(())
+            }
+            _ => self.number().map(|_| ()),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Error, Result};
+
+use crate::{
+    connection::{Connection, ConnectionState, Disconnected},
+    protocol::ProtocolVersion,
+};
+
+/// Why the server will keep refusing us, so reconnecting is pointless
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermanentFailureKind {
+    Banned,
+    NotWhitelisted,
+    OutdatedClient,
+    OutdatedServer,
+    /// The server does not speak our protocol version at all
+    Incompatible,
+}
+
+impl PermanentFailureKind {
+    /// Recognises the vanilla translation keys and their English text, which is what plugins
+    /// usually send instead
+    pub fn from_reason(reason: &str) -> Option<Self> {
+        let reason = reason.to_lowercase();
+        let matches = |patterns: &[&str]| patterns.iter().any(|p| reason.contains(p));
+        if is_temporary_ban(&reason) {
+            None
+        } else if matches(&["multiplayer.disconnect.banned", "you are banned"]) {
+            Some(Self::Banned)
+        } else if matches(&[
+            "multiplayer.disconnect.not_whitelisted",
+            "not whitelisted",
+            "not white-listed",
+        ]) {
+            Some(Self::NotWhitelisted)
+        } else if matches(&["multiplayer.disconnect.outdated_client", "outdated client"]) {
+            Some(Self::OutdatedClient)
+        } else if matches(&["multiplayer.disconnect.outdated_server", "outdated server"]) {
+            Some(Self::OutdatedServer)
+        } else if matches(&["multiplayer.disconnect.incompatible", "incompatible client"]) {
+            Some(Self::Incompatible)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether the reason is a ban that runs out, which vanilla words as the ban reason followed by
+/// when the ban will be removed. Worth retrying, but only after a long wait.
+pub fn is_temporary_ban(reason: &str) -> bool {
+    let reason = reason.to_lowercase();
+    [
+        "multiplayer.disconnect.banned.expiration",
+        "your ban will be removed",
+    ]
+    .iter()
+    .any(|p| reason.contains(p))
+}
+
+/// Returned by [`ReconnectingClient::run`] when the server refuses us for good
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermanentFailure {
+    pub kind: PermanentFailureKind,
+    pub reason: String,
+}
+
+impl std::fmt::Display for PermanentFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "giving up ({:?}): {}", self.kind, self.reason)
+    }
+}
+
+impl std::error::Error for PermanentFailure {}
+
+/// Exponential backoff between reconnects, with random jitter so that many bots dropped at once
+/// do not all come back at the same moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+    /// Fraction of the delay that is randomised in either direction, from 0 to 1
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(300),
+            multiplier: 2.0,
+            jitter: 0.25,
+        }
+    }
+}
+
+impl Backoff {
+    /// Waits between attempts while temporarily banned: bans last hours or days, and hammering
+    /// the server only gets noticed
+    pub fn temporary_ban() -> Self {
+        Self {
+            initial: Duration::from_secs(15 * 60),
+            max: Duration::from_secs(6 * 60 * 60),
+            multiplier: 2.0,
+            jitter: 0.25,
+        }
+    }
+
+    /// Delay before reconnect number `attempt`, counting from 0
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base = self.initial.as_secs_f64() * self.multiplier.powi(attempt as i32);
+        let base = base.min(self.max.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 - jitter + 2.0 * jitter * random_unit();
+        Duration::from_secs_f64(base * factor)
+    }
+}
+
+/// A random number in `[0, 1)`, good enough for jitter without pulling in a RNG crate
+fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientEvent {
+    Connecting {
+        attempt: u32,
+    },
+    /// The server's status reported `server_protocol` and we connect with `version`, the
+    /// closest one we support. Only sent when the version is not fixed.
+    VersionChosen {
+        server_protocol: i32,
+        version: ProtocolVersion,
+    },
+    /// Login and configuration finished and we are in play
+    Connected,
+    /// The session ended, with the error that ended it
+    Disconnected {
+        reason: String,
+    },
+    Reconnecting {
+        delay: Duration,
+    },
+    GaveUp {
+        reason: String,
+    },
+}
+
+type SetupFn = dyn FnMut(&mut Connection) -> Result<()> + Send;
+
+/// Keeps a bot connected: runs login, configuration and play, and reconnects with backoff when
+/// the session ends, until the server refuses us permanently.
+pub struct ReconnectingClient {
+    host: String,
+    port: u16,
+    /// `None` to ask the server with a status ping before every connection
+    version: Option<ProtocolVersion>,
+    backoff: Backoff,
+    /// Used instead of `backoff` after a temporary ban
+    ban_backoff: Backoff,
+    /// Give up after this many failed attempts in a row
+    max_attempts: Option<u32>,
+    setup: Box<SetupFn>,
+    on_event: Box<dyn FnMut(&ClientEvent) + Send>,
+}
+
+impl ReconnectingClient {
+    pub fn new(host: String, port: u16) -> Self {
+        Self {
+            host,
+            port,
+            version: Some(ProtocolVersion::LATEST),
+            backoff: Backoff::default(),
+            ban_backoff: Backoff::temporary_ban(),
+            max_attempts: None,
+            setup: Box::new(|_| Ok(())),
+            on_event: Box::new(|_| {}),
+        }
+    }
+
+    pub fn set_version(&mut self, version: Option<ProtocolVersion>) {
+        self.version = version;
+    }
+
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        self.backoff = backoff;
+    }
+
+    pub fn set_ban_backoff(&mut self, ban_backoff: Backoff) {
+        self.ban_backoff = ban_backoff;
+    }
+
+    pub fn set_max_attempts(&mut self, max_attempts: Option<u32>) {
+        self.max_attempts = max_attempts;
+    }
+
+    /// Runs on every new connection before logging in, for example to register plugin handlers
+    /// or client settings
+    pub fn set_setup(&mut self, setup: impl FnMut(&mut Connection) -> Result<()> + Send + 'static) {
+        self.setup = Box::new(setup);
+    }
+
+    pub fn on_event(&mut self, on_event: impl FnMut(&ClientEvent) + Send + 'static) {
+        self.on_event = Box::new(on_event);
+    }
+
+    /// Only returns with an error: a [`PermanentFailure`], or the last error once
+    /// `max_attempts` attempts in a row have failed.
+    pub fn run(&mut self) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            (self.on_event)(&ClientEvent::Connecting { attempt });
+            let mut reached_play = false;
+            let err = match self.session(&mut reached_play) {
+                Ok(()) => Error::msg("session ended"),
+                Err(err) => err,
+            };
+            (self.on_event)(&ClientEvent::Disconnected {
+                reason: err.to_string(),
+            });
+
+            let disconnected = err.downcast_ref::<Disconnected>();
+            if let Some(disconnected) = disconnected
+                && let Some(kind) = PermanentFailureKind::from_reason(&disconnected.reason)
+            {
+                let failure = PermanentFailure {
+                    kind,
+                    reason: disconnected.reason.clone(),
+                };
+                (self.on_event)(&ClientEvent::GaveUp {
+                    reason: failure.to_string(),
+                });
+                return Err(failure.into());
+            }
+
+            // a session that got into play was a success, so start backing off from scratch
+            attempt = if reached_play { 0 } else { attempt + 1 };
+            if self.max_attempts.is_some_and(|max| attempt >= max) {
+                (self.on_event)(&ClientEvent::GaveUp {
+                    reason: err.to_string(),
+                });
+                return Err(err);
+            }
+
+            let backoff = match disconnected {
+                Some(disconnected) if is_temporary_ban(&disconnected.reason) => &self.ban_backoff,
+                _ => &self.backoff,
+            };
+            let delay = backoff.delay(attempt.saturating_sub(1));
+            (self.on_event)(&ClientEvent::Reconnecting { delay });
+            thread::sleep(delay);
+        }
+    }
+
+    fn session(&mut self, reached_play: &mut bool) -> Result<()> {
+        let mut conn = match self.version {
+            Some(version) => {
+                Connection::connect_with_version(self.host.clone(), self.port, version)?
+            }
+            None => {
+                let conn = Connection::connect_auto(self.host.clone(), self.port)?;
+                if let Some(server_protocol) = conn.server_protocol() {
+                    (self.on_event)(&ClientEvent::VersionChosen {
+                        server_protocol,
+                        version: conn.version(),
+                    });
+                }
+                conn
+            }
+        };
+        (self.setup)(&mut conn)?;
+        conn.login()?;
+        conn.configure()?;
+        if conn.state() == ConnectionState::Play {
+            *reached_play = true;
+            (self.on_event)(&ClientEvent::Connected);
+        }
+        conn.play()
+    }
+}
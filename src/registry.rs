@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use crate::{
+    datatypes::{Identifier, Tag},
+    packet::{DataPack, RegistryEntry},
+};
+
+/// What the server sent during configuration: registry contents, tags and the data packs we
+/// agreed we both know.
+///
+/// Everything is replaced when configuration runs again, whether after a transfer or a Start
+/// Configuration from play, since the new server's registries may differ.
+#[derive(Debug, Clone, Default)]
+pub struct Registries {
+    registries: HashMap<Identifier, Vec<RegistryEntry>>,
+    tags: HashMap<Identifier, Vec<Tag>>,
+    known_packs: Vec<DataPack>,
+}
+
+impl Registries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.registries.clear();
+        self.tags.clear();
+        self.known_packs.clear();
+    }
+
+    /// Entries in network id order
+    pub fn registry(&self, registry: &str) -> Option<&[RegistryEntry]> {
+        self.registries.get(registry).map(Vec::as_slice)
+    }
+
+    pub fn registry_ids(&self) -> impl Iterator<Item = &Identifier> {
+        self.registries.keys()
+    }
+
+    pub fn entry(&self, registry: &str, id: usize) -> Option<&RegistryEntry> {
+        self.registry(registry)?.get(id)
+    }
+
+    /// The network id of an entry
+    pub fn id_of(&self, registry: &str, name: &str) -> Option<usize> {
+        self.registry(registry)?
+            .iter()
+            .position(|entry| entry.id.as_str() == name)
+    }
+
+    /// Replaces the contents of one registry
+    pub fn insert(&mut self, registry: Identifier, entries: Vec<RegistryEntry>) {
+        self.registries.insert(registry, entries);
+    }
+
+    pub fn tags(&self, registry: &str) -> Option<&[Tag]> {
+        self.tags.get(registry).map(Vec::as_slice)
+    }
+
+    /// Replaces the tags of one registry
+    pub fn insert_tags(&mut self, registry: Identifier, tags: Vec<Tag>) {
+        self.tags.insert(registry, tags);
+    }
+
+    pub fn known_packs(&self) -> &[DataPack] {
+        &self.known_packs
+    }
+
+    pub fn set_known_packs(&mut self, known_packs: Vec<DataPack>) {
+        self.known_packs = known_packs;
+    }
+}
@@ -0,0 +1,216 @@
+use sha1::{Digest, Sha1};
+
+use crate::{
+    datatypes::{Error, UUID},
+    packet::{ResourcePackPush, ResourcePackStatus},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourcePackDecision {
+    Accept,
+    Decline,
+}
+
+/// How to answer Add Resource Pack
+#[derive(Default)]
+pub enum ResourcePackPolicy {
+    /// Accept every pack and report it loaded, so servers that require a pack let us stay
+    #[default]
+    Accept,
+    Decline,
+    Callback(Box<dyn FnMut(&ResourcePackPush) -> ResourcePackDecision + Send>),
+}
+
+impl ResourcePackPolicy {
+    pub fn callback(
+        callback: impl FnMut(&ResourcePackPush) -> ResourcePackDecision + Send + 'static,
+    ) -> Self {
+        Self::Callback(Box::new(callback))
+    }
+
+    fn decide(&mut self, pack: &ResourcePackPush) -> ResourcePackDecision {
+        match self {
+            Self::Accept => ResourcePackDecision::Accept,
+            Self::Decline => ResourcePackDecision::Decline,
+            Self::Callback(callback) => callback(pack),
+        }
+    }
+}
+
+impl std::fmt::Debug for ResourcePackPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Accept => f.write_str("Accept"),
+            Self::Decline => f.write_str("Decline"),
+            Self::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
+/// Downloads accepted packs. Without one, accepted packs are reported as loaded without being
+/// downloaded.
+pub trait ResourcePackFetcher: Send {
+    fn fetch(&mut self, url: &str) -> Result<Vec<u8>, Error>;
+}
+
+impl<F: FnMut(&str) -> Result<Vec<u8>, Error> + Send> ResourcePackFetcher for F {
+    fn fetch(&mut self, url: &str) -> Result<Vec<u8>, Error> {
+        self(url)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadedResourcePack {
+    pub uuid: UUID,
+    pub url: String,
+    pub hash: String,
+    /// The pack itself, if a fetcher downloaded it
+    pub data: Option<Vec<u8>>,
+}
+
+/// Why an accepted pack was answered with Failed Download
+#[derive(Debug)]
+pub enum ResourcePackFailure {
+    /// The SHA-1 of what was downloaded, in lowercase hex, is not the one the server sent
+    HashMismatch {
+        expected: String,
+        actual: String,
+    },
+    Download(Error),
+}
+
+impl std::fmt::Display for ResourcePackFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::HashMismatch { expected, actual } => {
+                write!(f, "expected hash {expected} but downloaded {actual}")
+            }
+            Self::Download(err) => write!(f, "download failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ResourcePackFailure {}
+
+#[derive(Debug)]
+pub struct FailedResourcePack {
+    pub uuid: UUID,
+    pub url: String,
+    pub reason: ResourcePackFailure,
+}
+
+/// The packs the server has pushed and we accepted, and how we answer new ones
+#[derive(Default)]
+pub struct ResourcePacks {
+    policy: ResourcePackPolicy,
+    fetcher: Option<Box<dyn ResourcePackFetcher>>,
+    loaded: Vec<LoadedResourcePack>,
+    failed: Vec<FailedResourcePack>,
+}
+
+impl ResourcePacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_policy(&mut self, policy: ResourcePackPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn set_fetcher(&mut self, fetcher: impl ResourcePackFetcher + 'static) {
+        self.fetcher = Some(Box::new(fetcher));
+    }
+
+    pub fn loaded(&self) -> &[LoadedResourcePack] {
+        &self.loaded
+    }
+
+    /// Accepted packs that could not be downloaded, with why, until they are pushed again or
+    /// removed
+    pub fn failed(&self) -> &[FailedResourcePack] {
+        &self.failed
+    }
+
+    /// Decides on a pushed pack, downloading it if there is a fetcher, and returns the statuses
+    /// to send back in order.
+    pub fn push(&mut self, pack: &ResourcePackPush) -> Vec<ResourcePackStatus> {
+        let url = pack.url.as_str();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return vec![ResourcePackStatus::InvalidUrl];
+        }
+        if self.policy.decide(pack) == ResourcePackDecision::Decline {
+            return vec![ResourcePackStatus::Declined];
+        }
+
+        let mut statuses = vec![ResourcePackStatus::Accepted];
+        self.failed.retain(|failed| failed.uuid != pack.uuid);
+        let fetched = self.fetcher.as_mut().map(|fetcher| {
+            let data = fetcher.fetch(url).map_err(ResourcePackFailure::Download)?;
+            let expected = pack.hash.as_str();
+            let actual = sha1_hex(&data);
+            // servers may leave the hash empty, in which case there is nothing to check
+            if !expected.is_empty() && !actual.eq_ignore_ascii_case(expected) {
+                return Err(ResourcePackFailure::HashMismatch {
+                    expected: expected.to_owned(),
+                    actual,
+                });
+            }
+            Ok(data)
+        });
+        let data = match fetched.transpose() {
+            Ok(data) => data,
+            Err(reason) => {
+                self.failed.push(FailedResourcePack {
+                    uuid: pack.uuid,
+                    url: url.to_owned(),
+                    reason,
+                });
+                statuses.push(ResourcePackStatus::FailedDownload);
+                return statuses;
+            }
+        };
+        statuses.push(ResourcePackStatus::Downloaded);
+        statuses.push(ResourcePackStatus::SuccessfullyLoaded);
+
+        self.loaded.retain(|loaded| loaded.uuid != pack.uuid);
+        self.loaded.push(LoadedResourcePack {
+            uuid: pack.uuid,
+            url: url.to_owned(),
+            hash: pack.hash.to_string(),
+            data,
+        });
+        statuses
+    }
+
+    /// Removes one pack, or all of them for `None`
+    pub fn pop(&mut self, uuid: Option<UUID>) {
+        match uuid {
+            Some(uuid) => {
+                self.loaded.retain(|loaded| loaded.uuid != uuid);
+                self.failed.retain(|failed| failed.uuid != uuid);
+            }
+            None => {
+                self.loaded.clear();
+                self.failed.clear();
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ResourcePacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResourcePacks")
+            .field("policy", &self.policy)
+            .field("has_fetcher", &self.fetcher.is_some())
+            .field("loaded", &self.loaded)
+            .field("failed", &self.failed)
+            .finish()
+    }
+}
+
+fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
@@ -0,0 +1,49 @@
+use crate::nbt::Nbt;
+
+/// Flattens a text component to plain text, dropping styling.
+///
+/// Translated components have no English text on the wire, so their translation key is used
+/// instead, followed by their arguments in brackets.
+pub fn plain_text(component: &Nbt) -> String {
+    let mut text = String::new();
+    push_plain_text(component, &mut text);
+    text
+}
+
+fn push_plain_text(component: &Nbt, out: &mut String) {
+    match component {
+        Nbt::String(s) => out.push_str(s),
+        Nbt::List(parts) => {
+            for part in parts {
+                push_plain_text(part, out);
+            }
+        }
+        Nbt::Compound(_) => {
+            if let Some(text) = component.get("text").and_then(Nbt::as_str) {
+                out.push_str(text);
+            } else if let Some(key) = component.get("translate").and_then(Nbt::as_str) {
+                match component.get("fallback").and_then(Nbt::as_str) {
+                    Some(fallback) => out.push_str(fallback),
+                    None => out.push_str(key),
+                }
+                if let Some(args) = component.get("with").and_then(Nbt::as_list) {
+                    out.push_str(" [");
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            out.push_str(", ");
+                        }
+                        push_plain_text(arg, out);
+                    }
+                    out.push(']');
+                }
+            } else if let Some(other) = component.get("") {
+                // lists of mixed types wrap their elements in a compound with an empty key
+                push_plain_text(other, out);
+            }
+            if let Some(extra) = component.get("extra") {
+                push_plain_text(extra, out);
+            }
+        }
+        _ => {}
+    }
+}
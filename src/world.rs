@@ -0,0 +1,282 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    block::{Aabb, BlockState},
+    chunk::{Chunk, Dimension},
+    datatypes::{Error, Position},
+    packet::{ChunkDataAndUpdateLightPacket, SectionBlocksUpdatePacket, UpdateLightPacket},
+};
+
+/// The chunks the server has sent us in the current dimension.
+///
+/// Filled in by the play loop; everything is dropped when the dimension changes.
+#[derive(Debug, Clone, Default)]
+pub struct World {
+    dimension: Option<Dimension>,
+    chunks: HashMap<(i32, i32), Chunk>,
+    /// When Chunk Batch Start arrived and the chunks received since, while a batch is being sent
+    batch: Option<(Instant, usize)>,
+    chunk_rate: ChunkBatchRate,
+    /// Bumped whenever a block may have changed
+    revision: u64,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dimension(&self) -> Option<&Dimension> {
+        self.dimension.as_ref()
+    }
+
+    /// Moves to another dimension, unloading every chunk
+    pub fn set_dimension(&mut self, dimension: Dimension) {
+        self.dimension = Some(dimension);
+        self.chunks.clear();
+        self.batch = None;
+        self.revision += 1;
+    }
+
+    /// Forgets the dimension and every chunk, as when the server takes us out of play. The
+    /// measured chunk rate is kept.
+    pub fn clear(&mut self) {
+        self.dimension = None;
+        self.chunks.clear();
+        self.batch = None;
+        self.revision += 1;
+    }
+
+    /// Decodes and stores a chunk, replacing any already loaded at its coordinates
+    pub fn load(&mut self, packet: ChunkDataAndUpdateLightPacket) -> Result<&Chunk, Error> {
+        let dimension = self
+            .dimension
+            .ok_or_else(|| anyhow!("chunk data before login"))?;
+        let chunk = Chunk::from_packet(packet, &dimension)?;
+        if let Some((_, received)) = &mut self.batch {
+            *received += 1;
+        }
+        self.revision += 1;
+        let slot = self.chunks.entry((chunk.x, chunk.z)).insert_entry(chunk);
+        Ok(slot.into_mut())
+    }
+
+    pub fn unload(&mut self, x: i32, z: i32) -> Option<Chunk> {
+        self.revision += 1;
+        self.chunks.remove(&(x, z))
+    }
+
+    /// A counter that changes whenever chunks are loaded or unloaded or a block is set, to tell
+    /// when something computed from the blocks, like a path, may be stale
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn chunk(&self, x: i32, z: i32) -> Option<&Chunk> {
+        self.chunks.get(&(x, z))
+    }
+
+    pub fn chunk_mut(&mut self, x: i32, z: i32) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&(x, z))
+    }
+
+    /// Loaded chunks in no particular order
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// The block state at a position, or `None` if its chunk is not loaded or it is outside the
+    /// world
+    pub fn block_at(&self, position: Position) -> Option<i32> {
+        let (x, z) = chunk_of(position);
+        let chunk = self.chunk(x, z)?;
+        chunk.block_state(
+            position.x().rem_euclid(16) as usize,
+            position.y(),
+            position.z().rem_euclid(16) as usize,
+        )
+    }
+
+    /// Whether a player can stand with their feet at a position: it and the block above are
+    /// free and the block below can be stood on. Unloaded blocks are never walkable.
+    pub fn is_walkable(&self, position: Position) -> bool {
+        let state = |dy: i32| {
+            self.block_at(Position::new(position.x(), position.y() + dy, position.z()))
+                .map(BlockState)
+        };
+        state(0).is_some_and(BlockState::is_passable)
+            && state(1).is_some_and(BlockState::is_passable)
+            && state(-1).is_some_and(BlockState::can_stand_on)
+    }
+
+    /// Collision boxes of the blocks touching an area, in world coordinates. Blocks in chunks
+    /// that are not loaded count as full cubes so that nothing moves into them, while blocks
+    /// above or below the world have none.
+    pub fn collisions(&self, area: &Aabb) -> Vec<Aabb> {
+        let mut boxes = Vec::new();
+        let range = |min: f64, max: f64| min.floor() as i32..=max.ceil() as i32 - 1;
+        for x in range(area.min.x, area.max.x) {
+            for z in range(area.min.z, area.max.z) {
+                let (chunk_x, chunk_z) = chunk_of(Position::new(x, 0, z));
+                let chunk = self.chunk(chunk_x, chunk_z);
+                for y in range(area.min.y, area.max.y) {
+                    let blocks = match chunk {
+                        None => vec![Aabb::unit()],
+                        Some(chunk) => chunk
+                            .block_state(x.rem_euclid(16) as usize, y, z.rem_euclid(16) as usize)
+                            .map_or_else(Vec::new, |state| BlockState(state).collision_boxes()),
+                    };
+                    boxes.extend(
+                        blocks
+                            .into_iter()
+                            .map(|block| block.offset(x as f64, y as f64, z as f64))
+                            .filter(|block| block.intersects(area)),
+                    );
+                }
+            }
+        }
+        boxes
+    }
+
+    /// Changes the block at a position, returning false if its chunk is not loaded or it is
+    /// outside the world
+    pub fn set_block(&mut self, position: Position, state: i32) -> bool {
+        let (x, z) = chunk_of(position);
+        self.revision += 1;
+        match self.chunk_mut(x, z) {
+            Some(chunk) => chunk.set_block_state(
+                position.x().rem_euclid(16) as usize,
+                position.y(),
+                position.z().rem_euclid(16) as usize,
+                state,
+            ),
+            None => false,
+        }
+    }
+
+    /// Applies Section Blocks Update, returning the number of blocks changed
+    pub fn apply_section_update(&mut self, packet: &SectionBlocksUpdatePacket) -> usize {
+        let (x, y, z) = packet.section();
+        packet
+            .changes()
+            .filter(|&(dx, dy, dz, state)| {
+                self.set_block(
+                    Position::new(x * 16 + dx as i32, y * 16 + dy as i32, z * 16 + dz as i32),
+                    state,
+                )
+            })
+            .count()
+    }
+
+    /// Applies Update Light, returning false if the chunk is not loaded
+    pub fn update_light(&mut self, packet: UpdateLightPacket) -> bool {
+        match self.chunk_mut(packet.chunk_x.0, packet.chunk_z.0) {
+            Some(chunk) => {
+                chunk.light.update(packet.light);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn batch_started(&mut self) {
+        self.batch = Some((Instant::now(), 0));
+    }
+
+    /// Ends the batch of `batch_size` chunks the server says it sent, timing it for the chunk
+    /// rate, and returns how many chunks actually arrived in it
+    pub fn batch_finished(&mut self, batch_size: usize) -> usize {
+        let Some((started, received)) = self.batch.take() else {
+            return 0;
+        };
+        self.chunk_rate.record(started.elapsed(), batch_size);
+        received
+    }
+
+    pub fn chunk_rate(&self) -> &ChunkBatchRate {
+        &self.chunk_rate
+    }
+
+    /// For example to cap the rate of a crawler that does heavy work per chunk
+    pub fn chunk_rate_mut(&mut self) -> &mut ChunkBatchRate {
+        &mut self.chunk_rate
+    }
+}
+
+/// Picks the chunks per tick we ask for in Chunk Batch Received, like vanilla: from a running
+/// average of how long each chunk of a batch took to arrive and be processed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkBatchRate {
+    nanos_per_chunk: f64,
+    samples: u32,
+    /// Time per tick we are willing to spend on chunks
+    pub budget: Duration,
+    pub max_chunks_per_tick: Option<f32>,
+}
+
+impl Default for ChunkBatchRate {
+    fn default() -> Self {
+        Self {
+            nanos_per_chunk: Self::INITIAL_NANOS_PER_CHUNK,
+            samples: 1,
+            budget: Duration::from_millis(7),
+            max_chunks_per_tick: None,
+        }
+    }
+}
+
+impl ChunkBatchRate {
+    const INITIAL_NANOS_PER_CHUNK: f64 = 2_000_000.0;
+    /// The average never weighs older batches more than this many new ones
+    const MAX_SAMPLES: u32 = 49;
+    /// A single batch cannot move the estimate by more than this factor
+    const CLAMP: f64 = 3.0;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, elapsed: Duration, batch_size: usize) {
+        if batch_size == 0 {
+            return;
+        }
+        let per_chunk = (elapsed.as_nanos() as f64 / batch_size as f64).clamp(
+            self.nanos_per_chunk / Self::CLAMP,
+            self.nanos_per_chunk * Self::CLAMP,
+        );
+        let samples = self.samples as f64;
+        self.nanos_per_chunk = (self.nanos_per_chunk * samples + per_chunk) / (samples + 1.0);
+        self.samples = (self.samples + 1).min(Self::MAX_SAMPLES);
+    }
+
+    /// Average time a chunk takes
+    pub fn time_per_chunk(&self) -> Duration {
+        Duration::from_nanos(self.nanos_per_chunk as u64)
+    }
+
+    pub fn desired_chunks_per_tick(&self) -> f32 {
+        let desired = (self.budget.as_nanos() as f64 / self.nanos_per_chunk) as f32;
+        match self.max_chunks_per_tick {
+            Some(max) => desired.min(max),
+            None => desired,
+        }
+    }
+}
+
+/// Chunk x and z of a block position
+pub fn chunk_of(position: Position) -> (i32, i32) {
+    (position.x().div_euclid(16), position.z().div_euclid(16))
+}
@@ -0,0 +1,155 @@
+use minecraft_client::{
+    block::{block, blocks, BlockShape, BlockState},
+    chunk::{ChunkSection, Dimension, LightData, PaletteStrategy, PalettedContainer},
+    datatypes::Position,
+    packet::ChunkDataAndUpdateLightPacket,
+    world::World,
+};
+
+fn state(s: &str) -> BlockState {
+    s.parse().unwrap()
+}
+
+#[test]
+fn well_known_ids() {
+    assert_eq!(state("air"), BlockState(0));
+    assert_eq!(state("minecraft:stone"), BlockState(1));
+    assert_eq!(state("grass_block[snowy=true]"), BlockState(8));
+    assert_eq!(state("grass_block"), BlockState(9));
+    assert_eq!(state("water"), BlockState(86));
+    assert_eq!(state("water[level=15]"), BlockState(101));
+    assert_eq!(state("lava"), BlockState(102));
+    assert_eq!(block("minecraft:bedrock").unwrap().first_state, 85);
+}
+
+#[test]
+fn the_whole_registry_is_known() {
+    let cave_air = block("minecraft:cave_air").unwrap().default_state();
+    assert_eq!(cave_air.to_string(), "minecraft:cave_air");
+    assert_eq!(state("cave_air"), cave_air);
+    assert!(cave_air.is_air() && cave_air.is_passable());
+
+    let stairs = block("minecraft:oak_stairs").unwrap().default_state();
+    assert_eq!(
+        stairs.to_string(),
+        "minecraft:oak_stairs[facing=north,half=bottom,shape=straight,waterlogged=false]"
+    );
+    assert_eq!(state(&stairs.to_string()), stairs);
+    assert_eq!(state("oak_stairs[half=top]").property("half"), Some("top"));
+
+    // plants and thin blocks past note_block are walked through rather than into
+    for name in ["short_grass", "poppy", "torch", "rail"] {
+        assert!(state(name).is_passable(), "{name}");
+    }
+    // while ones whose collision depends on their state or is smaller than a block are not
+    for name in ["snow[layers=8]", "snow", "conduit", "sea_pickle", "red_candle"] {
+        assert!(state(name).is_solid(), "{name}");
+    }
+    assert_eq!(state("white_carpet").shape(), BlockShape::Height(1));
+    assert!(state("mud").can_stand_on());
+
+    let last = blocks().last().unwrap();
+    assert_eq!(last.first_state + last.state_count(), 29671);
+}
+
+#[test]
+fn names_round_trip() {
+    for block in blocks() {
+        for id in block.first_state..block.first_state + block.state_count() {
+            let name = BlockState(id).to_string();
+            assert_eq!(state(&name), BlockState(id), "{name}");
+        }
+    }
+    let last = blocks().last().unwrap();
+    let past_the_end = BlockState(last.first_state + last.state_count());
+    assert!(past_the_end.info().is_none());
+    assert_eq!(past_the_end.to_string(), past_the_end.0.to_string());
+    assert!(BlockState(-1).info().is_none());
+}
+
+#[test]
+fn properties() {
+    let propagule = state("mangrove_propagule[age=2,waterlogged=true]");
+    assert_eq!(
+        propagule.to_string(),
+        "minecraft:mangrove_propagule[age=2,hanging=false,stage=0,waterlogged=true]"
+    );
+    assert_eq!(propagule.property("age"), Some("2"));
+    let hanging = propagule.with("hanging", "true").unwrap();
+    assert_eq!(hanging.property("hanging"), Some("true"));
+    assert_eq!(hanging.property("age"), Some("2"));
+    assert!(propagule.with("hanging", "sideways").is_none());
+    assert!(propagule.with("facing", "north").is_none());
+
+    let leaves = block("minecraft:oak_leaves").unwrap().default_state();
+    assert_eq!(leaves.property("distance"), Some("7"));
+    assert_eq!(leaves.property("persistent"), Some("false"));
+
+    assert!("stone[".parse::<BlockState>().is_err());
+    assert!("stone[snowy=true]".parse::<BlockState>().is_err());
+    assert!("no_such_block".parse::<BlockState>().is_err());
+}
+
+#[test]
+fn shapes() {
+    assert!(state("air").is_air());
+    assert!(state("air").is_passable());
+    assert!(state("stone").is_solid());
+    assert!(state("stone").can_stand_on());
+    assert!(state("oak_sapling").is_passable());
+    assert!(!state("oak_sapling").can_stand_on());
+    assert!(state("water").is_liquid());
+    assert!(state("water").is_passable());
+    assert_eq!(state("water").shape(), BlockShape::Liquid);
+    // unknown states are kept out of
+    assert!(BlockState(1_000_000).is_solid());
+    assert!(!BlockState(1_000_000).is_passable());
+}
+
+#[test]
+fn walkable_positions() {
+    let dimension = Dimension {
+        min_y: 0,
+        height: 16,
+        biome_count: 1,
+    };
+    let mut values = vec![0; 4096];
+    for x in 0..16 {
+        for z in 0..16 {
+            values[z * 16 + x] = state("stone").0;
+        }
+    }
+    values[(16 + 3) * 16 + 2] = state("water").0;
+    let mut data = Vec::new();
+    ChunkSection {
+        block_count: 256,
+        block_states: PalettedContainer::from_values(PaletteStrategy::BLOCK_STATES, &values),
+        biomes: PalettedContainer::single(dimension.biome_strategy(), 0),
+    }
+    .encode(&mut data)
+    .unwrap();
+
+    let mut world = World::new();
+    world.set_dimension(dimension);
+    world
+        .load(ChunkDataAndUpdateLightPacket {
+            chunk_x: 0,
+            chunk_z: 0,
+            heightmaps: vec![],
+            data,
+            block_entities: vec![],
+            light: LightData::default(),
+        })
+        .unwrap();
+
+    assert!(world.is_walkable(Position::new(0, 1, 0)));
+    assert!(!world.is_walkable(Position::new(0, 0, 0)));
+    assert!(!world.is_walkable(Position::new(0, 2, 0)));
+    // water to wade through, but nothing to stand on above it
+    assert!(world.is_walkable(Position::new(2, 1, 3)));
+    assert!(!world.is_walkable(Position::new(2, 2, 3)));
+    world.set_block(Position::new(0, 2, 0), state("stone").0);
+    assert!(!world.is_walkable(Position::new(0, 1, 0)));
+    assert!(world.is_walkable(Position::new(0, 3, 0)));
+    assert!(!world.is_walkable(Position::new(20, 1, 0)));
+}
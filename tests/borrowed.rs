@@ -0,0 +1,217 @@
+use minecraft_client::{
+    chunk::{BlockEntity, ChunkSection, Dimension, LightData, PaletteStrategy, PalettedContainer},
+    datatypes::{
+        ByteSlice, Identifier as MinecraftDataString, IdentifierRef, MStr, MinecraftData,
+        MinecraftDataRef, VarInt,
+    },
+    nbt::{Nbt, NbtRef},
+    packet::{
+        decode_packet_body_ref, ChunkDataAndUpdateLightPacket, ChunkDataAndUpdateLightPacketRef,
+        RegistryDataPacket, RegistryDataPacketRef, RegistryEntry,
+    },
+    protocol::ProtocolVersion,
+};
+
+fn encode<T: MinecraftData>(value: T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).unwrap();
+    bytes
+}
+
+fn sample_nbt() -> Nbt {
+    Nbt::Compound(vec![
+        ("height".to_owned(), Nbt::Int(384)),
+        ("min_y".to_owned(), Nbt::Int(-64)),
+        (
+            "name".to_owned(),
+            Nbt::String("caf\u{e9} \u{0} 🙂".to_owned()),
+        ),
+        (
+            "list".to_owned(),
+            Nbt::List(vec![Nbt::Double(1.5), Nbt::Double(-2.0)]),
+        ),
+        ("longs".to_owned(), Nbt::LongArray(vec![1, -1, i64::MAX])),
+        ("bytes".to_owned(), Nbt::ByteArray(vec![1, 2, 3])),
+        ("nested".to_owned(), Nbt::Compound(vec![])),
+    ])
+}
+
+#[test]
+fn nbt_round_trip() {
+    let nbt = sample_nbt();
+    let bytes = encode(nbt.clone());
+    assert_eq!(bytes.len(), nbt.num_bytes());
+    assert_eq!(Nbt::decode(&mut &bytes[..]).unwrap(), nbt);
+    assert_eq!(nbt.get("min_y").and_then(Nbt::as_i64), Some(-64));
+}
+
+#[test]
+fn nbt_strings_are_modified_utf8() {
+    let bytes = encode(Nbt::String("\0🙂".to_owned()));
+    // type, length, NUL as two bytes, then the emoji as two three-byte surrogates
+    assert_eq!(
+        bytes,
+        [8, 0, 8, 0xC0, 0x80, 0xED, 0xA0, 0xBD, 0xED, 0xB9, 0x82]
+    );
+}
+
+#[test]
+fn nbt_ref_borrows_exactly_one_tag() {
+    let mut bytes = encode(sample_nbt());
+    let len = bytes.len();
+    bytes.extend_from_slice(&[0xAA, 0xBB]);
+    let mut buf = &bytes[..];
+    let nbt = NbtRef::decode_ref(&mut buf).unwrap();
+    assert_eq!(nbt.as_bytes().len(), len);
+    assert_eq!(buf, [0xAA, 0xBB]);
+    assert_eq!(nbt.to_owned().unwrap(), sample_nbt());
+}
+
+#[test]
+fn nbt_depth_limit() {
+    // 1000 nested lists of lists
+    let mut bytes = vec![9];
+    for _ in 0..1000 {
+        bytes.extend_from_slice(&[9, 0, 0, 0, 1]);
+    }
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0]);
+    assert!(Nbt::decode(&mut &bytes[..]).is_err());
+    assert!(NbtRef::decode_ref(&mut &bytes[..]).is_err());
+}
+
+#[test]
+fn nbt_ref_truncated() {
+    let bytes = encode(sample_nbt());
+    assert!(NbtRef::decode_ref(&mut &bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn borrowed_strings_and_bytes() {
+    let mut bytes = encode(MinecraftDataString::try_from("minecraft:stone").unwrap());
+    bytes.extend(encode(vec![9u8, 8, 7]));
+    let mut buf = &bytes[..];
+    let id = IdentifierRef::decode_ref(&mut buf).unwrap();
+    let data = ByteSlice::decode_ref(&mut buf).unwrap();
+    assert!(buf.is_empty());
+    assert_eq!(id.as_str(), "minecraft:stone");
+    assert_eq!(data.0, [9, 8, 7]);
+    // the borrowed values point into the original buffer
+    assert!(bytes.as_ptr_range().contains(&id.as_str().as_ptr()));
+    assert!(MStr::<4>::decode_ref(&mut &bytes[..]).is_err());
+    assert!(ByteSlice::decode_ref(&mut &encode(VarInt(100))[..]).is_err());
+}
+
+#[test]
+fn registry_packet_borrowed_matches_owned() {
+    let packet = RegistryDataPacket {
+        registry_id: "minecraft:dimension_type".try_into().unwrap(),
+        entries: vec![
+            RegistryEntry {
+                id: "minecraft:overworld".try_into().unwrap(),
+                data: Some(sample_nbt()),
+            },
+            RegistryEntry {
+                id: "minecraft:the_end".try_into().unwrap(),
+                data: None,
+            },
+        ],
+    };
+    let bytes = encode(packet.clone());
+    let borrowed: RegistryDataPacketRef =
+        decode_packet_body_ref(&bytes, ProtocolVersion::LATEST).unwrap();
+    assert_eq!(borrowed.registry_id.as_str(), "minecraft:dimension_type");
+    assert_eq!(borrowed.entries.len(), 2);
+    assert_eq!(borrowed.entries[0].id.as_str(), "minecraft:overworld");
+    assert_eq!(
+        borrowed.entries[0].data.unwrap().to_owned().unwrap(),
+        sample_nbt()
+    );
+    assert!(borrowed.entries[1].data.is_none());
+}
+
+#[test]
+fn chunk_packet_borrows_its_sections() {
+    let dimension = Dimension {
+        min_y: 0,
+        height: 32,
+        biome_count: 4,
+    };
+    let sections: Vec<ChunkSection> = [1, 0]
+        .into_iter()
+        .map(|block| ChunkSection {
+            block_count: if block == 0 { 0 } else { 4096 },
+            block_states: PalettedContainer::single(PaletteStrategy::BLOCK_STATES, block),
+            biomes: PalettedContainer::single(dimension.biome_strategy(), 0),
+        })
+        .collect();
+    let mut data = Vec::new();
+    for section in &sections {
+        section.encode(&mut data).unwrap();
+    }
+    let chest = BlockEntity {
+        packed_xz: 0x3A,
+        y: 5,
+        kind: VarInt(2),
+        data: sample_nbt(),
+    };
+    let bytes = encode(ChunkDataAndUpdateLightPacket {
+        chunk_x: 2,
+        chunk_z: -3,
+        heightmaps: vec![],
+        data,
+        block_entities: vec![chest.clone()],
+        light: LightData::default(),
+    });
+
+    let borrowed: ChunkDataAndUpdateLightPacketRef =
+        decode_packet_body_ref(&bytes, ProtocolVersion::LATEST).unwrap();
+    assert_eq!((borrowed.chunk_x, borrowed.chunk_z), (2, -3));
+    assert!(bytes.as_ptr_range().contains(&borrowed.data.0.as_ptr()));
+    assert_eq!(
+        ChunkSection::decode_all(borrowed.data.0, &dimension).unwrap(),
+        sections
+    );
+    let entity = borrowed.block_entities[0];
+    assert_eq!((entity.x(), entity.z()), (3, 10));
+    assert!(bytes
+        .as_ptr_range()
+        .contains(&entity.data.as_bytes().as_ptr()));
+    assert_eq!(entity.to_owned().unwrap(), chest);
+}
+
+#[derive(Debug, MinecraftData)]
+#[mc(borrowed)]
+struct Borrowed<'a> {
+    name: MStr<'a, 16>,
+    count: VarInt,
+    has_payload: bool,
+    #[present_if(has_payload)]
+    #[mc(max_len = 4)]
+    payload: Option<ByteSlice<'a>>,
+    kind: BorrowedKind<'a>,
+}
+
+#[derive(Debug, MinecraftData)]
+#[mc(borrowed)]
+enum BorrowedKind<'a> {
+    #[mc_repr(VarInt(0))]
+    Empty,
+    #[mc_repr(VarInt(1))]
+    Named { name: MStr<'a, 16> },
+}
+
+#[test]
+fn derive_borrowed() {
+    let mut bytes = encode(MinecraftDataString::try_from("bob").unwrap());
+    bytes.extend(encode(VarInt(5)));
+    bytes.extend(encode(true));
+    bytes.extend(encode(vec![1u8, 2]));
+    bytes.extend(encode(VarInt(1)));
+    bytes.extend(encode(MinecraftDataString::try_from("alice").unwrap()));
+    let value: Borrowed = decode_packet_body_ref(&bytes, ProtocolVersion::LATEST).unwrap();
+    assert_eq!(value.name.as_str(), "bob");
+    assert_eq!(value.count, VarInt(5));
+    assert!(value.has_payload);
+    assert_eq!(value.payload.unwrap().0, [1, 2]);
+    assert!(matches!(value.kind, BorrowedKind::Named { name } if name.as_str() == "alice"));
+}
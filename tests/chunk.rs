@@ -0,0 +1,278 @@
+mod common;
+
+use common::{play_until_ended, FakeServer, MIN_Y};
+use minecraft_client::{
+    chunk::{
+        Chunk, ChunkSection, Dimension, Heightmap, HeightmapKind, LightArray, LightData, Palette,
+        PaletteStrategy, PalettedContainer,
+    },
+    connection::Connection,
+    datatypes::{BitSet, MinecraftData, VarInt},
+    nbt::Nbt,
+    packet::{
+        ChunkDataAndUpdateLightPacket, ClientboundPlayKeepAlivePacket, RegistryEntry,
+        ServerboundPlayKeepAlivePacket, UpdateLightPacket,
+    },
+    registry::Registries,
+};
+
+const DIMENSION: Dimension = Dimension {
+    min_y: -64,
+    height: 384,
+    biome_count: 4,
+};
+
+fn round_trip(container: &PalettedContainer) -> PalettedContainer {
+    let mut bytes = Vec::new();
+    container.encode(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), container.num_bytes());
+    let mut slice = bytes.as_slice();
+    let decoded = PalettedContainer::decode(&mut slice, container.strategy()).unwrap();
+    assert!(slice.is_empty());
+    decoded
+}
+
+#[test]
+fn single_valued_containers() {
+    let container = PalettedContainer::from_values(PaletteStrategy::BLOCK_STATES, &[9; 4096]);
+    assert_eq!(container.palette(), &Palette::Single(9));
+    assert_eq!(container.num_bytes(), 2);
+    assert_eq!(round_trip(&container), container);
+    assert!(container.iter().all(|state| state == 9));
+}
+
+#[test]
+fn small_block_palettes_are_packed_with_four_bits() {
+    // what vanilla sends for two block states: 1 bit on the wire, 4 bits per entry in the longs
+    let mut bytes = vec![1u8, 2, 0, 9];
+    let mut longs = vec![0u64; 256];
+    longs[0] = 0x10;
+    for long in longs {
+        bytes.extend(long.to_be_bytes());
+    }
+    let container =
+        PalettedContainer::decode(&mut bytes.as_slice(), PaletteStrategy::BLOCK_STATES).unwrap();
+    assert_eq!(container.bits(), 4);
+    assert_eq!(container.get(0), 0);
+    assert_eq!(container.get(1), 9);
+    assert_eq!(container.get(2), 0);
+
+    let mut values = vec![0; 4096];
+    values[1] = 9;
+    values[4095] = 3;
+    let packed = PalettedContainer::from_values(PaletteStrategy::BLOCK_STATES, &values);
+    assert_eq!(packed.bits(), 4);
+    assert_eq!(round_trip(&packed).iter().collect::<Vec<_>>(), values);
+}
+
+#[test]
+fn direct_block_palettes() {
+    let values: Vec<i32> = (0..4096).map(|i| i * 7).collect();
+    let container = PalettedContainer::from_values(PaletteStrategy::BLOCK_STATES, &values);
+    assert_eq!(container.palette(), &Palette::Direct);
+    assert_eq!(container.bits(), 15);
+    let decoded = round_trip(&container);
+    assert_eq!(decoded.iter().collect::<Vec<_>>(), values);
+}
+
+#[test]
+fn biome_palettes() {
+    let strategy = PaletteStrategy::biomes(4);
+    let values: Vec<i32> = (0..64).map(|i| i % 2).collect();
+    let indirect = PalettedContainer::from_values(strategy, &values);
+    assert_eq!(indirect.bits(), 1);
+    assert_eq!(round_trip(&indirect).iter().collect::<Vec<_>>(), values);
+
+    // more biomes than an indirect palette holds
+    let strategy = PaletteStrategy::biomes(64);
+    let values: Vec<i32> = (0..64).collect();
+    let direct = PalettedContainer::from_values(strategy, &values);
+    assert_eq!(direct.palette(), &Palette::Direct);
+    assert_eq!(direct.bits(), 6);
+    assert_eq!(round_trip(&direct).iter().collect::<Vec<_>>(), values);
+}
+
+#[test]
+fn palette_indices_are_checked() {
+    let mut bytes = vec![4u8, 1, 5];
+    bytes.extend(1u64.to_be_bytes());
+    bytes.extend([0; 255 * 8]);
+    assert!(
+        PalettedContainer::decode(&mut bytes.as_slice(), PaletteStrategy::BLOCK_STATES).is_err()
+    );
+
+    // a palette longer than the container
+    let mut bytes = vec![8u8];
+    VarInt(5000).encode(&mut bytes).unwrap();
+    assert!(
+        PalettedContainer::decode(&mut bytes.as_slice(), PaletteStrategy::BLOCK_STATES).is_err()
+    );
+}
+
+fn section(block: i32) -> ChunkSection {
+    ChunkSection {
+        block_count: if block == 0 { 0 } else { 4096 },
+        block_states: PalettedContainer::single(PaletteStrategy::BLOCK_STATES, block),
+        biomes: PalettedContainer::single(DIMENSION.biome_strategy(), 0),
+    }
+}
+
+fn encode_sections(sections: &[ChunkSection]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for section in sections {
+        section.encode(&mut data).unwrap();
+    }
+    data
+}
+
+fn chunk_packet(x: i32, z: i32, sections: &[ChunkSection]) -> ChunkDataAndUpdateLightPacket {
+    ChunkDataAndUpdateLightPacket {
+        chunk_x: x,
+        chunk_z: z,
+        heightmaps: vec![],
+        data: encode_sections(sections),
+        block_entities: vec![],
+        light: LightData::default(),
+    }
+}
+
+#[test]
+fn chunks_decode_with_the_dimension_height() {
+    let mut sections: Vec<ChunkSection> = (0..24).map(|_| section(0)).collect();
+    sections[0] = section(1);
+    let mut values = vec![0; 4096];
+    // x 3, y 5, z 2 in the section just above y 0
+    values[(5 * 16 + 2) * 16 + 3] = 42;
+    sections[4].block_states =
+        PalettedContainer::from_values(PaletteStrategy::BLOCK_STATES, &values);
+
+    let chunk = Chunk::from_packet(chunk_packet(2, -3, &sections), &DIMENSION).unwrap();
+    assert_eq!((chunk.x, chunk.z), (2, -3));
+    assert_eq!(chunk.sections.len(), 24);
+    assert_eq!(chunk.block_state(0, -64, 0), Some(1));
+    assert_eq!(chunk.block_state(15, -49, 15), Some(1));
+    assert_eq!(chunk.block_state(0, -48, 0), Some(0));
+    assert_eq!(chunk.block_state(3, 5, 2), Some(42));
+    assert_eq!(chunk.block_state(0, -65, 0), None);
+    assert_eq!(chunk.block_state(0, 320, 0), None);
+
+    // a dimension with fewer sections leaves bytes over, one with more runs out
+    let short = Dimension {
+        height: 256,
+        ..DIMENSION
+    };
+    assert!(Chunk::from_packet(chunk_packet(0, 0, &sections), &short).is_err());
+    let tall = Dimension {
+        height: 400,
+        ..DIMENSION
+    };
+    assert!(Chunk::from_packet(chunk_packet(0, 0, &sections), &tall).is_err());
+}
+
+#[test]
+fn packets_round_trip() {
+    let mut sky_light_mask = BitSet::default();
+    sky_light_mask.set(1, true);
+    sky_light_mask.set(3, true);
+    let mut bright = vec![0u8; LightArray::LEN];
+    bright[0] = 0xF0;
+    let packet = ChunkDataAndUpdateLightPacket {
+        heightmaps: vec![Heightmap {
+            kind: HeightmapKind::MotionBlocking,
+            data: vec![0; 37],
+        }],
+        light: LightData {
+            sky_light_mask,
+            sky_light: vec![LightArray(vec![0; LightArray::LEN]), LightArray(bright)],
+            ..LightData::default()
+        },
+        ..chunk_packet(1, 1, &[section(1)])
+    };
+    let mut bytes = Vec::new();
+    let len = packet.num_bytes();
+    packet.encode(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), len);
+    let decoded = ChunkDataAndUpdateLightPacket::decode(&mut bytes.as_slice()).unwrap();
+    assert_eq!(decoded.heightmaps[0].kind, HeightmapKind::MotionBlocking);
+    assert!(decoded.light.sky_light(0).is_none());
+    assert_eq!(decoded.light.sky_light(1).unwrap().get(1, 0, 0), 0);
+    assert_eq!(decoded.light.sky_light(3).unwrap().get(1, 0, 0), 15);
+    assert_eq!(decoded.light.sky_light(3).unwrap().get(0, 0, 0), 0);
+
+    let mut short = Vec::new();
+    VarInt(10).encode(&mut short).unwrap();
+    short.extend([0; 10]);
+    assert!(LightArray::decode(&mut short.as_slice()).is_err());
+}
+
+#[test]
+fn heightmaps_are_relative_to_the_bottom() {
+    // 9 bits per column for 384 blocks, 7 columns to a long
+    let mut data = vec![0i64; 37];
+    data[0] = 100 | (200 << 9);
+    let heightmap = Heightmap {
+        kind: HeightmapKind::WorldSurface,
+        data,
+    };
+    assert_eq!(heightmap.get(0, 0, &DIMENSION), Some(36));
+    assert_eq!(heightmap.get(1, 0, &DIMENSION), Some(136));
+    assert_eq!(heightmap.get(2, 0, &DIMENSION), Some(-64));
+}
+
+#[test]
+fn dimension_from_registries() {
+    let mut registries = Registries::new();
+    registries.insert(
+        "minecraft:dimension_type".try_into().unwrap(),
+        vec![RegistryEntry {
+            id: "minecraft:the_nether".try_into().unwrap(),
+            data: Some(Nbt::Compound(vec![
+                ("min_y".to_string(), Nbt::Int(0)),
+                ("height".to_string(), Nbt::Int(256)),
+            ])),
+        }],
+    );
+    assert!(Dimension::from_registries(&registries, 0).is_err());
+    registries.insert(
+        "minecraft:worldgen/biome".try_into().unwrap(),
+        vec![RegistryEntry {
+            id: "minecraft:nether_wastes".try_into().unwrap(),
+            data: None,
+        }],
+    );
+    let dimension = Dimension::from_registries(&registries, 0).unwrap();
+    assert_eq!(
+        dimension,
+        Dimension {
+            min_y: 0,
+            height: 256,
+            biome_count: 1,
+        }
+    );
+    assert_eq!(dimension.section_count(), 16);
+    assert!(Dimension::from_registries(&registries, 1).is_err());
+}
+
+#[test]
+fn play_continues_past_chunks() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        let sections: Vec<ChunkSection> = (0..24).map(|_| section(1)).collect();
+        server.send(chunk_packet(0, 0, &sections));
+        server.send(UpdateLightPacket {
+            chunk_x: VarInt(0),
+            chunk_z: VarInt(0),
+            light: LightData::default(),
+        });
+        server.send(ClientboundPlayKeepAlivePacket { keep_alive_id: 3 });
+        server.recv_skipping::<ServerboundPlayKeepAlivePacket>();
+        server.end_play();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    play_until_ended(&mut conn);
+    assert_eq!(conn.dimension().unwrap().min_y, MIN_Y);
+    server.join().unwrap();
+}
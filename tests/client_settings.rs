@@ -0,0 +1,74 @@
+mod common;
+
+use common::FakeServer;
+use minecraft_client::{
+    connection::Connection,
+    datatypes::{GameProfile, MinecraftData, UUID},
+    packet::{
+        AcknowledgeFinishConfigurationPacket, ChatMode, ClientSettings,
+        ConfigurationClientInformationPacket, FinishConfigurationPacket, LoginAcknowledgedPacket,
+        LoginStartPacket, LoginSuccessPacket, MainHand, ParticleStatus,
+        PlayClientInformationPacket,
+    },
+};
+
+fn crawler_settings() -> ClientSettings {
+    ClientSettings {
+        locale: "de_de".try_into().unwrap(),
+        view_distance: 2,
+        chat_mode: ChatMode::Hidden,
+        displayed_skin_parts: ClientSettings::SKIN_CAPE | ClientSettings::SKIN_HAT,
+        main_hand: MainHand::Left,
+        particle_status: ParticleStatus::Minimal,
+        ..ClientSettings::default()
+    }
+}
+
+#[test]
+fn wire_format() {
+    let mut bytes = Vec::new();
+    let settings = crawler_settings();
+    let len = settings.num_bytes();
+    settings.encode(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), len);
+    assert_eq!(
+        bytes,
+        [5, b'd', b'e', b'_', b'd', b'e', 2, 2, 1, 0x41, 0, 0, 1, 2]
+    );
+    assert_eq!(
+        ClientSettings::decode(&mut &bytes[..]).unwrap(),
+        crawler_settings()
+    );
+}
+
+#[test]
+fn sent_on_configure_and_when_changed() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        let start = server.recv::<LoginStartPacket>();
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+        let info = server.recv_skipping::<ConfigurationClientInformationPacket>();
+        assert_eq!(info.settings, crawler_settings());
+        server.send(FinishConfigurationPacket);
+        server.recv::<AcknowledgeFinishConfigurationPacket>();
+        let info = server.recv::<PlayClientInformationPacket>();
+        assert_eq!(info.settings.view_distance, 5);
+        assert_eq!(info.settings.locale.as_str(), "de_de");
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.set_client_settings(crawler_settings()).unwrap();
+    connection.login().unwrap();
+    connection.configure().unwrap();
+    let mut settings = connection.client_settings().clone();
+    settings.view_distance = 5;
+    connection.set_client_settings(settings).unwrap();
+    server.join().unwrap();
+}
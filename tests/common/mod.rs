@@ -0,0 +1,234 @@
+//! A scripted stand-in for a server, so that connection flows can be tested offline.
+
+#![allow(dead_code)]
+
+use std::{
+    io::{BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+};
+
+use minecraft_client::{
+    chunk::{ChunkSection, Dimension, LightData, PaletteStrategy, PalettedContainer},
+    connection::{Connection, Disconnected},
+    datatypes::{GameProfile, VarInt, UUID},
+    nbt::Nbt,
+    packet::{
+        decode_packet_body, decode_packet_header, AcknowledgeFinishConfigurationPacket,
+        ChunkDataAndUpdateLightPacket, FinishConfigurationPacket, LoginAcknowledgedPacket,
+        LoginStartPacket, LoginSuccessPacket, Packet, PlayDisconnectPacket, PlayLoginPacket,
+        RegistryDataPacket, RegistryEntry,
+    },
+    protocol::ProtocolVersion,
+    world::World,
+};
+
+/// Entity id of the player in [`FakeServer::join_game`]
+pub const PLAYER_ENTITY_ID: i32 = 7;
+
+/// Bottom of the overworld in [`FakeServer::join_game`]
+pub const MIN_Y: i32 = -64;
+
+/// The overworld [`FakeServer::join_game`] puts the client in
+pub const DIMENSION: Dimension = Dimension {
+    min_y: MIN_Y,
+    height: 384,
+    biome_count: 4,
+};
+
+/// A chunk of [`DIMENSION`] with stone up to y 0 and air above
+pub fn flat_chunk(x: i32, z: i32) -> ChunkDataAndUpdateLightPacket {
+    let mut data = Vec::new();
+    for i in 0..DIMENSION.section_count() {
+        let block = if i < 4 { 1 } else { 0 };
+        ChunkSection {
+            block_count: if block == 0 { 0 } else { 4096 },
+            block_states: PalettedContainer::single(PaletteStrategy::BLOCK_STATES, block),
+            biomes: PalettedContainer::single(DIMENSION.biome_strategy(), 0),
+        }
+        .encode(&mut data)
+        .unwrap();
+    }
+    ChunkDataAndUpdateLightPacket {
+        chunk_x: x,
+        chunk_z: z,
+        heightmaps: vec![],
+        data,
+        block_entities: vec![],
+        light: LightData::default(),
+    }
+}
+
+/// Flat chunks from -1 to 1 on both axes, enough to walk around the middle one
+pub fn flat_world() -> World {
+    let mut world = World::new();
+    world.set_dimension(DIMENSION);
+    for x in -1..=1 {
+        for z in -1..=1 {
+            world.load(flat_chunk(x, z)).unwrap();
+        }
+    }
+    world
+}
+
+fn registry_entry(id: &str, data: Option<Nbt>) -> RegistryEntry {
+    RegistryEntry {
+        id: id.try_into().unwrap(),
+        data,
+    }
+}
+
+/// The reason [`FakeServer::end_play`] gives, so that tests can tell it from a real failure
+pub const END_OF_TEST: &str = "end of test";
+
+/// Runs the play loop until the fake server calls [`FakeServer::end_play`]
+pub fn play_until_ended(conn: &mut Connection) {
+    let err = conn.play().unwrap_err();
+    match err.downcast_ref::<Disconnected>() {
+        Some(disconnected) if disconnected.reason == END_OF_TEST => {}
+        _ => panic!("play ended early: {err:?}"),
+    }
+}
+
+pub struct FakeServer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl FakeServer {
+    /// Binds to a free local port and runs `script` against the first client that connects
+    pub fn spawn<F>(script: F) -> (u16, JoinHandle<()>)
+    where
+        F: FnOnce(FakeServer) + Send + 'static,
+    {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            script(FakeServer {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            });
+        });
+        (port, handle)
+    }
+
+    /// Like `spawn`, but runs each script in turn against the next client that connects
+    pub fn spawn_each(scripts: Vec<Box<dyn FnOnce(FakeServer) + Send>>) -> (u16, JoinHandle<()>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            for script in scripts {
+                let (stream, _) = listener.accept().unwrap();
+                script(FakeServer {
+                    reader: BufReader::new(stream.try_clone().unwrap()),
+                    writer: stream,
+                });
+            }
+        });
+        (port, handle)
+    }
+
+    pub fn send<P: Packet>(&mut self, packet: P) {
+        let mut bytes = Vec::new();
+        packet.encode_packet(&mut bytes).unwrap();
+        self.writer.write_all(&bytes).unwrap();
+    }
+
+    /// Sends an already framed packet, for example one encoded with an older version's id
+    pub fn send_raw(&mut self, bytes: &[u8]) {
+        self.writer.write_all(bytes).unwrap();
+    }
+
+    /// Reads the next packet, returning its id and body
+    pub fn recv_raw(&mut self) -> (VarInt, Vec<u8>) {
+        let header = decode_packet_header(&mut self.reader).unwrap();
+        let mut body = vec![0u8; header.body_len()];
+        self.reader.read_exact(&mut body).unwrap();
+        (header.id, body)
+    }
+
+    /// Reads the next packet, which must be a `P`
+    pub fn recv<P: Packet>(&mut self) -> P {
+        let (id, body) = self.recv_raw();
+        assert_eq!(id, P::ID, "expected {}", std::any::type_name::<P>());
+        decode_packet_body(&body, ProtocolVersion::LATEST).unwrap()
+    }
+
+    /// Skips packets until one with the id of `P` arrives
+    pub fn recv_skipping<P: Packet>(&mut self) -> P {
+        loop {
+            let (id, body) = self.recv_raw();
+            if id == P::ID {
+                return decode_packet_body(&body, ProtocolVersion::LATEST).unwrap();
+            }
+        }
+    }
+
+    /// Disconnects a client in play, which is how a test ends the play loop
+    pub fn end_play(&mut self) {
+        self.send(PlayDisconnectPacket {
+            reason: Nbt::String(END_OF_TEST.to_string()),
+        });
+    }
+
+    /// Takes a client through login and configuration into play, in a 384 block tall overworld
+    /// with four biomes
+    pub fn join_game(&mut self) {
+        self.recv_raw();
+        let start = self.recv::<LoginStartPacket>();
+        self.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        self.recv::<LoginAcknowledgedPacket>();
+        self.send(RegistryDataPacket {
+            registry_id: "minecraft:dimension_type".try_into().unwrap(),
+            entries: vec![registry_entry(
+                "minecraft:overworld",
+                Some(Nbt::Compound(vec![
+                    ("min_y".to_string(), Nbt::Int(MIN_Y)),
+                    ("height".to_string(), Nbt::Int(384)),
+                ])),
+            )],
+        });
+        self.send(RegistryDataPacket {
+            registry_id: "minecraft:worldgen/biome".try_into().unwrap(),
+            entries: ["plains", "desert", "forest", "ocean"]
+                .iter()
+                .map(|name| {
+                    registry_entry(&format!("minecraft:{name}"), Some(Nbt::Compound(vec![])))
+                })
+                .collect(),
+        });
+        self.send(FinishConfigurationPacket);
+        self.recv_skipping::<AcknowledgeFinishConfigurationPacket>();
+        self.send(PlayLoginPacket {
+            entity_id: PLAYER_ENTITY_ID,
+            is_hardcore: false,
+            dimension_names: vec!["minecraft:overworld".try_into().unwrap()],
+            max_players: VarInt(20),
+            view_distance: VarInt(10),
+            simulation_distance: VarInt(10),
+            reduced_debug_info: false,
+            enable_respawn_screen: true,
+            do_limited_crafting: false,
+            dimension_type: VarInt(0),
+            dimension_name: "minecraft:overworld".try_into().unwrap(),
+            hashed_seed: 0,
+            game_mode: 0,
+            previous_game_mode: -1,
+            is_debug: false,
+            is_flat: false,
+            has_death_location: false,
+            death_dimention_name: None,
+            death_location: None,
+            portal_cooldown: VarInt(0),
+            sea_level: VarInt(63),
+            enforced_secure_chat: false,
+        });
+    }
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::FakeServer;
+use minecraft_client::{
+    connection::Connection,
+    cookie::{Cookie, CookieJar, MAX_COOKIE_LEN},
+    datatypes::{GameProfile, Identifier, MinecraftData, UUID},
+    packet::{
+        decode_packet_body, AcknowledgeFinishConfigurationPacket, ConfigurationCookieRequestPacket,
+        ConfigurationCookieResponsePacket, ConfigurationStoreCookiePacket,
+        FinishConfigurationPacket, LoginAcknowledgedPacket, LoginStartPacket, LoginSuccessPacket,
+        PlayStoreCookiePacket,
+    },
+    protocol::ProtocolVersion,
+};
+
+fn cookie(key: &str, payload: &[u8]) -> Cookie {
+    Cookie {
+        key: key.try_into().unwrap(),
+        payload: payload.to_vec(),
+    }
+}
+
+#[test]
+fn jar_enforces_cap() {
+    let mut jar = CookieJar::new();
+    jar.insert(cookie("a:b", &[0; MAX_COOKIE_LEN])).unwrap();
+    assert!(jar.insert(cookie("a:c", &[0; MAX_COOKIE_LEN + 1])).is_err());
+    assert_eq!(jar.len(), 1);
+    jar.insert(cookie("a:b", b"new")).unwrap();
+    let key: Identifier = "a:b".try_into().unwrap();
+    assert_eq!(jar.get(&key), Some(&b"new"[..]));
+}
+
+#[test]
+fn oversized_store_cookie_is_rejected_on_decode() {
+    let mut body = Vec::new();
+    ConfigurationStoreCookiePacket {
+        cookie: cookie("a:b", &[1; MAX_COOKIE_LEN + 1]),
+    }
+    .encode(&mut body)
+    .unwrap();
+    assert!(
+        decode_packet_body::<ConfigurationStoreCookiePacket>(&body, ProtocolVersion::LATEST)
+            .is_err()
+    );
+    let mut body = Vec::new();
+    PlayStoreCookiePacket {
+        cookie: cookie("a:b", &[1; MAX_COOKIE_LEN]),
+    }
+    .encode(&mut body)
+    .unwrap();
+    assert!(decode_packet_body::<PlayStoreCookiePacket>(&body, ProtocolVersion::LATEST).is_ok());
+}
+
+#[test]
+fn jar_save_and_load() {
+    let mut jar = CookieJar::new();
+    jar.insert(cookie("lobby:ticket", b"abc")).unwrap();
+    jar.insert(cookie("lobby:empty", b"")).unwrap();
+    let path = std::env::temp_dir().join(format!("cookies-{}.bin", std::process::id()));
+    jar.save(&path).unwrap();
+    let loaded = CookieJar::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, jar);
+}
+
+#[test]
+fn configuration_cookie_requests_are_answered() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        let start = server.recv::<LoginStartPacket>();
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+        server.send(ConfigurationCookieRequestPacket {
+            key: "saved:cookie".try_into().unwrap(),
+        });
+        let resp = server.recv_skipping::<ConfigurationCookieResponsePacket>();
+        assert_eq!(resp.payload.as_deref(), Some(&b"from disk"[..]));
+        server.send(ConfigurationStoreCookiePacket {
+            cookie: cookie("new:cookie", b"hi"),
+        });
+        server.send(ConfigurationCookieRequestPacket {
+            key: "new:cookie".try_into().unwrap(),
+        });
+        let resp = server.recv::<ConfigurationCookieResponsePacket>();
+        assert_eq!(resp.payload.as_deref(), Some(&b"hi"[..]));
+        server.send(FinishConfigurationPacket);
+        server.recv::<AcknowledgeFinishConfigurationPacket>();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    conn.cookies_mut()
+        .insert(cookie("saved:cookie", b"from disk"))
+        .unwrap();
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    server.join().unwrap();
+    assert_eq!(conn.cookies().len(), 2);
+}
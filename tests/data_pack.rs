@@ -0,0 +1,271 @@
+mod common;
+
+use common::FakeServer;
+use minecraft_client::{
+    connection::Connection,
+    data_pack::{DataPackCatalog, LocalDataPack},
+    datatypes::{GameProfile, UUID},
+    nbt::Nbt,
+    packet::{
+        AcknowledgeFinishConfigurationPacket, ClientboundKnownPacksPacket,
+        ConfigurationClientInformationPacket, DataPack, FinishConfigurationPacket,
+        LoginAcknowledgedPacket, LoginStartPacket, LoginSuccessPacket, RegistryDataPacket,
+        RegistryEntry, ServerboundKnownPacksPacket,
+    },
+};
+
+fn pack(namespace: &str, id: &str, version: &str) -> DataPack {
+    DataPack {
+        namespace: namespace.try_into().unwrap(),
+        id: id.try_into().unwrap(),
+        version: version.try_into().unwrap(),
+    }
+}
+
+fn core() -> LocalDataPack {
+    let mut core = LocalDataPack::new(pack("minecraft", "core", "1.21.10"));
+    core.insert(
+        "minecraft:dimension_type".try_into().unwrap(),
+        "minecraft:overworld".try_into().unwrap(),
+        Nbt::Compound(vec![("height".to_string(), Nbt::Int(384))]),
+    );
+    core
+}
+
+#[test]
+fn only_packs_we_have_are_claimed() {
+    let mut catalog = DataPackCatalog::new();
+    catalog.add(core());
+    let offered = [
+        pack("minecraft", "core", "1.21.5"),
+        pack("minecraft", "core", "1.21.10"),
+        pack("example", "extra", "1"),
+    ];
+    assert_eq!(
+        catalog.negotiate(&offered),
+        [pack("minecraft", "core", "1.21.10")]
+    );
+    assert!(catalog
+        .resolve(
+            &offered[..1],
+            "minecraft:dimension_type",
+            "minecraft:overworld"
+        )
+        .is_none());
+    assert!(catalog
+        .resolve(
+            &offered[1..],
+            "minecraft:dimension_type",
+            "minecraft:overworld"
+        )
+        .is_some());
+}
+
+#[test]
+fn save_and_load() {
+    let path = std::env::temp_dir().join(format!("core-{}.bin", std::process::id()));
+    core().save(&path).unwrap();
+    let loaded = LocalDataPack::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, core());
+}
+
+#[test]
+fn entries_without_data_are_filled_from_known_packs() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        let start = server.recv::<LoginStartPacket>();
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+        server.send(ClientboundKnownPacksPacket {
+            known_packs: vec![
+                pack("minecraft", "core", "1.21.10"),
+                pack("example", "extra", "1"),
+            ],
+        });
+        let resp = server.recv_skipping::<ServerboundKnownPacksPacket>();
+        assert_eq!(resp.known_packs, [pack("minecraft", "core", "1.21.10")]);
+        server.send(RegistryDataPacket {
+            registry_id: "minecraft:dimension_type".try_into().unwrap(),
+            entries: vec![
+                RegistryEntry {
+                    id: "minecraft:overworld".try_into().unwrap(),
+                    data: None,
+                },
+                RegistryEntry {
+                    id: "example:flat".try_into().unwrap(),
+                    data: Some(Nbt::Compound(vec![("height".to_string(), Nbt::Int(16))])),
+                },
+            ],
+        });
+        server.send(FinishConfigurationPacket);
+        server.recv::<AcknowledgeFinishConfigurationPacket>();
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.data_packs_mut().add(core());
+    connection.login().unwrap();
+    connection.configure().unwrap();
+    server.join().unwrap();
+
+    let dimensions = connection
+        .registries()
+        .registry("minecraft:dimension_type")
+        .unwrap();
+    let heights: Vec<_> = dimensions
+        .iter()
+        .map(|entry| entry.data.as_ref().unwrap().get("height").unwrap().as_i64())
+        .collect();
+    assert_eq!(heights, [Some(384), Some(16)]);
+}
+
+#[test]
+fn entries_missing_from_known_packs_are_an_error() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        let start = server.recv::<LoginStartPacket>();
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+        server.recv_skipping::<ConfigurationClientInformationPacket>();
+        server.send(RegistryDataPacket {
+            registry_id: "minecraft:dimension_type".try_into().unwrap(),
+            entries: vec![RegistryEntry {
+                id: "minecraft:overworld".try_into().unwrap(),
+                data: None,
+            }],
+        });
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.login().unwrap();
+    let err = connection.configure().unwrap_err();
+    assert!(err.to_string().contains("minecraft:overworld"), "{err}");
+    server.join().unwrap();
+}
+
+/// Sends `registry` the way vanilla does: without data when `minecraft:core` was claimed, and
+/// inline otherwise
+fn send_core_registry(
+    server: &mut FakeServer,
+    claimed: &[DataPack],
+    registry_id: &str,
+    ids: &[&str],
+) {
+    let core_claimed = claimed.contains(&pack("minecraft", "core", "1.21.9"));
+    server.send(RegistryDataPacket {
+        registry_id: registry_id.try_into().unwrap(),
+        entries: ids
+            .iter()
+            .map(|id| RegistryEntry {
+                id: format!("minecraft:{id}").try_into().unwrap(),
+                data: (!core_claimed).then(|| {
+                    Nbt::Compound(vec![(
+                        "message_id".to_string(),
+                        Nbt::String(id.to_string()),
+                    )])
+                }),
+            })
+            .collect(),
+    });
+}
+
+fn vanilla_configuration(mut server: FakeServer) {
+    server.recv_raw();
+    let start = server.recv::<LoginStartPacket>();
+    server.send(LoginSuccessPacket {
+        client: GameProfile {
+            uuid: UUID(1),
+            username: start.name,
+            properties: vec![],
+        },
+    });
+    server.recv::<LoginAcknowledgedPacket>();
+    server.send(ClientboundKnownPacksPacket {
+        known_packs: vec![pack("minecraft", "core", "1.21.9")],
+    });
+    let claimed = server
+        .recv_skipping::<ServerboundKnownPacksPacket>()
+        .known_packs;
+    send_core_registry(
+        &mut server,
+        &claimed,
+        "minecraft:dimension_type",
+        &["overworld"],
+    );
+    send_core_registry(
+        &mut server,
+        &claimed,
+        "minecraft:damage_type",
+        &["arrow", "fall"],
+    );
+    send_core_registry(
+        &mut server,
+        &claimed,
+        "minecraft:painting_variant",
+        &["kebab"],
+    );
+    server.send(FinishConfigurationPacket);
+    server.recv::<AcknowledgeFinishConfigurationPacket>();
+}
+
+#[test]
+fn vanilla_servers_configure_with_the_default_catalog() {
+    let (port, server) = FakeServer::spawn(vanilla_configuration);
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.login().unwrap();
+    connection.configure().unwrap();
+    server.join().unwrap();
+
+    let registries = connection.registries();
+    assert!(registries.known_packs().is_empty());
+    let damage_types = registries.registry("minecraft:damage_type").unwrap();
+    assert_eq!(damage_types.len(), 2);
+    assert!(damage_types.iter().all(|entry| entry.data.is_some()));
+}
+
+#[test]
+fn captured_core_is_claimed_next_session() {
+    let (port, server) = FakeServer::spawn_each(vec![
+        Box::new(vanilla_configuration),
+        Box::new(vanilla_configuration),
+    ]);
+
+    let mut first = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    first.login().unwrap();
+    first.configure().unwrap();
+    let core =
+        LocalDataPack::from_registries(pack("minecraft", "core", "1.21.9"), first.registries());
+    drop(first);
+
+    let mut second = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    second.data_packs_mut().add(core);
+    second.login().unwrap();
+    second.configure().unwrap();
+    server.join().unwrap();
+
+    let registries = second.registries();
+    assert_eq!(
+        registries.known_packs(),
+        [pack("minecraft", "core", "1.21.9")]
+    );
+    let painting = registries
+        .entry("minecraft:painting_variant", 0)
+        .unwrap()
+        .data
+        .as_ref()
+        .unwrap();
+    assert_eq!(painting.get("message_id").unwrap().as_str(), Some("kebab"));
+}
@@ -0,0 +1,139 @@
+use minecraft_client::datatypes::{
+    Angle, BitSet, FixedBitSet, LpVec3, MinecraftData, VarInt, VarLong, Vec3d,
+};
+
+fn encode<T: MinecraftData>(value: T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).unwrap();
+    bytes
+}
+
+fn decode<T: MinecraftData>(bytes: &[u8]) -> T {
+    let mut reader = bytes;
+    let value = T::decode(&mut reader).unwrap();
+    assert!(reader.is_empty(), "trailing bytes after decoding");
+    value
+}
+
+#[test]
+fn varlong_matches_reference_encodings() {
+    let cases: &[(i64, &[u8])] = &[
+        (0, &[0x00]),
+        (127, &[0x7f]),
+        (128, &[0x80, 0x01]),
+        (2147483647, &[0xff, 0xff, 0xff, 0xff, 0x07]),
+        (
+            i64::MAX,
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+        ),
+        (
+            -1,
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+        ),
+        (
+            i64::MIN,
+            &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01],
+        ),
+    ];
+    for &(value, bytes) in cases {
+        assert_eq!(encode(VarLong(value)), bytes);
+        assert_eq!(VarLong(value).num_bytes(), bytes.len());
+        assert_eq!(decode::<VarLong>(bytes), VarLong(value));
+    }
+}
+
+#[test]
+fn bit_sets() {
+    let mut bits = BitSet::default();
+    bits.set(3, true);
+    bits.set(70, true);
+    assert!(bits.get(3) && bits.get(70) && !bits.get(4) && !bits.get(1000));
+    assert_eq!(bits.iter_ones().collect::<Vec<_>>(), [3, 70]);
+    let bytes = encode(bits.clone());
+    assert_eq!(bytes.len(), bits.num_bytes());
+    assert_eq!(bytes[0], 2);
+    assert_eq!(decode::<BitSet>(&bytes), bits);
+    assert_eq!(bits.bit_len(), 128);
+
+    // clearing every bit leaves the storage, so the set is empty but still has room
+    bits.set(3, false);
+    bits.set(70, false);
+    assert!(bits.is_empty());
+    assert_eq!(bits.bit_len(), 128);
+    assert!(BitSet::default().is_empty());
+
+    let mut fixed = FixedBitSet::<20>::new();
+    fixed.set(0, true);
+    fixed.set(9, true);
+    fixed.set(19, true);
+    assert_eq!(
+        encode(fixed.clone()),
+        [0b0000_0001, 0b0000_0010, 0b0000_1000]
+    );
+    assert_eq!(decode::<FixedBitSet<20>>(&[1, 2, 8]), fixed);
+}
+
+#[test]
+fn angles() {
+    assert_eq!(Angle::from_degrees(90.0), Angle(64));
+    assert_eq!(Angle::from_degrees(-90.0), Angle(192));
+    assert_eq!(Angle(128).to_degrees(), 180.0);
+}
+
+#[test]
+fn lp_vec3_zero_is_one_byte() {
+    assert_eq!(encode(LpVec3::default()), [0]);
+    assert_eq!(decode::<LpVec3>(&[0]), LpVec3::default());
+}
+
+#[test]
+fn lp_vec3_round_trip() {
+    for vec in [
+        Vec3d {
+            x: 0.5,
+            y: -0.0784,
+            z: 0.25,
+        },
+        Vec3d {
+            x: -3.9,
+            y: 1.0,
+            z: 0.0,
+        },
+        Vec3d {
+            x: 12.0,
+            y: -100.5,
+            z: 40.25,
+        },
+    ] {
+        let bytes = encode(LpVec3(vec));
+        assert_eq!(bytes.len(), LpVec3(vec).num_bytes());
+        let decoded = Vec3d::from(decode::<LpVec3>(&bytes));
+        let scale = vec.x.abs().max(vec.y.abs()).max(vec.z.abs()).ceil();
+        let tolerance = scale / 32766.0 * 2.0;
+        assert!(
+            (decoded.x - vec.x).abs() <= tolerance,
+            "{decoded:?} vs {vec:?}"
+        );
+        assert!(
+            (decoded.y - vec.y).abs() <= tolerance,
+            "{decoded:?} vs {vec:?}"
+        );
+        assert!(
+            (decoded.z - vec.z).abs() <= tolerance,
+            "{decoded:?} vs {vec:?}"
+        );
+    }
+}
+
+#[test]
+fn lp_vec3_large_scale_uses_continuation() {
+    let vec = Vec3d {
+        x: 12.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    let bytes = encode(LpVec3(vec));
+    assert_eq!(bytes.len(), 7);
+    assert_eq!(bytes[0] & 4, 4);
+    assert_eq!(decode::<VarInt>(&bytes[6..]), VarInt(3));
+}
@@ -0,0 +1,317 @@
+mod common;
+
+use common::{flat_chunk, play_until_ended, FakeServer, PLAYER_ENTITY_ID};
+use minecraft_client::{
+    connection::Connection,
+    datatypes::{Angle, LpVec3, MinecraftData, VarInt, Vec3d, UUID},
+    entity::{
+        entity_type_id, entity_types, Entities, EntityMetadata, MetadataValue, ENTITY_TYPES_VERSION,
+    },
+    nbt::Nbt,
+    packet::{
+        decode_packet_body, RemoveEntitiesPacket, SetEntityMetadataPacket, SetEntityVelocityPacket,
+        SetHeadRotationPacket, SetPlayerPositionPacket, ShortVelocity, SpawnEntityPacket,
+        SynchronizePlayerPositionPacket, TeleportEntityPacket, TeleportFlags,
+        UpdateEntityPositionAndRotationPacket, UpdateEntityPositionPacket,
+    },
+    protocol::ProtocolVersion,
+};
+
+fn vec(x: f64, y: f64, z: f64) -> Vec3d {
+    Vec3d { x, y, z }
+}
+
+fn spawn(entity_id: i32, kind: i32, position: Vec3d) -> SpawnEntityPacket {
+    SpawnEntityPacket {
+        entity_id: VarInt(entity_id),
+        uuid: UUID(entity_id as u128),
+        kind: VarInt(kind),
+        position,
+        velocity: Some(LpVec3(Vec3d::default())),
+        pitch: Angle(0),
+        yaw: Angle::from_degrees(90.0),
+        head_yaw: Angle::from_degrees(90.0),
+        data: VarInt(0),
+        short_velocity: None,
+    }
+}
+
+fn metadata(entity_id: i32, entries: Vec<(u8, MetadataValue)>) -> SetEntityMetadataPacket {
+    SetEntityMetadataPacket {
+        entity_id: VarInt(entity_id),
+        metadata: EntityMetadata {
+            entries,
+            skipped: None,
+        },
+    }
+}
+
+#[test]
+fn metadata_round_trips() {
+    let metadata = EntityMetadata {
+        entries: vec![
+            (0, MetadataValue::Byte(0x02)),
+            (1, MetadataValue::VarInt(300)),
+            (
+                2,
+                MetadataValue::OptionalText(Some(Nbt::String("Bessie".to_string()))),
+            ),
+            (3, MetadataValue::Boolean(true)),
+            (6, MetadataValue::Pose(5)),
+            (9, MetadataValue::Float(10.0)),
+            (15, MetadataValue::OptionalBlockState(None)),
+            (16, MetadataValue::OptionalVarInt(Some(0))),
+            (
+                17,
+                MetadataValue::VillagerData {
+                    kind: 1,
+                    profession: 2,
+                    level: 3,
+                },
+            ),
+        ],
+        skipped: None,
+    };
+    let mut bytes = Vec::new();
+    metadata.clone().encode(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), metadata.num_bytes());
+    assert_eq!(*bytes.last().unwrap(), 0xFF);
+    // an absent optional varint is 0, so present ones are one more than their value
+    assert_eq!(&bytes[bytes.len() - 9..bytes.len() - 6], &[16, 19, 1]);
+    assert_eq!(
+        EntityMetadata::decode(&mut bytes.as_slice()).unwrap(),
+        metadata
+    );
+}
+
+#[test]
+fn undecoded_metadata_types_end_the_entries() {
+    // a float, then an item stack, whose encoding this client does not know
+    let mut bytes = vec![0x05];
+    bytes.extend([9, 3]);
+    bytes.extend(2.5f32.to_be_bytes());
+    bytes.extend([8, 7, 1, 0x9A, 0x07, 0]);
+    bytes.extend([10, 8, 1, 0xFF]);
+    let packet: SetEntityMetadataPacket =
+        decode_packet_body(&bytes, ProtocolVersion::LATEST).unwrap();
+    assert_eq!(packet.entity_id.0, 5);
+    assert_eq!(packet.metadata.entries, [(9, MetadataValue::Float(2.5))]);
+    assert_eq!(packet.metadata.skipped, Some(7));
+    assert!(packet.metadata.encode(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn velocities_before_1_21_9_are_shorts() {
+    let packet = SpawnEntityPacket {
+        velocity: None,
+        short_velocity: Some(ShortVelocity::from(vec(0.5, -0.25, 10.0))),
+        ..spawn(3, 1, vec(0.5, 64.0, 0.5))
+    };
+    let mut bytes = Vec::new();
+    ProtocolVersion::V1_21_5
+        .encode(packet.clone(), &mut bytes)
+        .unwrap();
+    // the velocity follows the data, rather than preceding the angles
+    assert_eq!(&bytes[bytes.len() - 7..bytes.len() - 6], &[0]);
+    let decoded: SpawnEntityPacket = decode_packet_body(&bytes, ProtocolVersion::V1_21_5).unwrap();
+    assert!(decoded.velocity.is_none());
+    assert_eq!(decoded.velocity(), vec(0.5, -0.25, 3.9));
+    assert_eq!(decoded.yaw, Angle::from_degrees(90.0));
+
+    let velocity: SetEntityVelocityPacket =
+        decode_packet_body(&[3, 0x0F, 0xA0, 0, 0, 0xF0, 0x60], ProtocolVersion::V1_21_5).unwrap();
+    assert_eq!(velocity.velocity(), vec(0.5, 0.0, -0.5));
+}
+
+#[test]
+fn the_store_follows_entity_packets() {
+    let mut entities = Entities::new();
+    entities.reset(PLAYER_ENTITY_ID);
+    let player = entities.local_player().unwrap();
+    assert_eq!(player.id, PLAYER_ENTITY_ID);
+    assert_eq!(player.kind, None);
+    assert_eq!(player.type_name, Some("player"));
+
+    let cow = entity_type_id("cow").unwrap();
+    let cow = entities.spawn(spawn(20, cow, vec(10.5, 64.0, -3.5)), ENTITY_TYPES_VERSION);
+    assert_eq!(cow.type_name, Some("cow"));
+    assert_eq!(cow.yaw, 90.0);
+    // past the end of the table the type is only known by id
+    let unknown = entities.spawn(spawn(21, 999, vec(0.0, 64.0, 0.0)), ENTITY_TYPES_VERSION);
+    assert_eq!(unknown.kind, Some(999));
+    assert!(unknown.type_name.is_none());
+    assert_eq!(entities.len(), 3);
+
+    assert!(entities.update_position(&UpdateEntityPositionPacket {
+        entity_id: VarInt(20),
+        delta_x: 4096,
+        delta_y: -2048,
+        delta_z: 1024,
+        on_ground: true,
+    }));
+    let cow = entities.get(20).unwrap();
+    assert_eq!(cow.position, vec(11.5, 63.5, -3.25));
+    assert!(cow.on_ground);
+
+    assert!(
+        entities.update_position_and_rotation(&UpdateEntityPositionAndRotationPacket {
+            entity_id: VarInt(20),
+            delta_x: -4096,
+            delta_y: 0,
+            delta_z: 0,
+            yaw: Angle::from_degrees(180.0),
+            pitch: Angle::from_degrees(45.0),
+            on_ground: false,
+        })
+    );
+    assert!(entities.set_head_rotation(&SetHeadRotationPacket {
+        entity_id: VarInt(20),
+        head_yaw: Angle::from_degrees(270.0),
+    }));
+    let cow = entities.get(20).unwrap();
+    assert_eq!(cow.position.x, 10.5);
+    assert_eq!((cow.yaw, cow.pitch, cow.head_yaw), (180.0, 45.0, 270.0));
+
+    assert!(entities.teleport(&TeleportEntityPacket {
+        entity_id: VarInt(20),
+        position: vec(100.0, 70.0, 100.0),
+        velocity: vec(0.0, -0.08, 0.0),
+        yaw: 12.5,
+        pitch: 0.0,
+        on_ground: false,
+    }));
+    assert!(entities.set_velocity(&SetEntityVelocityPacket {
+        entity_id: VarInt(20),
+        velocity: Some(LpVec3(vec(0.0, 0.5, 0.0))),
+        short_velocity: None,
+    }));
+    let cow = entities.get(20).unwrap();
+    assert_eq!(cow.position, vec(100.0, 70.0, 100.0));
+    assert_eq!(cow.yaw, 12.5);
+    assert!((cow.velocity.y - 0.5).abs() < 1e-3);
+
+    // metadata only carries what changed
+    assert!(entities.set_metadata(metadata(
+        20,
+        vec![(0, MetadataValue::Byte(0)), (1, MetadataValue::VarInt(300))]
+    )));
+    assert!(entities.set_metadata(metadata(20, vec![(0, MetadataValue::Byte(0x20))])));
+    let cow = entities.get(20).unwrap();
+    assert_eq!(cow.metadata[&0], MetadataValue::Byte(0x20));
+    assert_eq!(cow.metadata[&1], MetadataValue::VarInt(300));
+
+    // packets for entities we never saw spawn are ignored
+    assert!(!entities.set_head_rotation(&SetHeadRotationPacket {
+        entity_id: VarInt(404),
+        head_yaw: Angle(0),
+    }));
+
+    // our own player is never removed
+    let removed = entities.remove(&RemoveEntitiesPacket {
+        entity_ids: vec![VarInt(20), VarInt(404), VarInt(PLAYER_ENTITY_ID)],
+    });
+    assert_eq!(removed, 1);
+    assert!(entities.get(20).is_none());
+    assert!(entities.local_player().is_some());
+
+    // a respawn keeps only our player, and a new login replaces it
+    entities.clear();
+    assert_eq!(entities.len(), 1);
+    assert_eq!(entities.local_player_id(), Some(PLAYER_ENTITY_ID));
+    entities.reset(8);
+    assert_eq!(entities.len(), 1);
+    assert_eq!(entities.local_player().unwrap().id, 8);
+}
+
+#[test]
+fn the_play_loop_tracks_entities() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                server.send(flat_chunk(x, z));
+            }
+        }
+        server.send(SynchronizePlayerPositionPacket {
+            teleport_id: VarInt(1),
+            position: vec(8.5, 0.0, 8.5),
+            velocity: Vec3d::default(),
+            yaw: 0.0,
+            pitch: 0.0,
+            flags: TeleportFlags(0),
+        });
+        server.send(spawn(
+            20,
+            entity_type_id("pig").unwrap(),
+            vec(4.5, 0.0, 4.5),
+        ));
+        server.send(spawn(
+            21,
+            entity_type_id("cow").unwrap(),
+            vec(-4.5, 0.0, 4.5),
+        ));
+        server.send(UpdateEntityPositionPacket {
+            entity_id: VarInt(20),
+            delta_x: 8192,
+            delta_y: 0,
+            delta_z: 0,
+            on_ground: true,
+        });
+        server.send(metadata(20, vec![(2, MetadataValue::OptionalText(None))]));
+        server.send(RemoveEntitiesPacket {
+            entity_ids: vec![VarInt(21)],
+        });
+
+        // knockback on our own player throws it up
+        server.recv_skipping::<SetPlayerPositionPacket>();
+        server.send(SetEntityVelocityPacket {
+            entity_id: VarInt(PLAYER_ENTITY_ID),
+            velocity: Some(LpVec3(vec(0.0, 0.4, 0.0))),
+            short_velocity: None,
+        });
+        while server.recv_skipping::<SetPlayerPositionPacket>().position.y <= 0.0 {}
+        server.end_play();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    assert!(conn.entities().is_empty());
+    play_until_ended(&mut conn);
+    server.join().unwrap();
+
+    let entities = conn.entities();
+    assert_eq!(entities.len(), 2);
+    let pig = entities.get(20).unwrap();
+    assert_eq!(pig.kind, entity_type_id("pig"));
+    // resolved from the bundled table, as vanilla never sends the entity type registry
+    assert_eq!(pig.type_name, Some("pig"));
+    assert_eq!(pig.position, vec(6.5, 0.0, 4.5));
+    assert_eq!(pig.metadata[&2], MetadataValue::OptionalText(None));
+
+    assert_eq!(conn.entity_id(), Some(PLAYER_ENTITY_ID));
+    let player = conn.player().unwrap();
+    let local = entities.local_player().unwrap();
+    assert_eq!(local.position, player.position);
+    assert!(local.position.y > 0.0);
+}
+
+#[test]
+fn entity_types_are_bundled() {
+    let types = entity_types();
+    assert_eq!(types.len(), 153);
+    assert_eq!(types[0], "acacia_boat");
+    assert_eq!(entity_type_id("zombie"), Some(147));
+    assert_eq!(entity_type_id("player"), Some(151));
+    assert_eq!(types[152], "fishing_bobber");
+    assert_eq!(entity_type_id("minecraft:zombie"), None);
+
+    // other versions number types differently, so their names are not guessed
+    let mut entities = Entities::new();
+    let zombie = entities.spawn(
+        spawn(20, 147, vec(0.0, 64.0, 0.0)),
+        ProtocolVersion::V1_21_5,
+    );
+    assert_eq!(zombie.kind, Some(147));
+    assert!(zombie.type_name.is_none());
+}
@@ -0,0 +1,76 @@
+//! Uses `#[derive(MinecraftData)]` from outside of `minecraft_client`, the way a downstream crate
+//! defining its own plugin channel payloads would.
+
+use minecraft_client::datatypes::{Identifier, MString, MinecraftData, VarInt};
+
+#[derive(Debug, Clone, PartialEq, MinecraftData)]
+struct PluginPayload {
+    channel: Identifier,
+    version: VarInt,
+    has_motd: bool,
+    #[present_if(has_motd)]
+    motd: Option<MString<256>>,
+    kind: PayloadKind,
+}
+
+#[derive(Debug, Clone, PartialEq, MinecraftData)]
+enum PayloadKind {
+    #[mc_repr(VarInt(0))]
+    Ping,
+    #[mc_repr(VarInt(1))]
+    Pong { nonce: i64 },
+    #[mc_repr(VarInt(2))]
+    Tagged(u8, u16),
+}
+
+mod reexport {
+    pub use minecraft_client as mc;
+}
+
+#[derive(Debug, Clone, PartialEq, MinecraftData)]
+#[mc(crate = crate::reexport::mc)]
+struct Renamed(i32, bool);
+
+fn round_trip<T: MinecraftData + Clone + PartialEq>(value: T) {
+    let mut bytes = Vec::new();
+    value.clone().encode(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), value.num_bytes());
+    let decoded = T::decode(&mut bytes.as_slice()).unwrap();
+    assert_eq!(decoded, value);
+}
+
+#[test]
+fn struct_round_trip() {
+    round_trip(PluginPayload {
+        channel: Identifier::try_from("example:payload".to_owned()).unwrap(),
+        version: VarInt(300),
+        has_motd: true,
+        motd: Some(MString::try_from("hello".to_owned()).unwrap()),
+        kind: PayloadKind::Pong { nonce: -7 },
+    });
+    round_trip(PluginPayload {
+        channel: Identifier::try_from("example:payload".to_owned()).unwrap(),
+        version: VarInt(0),
+        has_motd: false,
+        motd: None,
+        kind: PayloadKind::Ping,
+    });
+}
+
+#[test]
+fn enum_round_trip() {
+    round_trip(PayloadKind::Ping);
+    round_trip(PayloadKind::Pong { nonce: i64::MAX });
+    round_trip(PayloadKind::Tagged(3, 0xBEEF));
+}
+
+#[test]
+fn enum_rejects_unknown_repr() {
+    let err = PayloadKind::decode(&mut [0x05u8].as_slice()).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid PayloadKind");
+}
+
+#[test]
+fn crate_path_override() {
+    round_trip(Renamed(-1, true));
+}
@@ -0,0 +1,190 @@
+use std::io::{Cursor, Read};
+
+use minecraft_client::{
+    datatypes::{
+        decode_frame, Error, FixedBitSet, IDSet, LengthPrefixed, LpVec3, MinecraftData,
+        MinecraftDataRef, MinecraftRead, VarInt, DEFAULT_MAX_LEN,
+    },
+    packet::{decode_packet_body, decode_packet_header, StatusResponsePacket},
+    protocol::ProtocolVersion,
+};
+
+fn encode<T: MinecraftData>(value: T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).unwrap();
+    bytes
+}
+
+fn decode<T: MinecraftData>(bytes: &[u8]) -> Result<T, Error> {
+    T::decode(&mut &bytes[..])
+}
+
+#[test]
+fn negative_vec_length() {
+    let err = decode::<Vec<u64>>(&encode(VarInt(-5))).unwrap_err();
+    assert!(err.to_string().contains("negative"), "{err}");
+}
+
+#[test]
+fn vec_length_over_default_max() {
+    let err = decode::<Vec<u8>>(&encode(VarInt(i32::MAX))).unwrap_err();
+    assert!(err.to_string().contains("maximum"), "{err}");
+}
+
+#[test]
+fn vec_length_past_end_of_frame_does_not_allocate_it() {
+    // within the default max, but a frame this short cannot hold that many u128s
+    let mut bytes = encode(VarInt(DEFAULT_MAX_LEN as i32));
+    bytes.extend_from_slice(&[0; 32]);
+    assert!(decode::<Vec<u128>>(&bytes).is_err());
+}
+
+#[test]
+fn fixed_size_types_count_their_minimum_bytes() {
+    assert_eq!(<FixedBitSet<20> as MinecraftData>::MIN_BYTES, 3);
+    assert_eq!(<FixedBitSet<20> as MinecraftDataRef>::MIN_BYTES, 3);
+    assert_eq!(<LpVec3 as MinecraftData>::MIN_BYTES, 1);
+    assert_eq!(<LpVec3 as MinecraftDataRef>::MIN_BYTES, 1);
+
+    // 100 of either cannot fit in 32 bytes
+    let mut bytes = encode(VarInt(100));
+    bytes.extend_from_slice(&[0; 32]);
+    for err in [
+        Vec::<FixedBitSet<20>>::decode_ref(&mut &bytes[..]).unwrap_err(),
+        Vec::<LpVec3>::decode_ref(&mut &bytes[..]).unwrap_err(),
+    ] {
+        assert!(err.to_string().contains("bytes left in the frame"), "{err}");
+    }
+}
+
+#[test]
+fn vec_length_past_end_of_frame_is_rejected_up_front() {
+    // a million u64s claimed with 32 bytes left
+    let mut bytes = encode(VarInt(1_000_000));
+    bytes.extend_from_slice(&[0; 32]);
+    let err = decode_frame::<Vec<u64>>(&mut &bytes[..], ProtocolVersion::LATEST).unwrap_err();
+    assert!(err.to_string().contains("bytes left in the frame"), "{err}");
+    let err = Vec::<u64>::decode_ref(&mut &bytes[..]).unwrap_err();
+    assert!(err.to_string().contains("bytes left in the frame"), "{err}");
+
+    // exactly as many as fit is fine, and nested lengths are checked against what is left
+    let mut bytes = encode(VarInt(4));
+    bytes.extend_from_slice(&[0; 32]);
+    let mut body = &bytes[..];
+    assert_eq!(
+        decode_frame::<Vec<u64>>(&mut body, ProtocolVersion::LATEST).unwrap(),
+        [0; 4]
+    );
+    assert!(body.is_empty());
+    let nested = [2, 1, 7, 5, 1, 2, 3];
+    let err = decode_frame::<Vec<Vec<u8>>>(&mut &nested[..], ProtocolVersion::LATEST).unwrap_err();
+    assert!(err.to_string().contains("length 5"), "{err}");
+    let nested = [2, 1, 7, 3, 1, 2, 3];
+    assert_eq!(
+        decode_frame::<Vec<Vec<u8>>>(&mut &nested[..], ProtocolVersion::LATEST).unwrap(),
+        [vec![7], vec![1, 2, 3]]
+    );
+}
+
+/// A wrapper that counts what is read through it, the way a caller might hold the frame
+struct Counting<R> {
+    inner: R,
+    read: usize,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read += read;
+        Ok(read)
+    }
+}
+
+impl<R: MinecraftRead> MinecraftRead for Counting<R> {
+    fn remaining(&self) -> Option<usize> {
+        self.inner.remaining()
+    }
+}
+
+#[test]
+fn vec_length_past_end_is_rejected_through_wrapped_readers() {
+    let mut bytes = encode(VarInt(1_000_000));
+    bytes.extend_from_slice(&[0; 32]);
+    let check = |err: Error| assert!(err.to_string().contains("bytes left"), "{err}");
+
+    let mut body = &bytes[..];
+    check(Vec::<u64>::decode(&mut &mut body).unwrap_err());
+    check(Vec::<u64>::decode(&mut Cursor::new(&bytes)).unwrap_err());
+    check(Vec::<u64>::decode(&mut (&bytes[..]).take(1 << 20)).unwrap_err());
+    let mut counting = Counting {
+        inner: &bytes[..],
+        read: 0,
+    };
+    check(Vec::<u64>::decode(&mut counting).unwrap_err());
+    assert_eq!(counting.read, 3);
+
+    // a limit shorter than the data bounds the length too
+    let mut bytes = encode(VarInt(4));
+    bytes.extend_from_slice(&[0; 32]);
+    check(Vec::<u64>::decode(&mut (&bytes[..]).take(20)).unwrap_err());
+    assert_eq!(
+        Vec::<u64>::decode(&mut Cursor::new(&bytes)).unwrap(),
+        [0; 4]
+    );
+}
+
+#[test]
+fn explicit_max_len() {
+    let bytes = encode(vec![1u8, 2, 3]);
+    assert_eq!(
+        Vec::<u8>::decode_with_max_len(&mut &bytes[..], 3).unwrap(),
+        [1, 2, 3]
+    );
+    assert!(Vec::<u8>::decode_with_max_len(&mut &bytes[..], 2).is_err());
+}
+
+#[test]
+fn id_set_lengths() {
+    assert!(decode::<IDSet>(&encode(VarInt(-1))).is_err());
+    assert!(decode::<IDSet>(&encode(VarInt(i32::MAX))).is_err());
+    let bytes = encode(IDSet::Enumerated(vec![VarInt(1), VarInt(2)]));
+    assert!(IDSet::decode_with_max_len(&mut &bytes[..], 2).is_ok());
+    assert!(IDSet::decode_with_max_len(&mut &bytes[..], 1).is_err());
+}
+
+#[derive(Debug, MinecraftData)]
+struct Limited {
+    #[mc(max_len = 2)]
+    names: Vec<u8>,
+    has_more: bool,
+    #[present_if(has_more)]
+    #[mc(max_len = 1)]
+    more: Option<Vec<u16>>,
+}
+
+#[test]
+fn derive_max_len() {
+    assert!(decode::<Limited>(&[2, 7, 8, 1, 1, 0, 9]).is_ok());
+    assert!(decode::<Limited>(&[3, 7, 8, 9, 0]).is_err());
+    assert!(decode::<Limited>(&[0, 1, 2, 0, 9, 0, 9]).is_err());
+    assert!(decode::<Limited>(&[0, 0]).is_ok());
+}
+
+#[test]
+fn packet_header_lengths() {
+    assert!(decode_packet_header(&mut &encode(VarInt(-1))[..]).is_err());
+    assert!(decode_packet_header(&mut &encode(VarInt(0))[..]).is_err());
+    assert!(decode_packet_header(&mut &encode(VarInt(1 << 21))[..]).is_err());
+    let header = decode_packet_header(&mut &[3, 0x00][..]).unwrap();
+    assert_eq!(header.body_len(), 2);
+}
+
+#[test]
+fn trailing_bytes_are_rejected() {
+    let mut body = encode(StatusResponsePacket {
+        json_response: "{}".try_into().unwrap(),
+    });
+    assert!(decode_packet_body::<StatusResponsePacket>(&body, ProtocolVersion::LATEST).is_ok());
+    body.push(0);
+    assert!(decode_packet_body::<StatusResponsePacket>(&body, ProtocolVersion::LATEST).is_err());
+}
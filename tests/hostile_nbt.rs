@@ -0,0 +1,51 @@
+use minecraft_client::{
+    datatypes::{decode_frame, MinecraftData, MinecraftDataRef},
+    nbt::{Nbt, NbtRef},
+    protocol::ProtocolVersion,
+};
+
+/// A list tag claiming `len` elements of `elem_type`, with nothing after the header
+fn list_header(elem_type: u8, len: i32) -> Vec<u8> {
+    let mut bytes = vec![9, elem_type];
+    bytes.extend(len.to_be_bytes());
+    bytes
+}
+
+#[test]
+fn lists_of_end_tags_must_be_empty() {
+    let bytes = list_header(0, i32::MAX);
+    let err = Nbt::decode(&mut &bytes[..]).unwrap_err();
+    assert!(err.to_string().contains("TAG_End"), "{err}");
+    let err = NbtRef::decode_ref(&mut &bytes[..]).unwrap_err();
+    assert!(err.to_string().contains("TAG_End"), "{err}");
+
+    // the empty list is how vanilla writes a list with no elements
+    let bytes = list_header(0, 0);
+    assert_eq!(Nbt::decode(&mut &bytes[..]).unwrap(), Nbt::List(vec![]));
+    assert!(NbtRef::decode_ref(&mut &bytes[..]).is_ok());
+}
+
+#[test]
+fn list_and_array_lengths_are_bounded_by_the_frame() {
+    // a billion compounds, ints, nested lists and longs in a frame of a few bytes
+    for (elem_type, len) in [(10, 1 << 30), (3, 1 << 28), (9, 1 << 28)] {
+        let mut bytes = list_header(elem_type, len);
+        bytes.extend([0; 8]);
+        let err = decode_frame::<Nbt>(&mut &bytes[..], ProtocolVersion::LATEST).unwrap_err();
+        assert!(err.to_string().contains("bytes left in the frame"), "{err}");
+        let err = NbtRef::decode_ref(&mut &bytes[..]).unwrap_err();
+        assert!(err.to_string().contains("bytes left in the frame"), "{err}");
+    }
+    let mut bytes = vec![12];
+    bytes.extend((1i32 << 28).to_be_bytes());
+    let err = decode_frame::<Nbt>(&mut &bytes[..], ProtocolVersion::LATEST).unwrap_err();
+    assert!(err.to_string().contains("bytes left in the frame"), "{err}");
+
+    // lists that do fit still decode
+    let mut bytes = list_header(3, 2);
+    bytes.extend([0, 0, 0, 1, 0, 0, 0, 2]);
+    assert_eq!(
+        decode_frame::<Nbt>(&mut &bytes[..], ProtocolVersion::LATEST).unwrap(),
+        Nbt::List(vec![Nbt::Int(1), Nbt::Int(2)])
+    );
+}
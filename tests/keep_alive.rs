@@ -0,0 +1,152 @@
+mod common;
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::FakeServer;
+use minecraft_client::{
+    connection::{Connection, Timeouts},
+    datatypes::{GameProfile, UUID},
+    packet::{
+        AcknowledgeFinishConfigurationPacket, ClientboundPlayKeepAlivePacket,
+        ConfigurationKeepAlivePacket, ConfigurationUpdateTagsPacket, FinishConfigurationPacket,
+        LoginAcknowledgedPacket, LoginStartPacket, LoginSuccessPacket,
+        ServerboundPlayKeepAlivePacket,
+    },
+};
+
+fn login(server: &mut FakeServer) {
+    server.recv_raw();
+    let start = server.recv::<LoginStartPacket>();
+    server.send(LoginSuccessPacket {
+        client: GameProfile {
+            uuid: UUID(1),
+            username: start.name,
+            properties: vec![],
+        },
+    });
+    server.recv::<LoginAcknowledgedPacket>();
+}
+
+#[test]
+fn watchdog_fires_when_keep_alives_stop() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        login(&mut server);
+        server.send(ConfigurationKeepAlivePacket { keep_alive_id: 1 });
+        server.recv_skipping::<ConfigurationKeepAlivePacket>();
+        server.send(FinishConfigurationPacket);
+        server.recv::<AcknowledgeFinishConfigurationPacket>();
+        for id in 2..4 {
+            thread::sleep(Duration::from_millis(100));
+            server.send(ClientboundPlayKeepAlivePacket { keep_alive_id: id });
+            assert_eq!(
+                server
+                    .recv::<ServerboundPlayKeepAlivePacket>()
+                    .keep_alive_id,
+                id
+            );
+        }
+        // go quiet but keep the connection open
+        thread::sleep(Duration::from_secs(2));
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection
+        .set_timeouts(Timeouts {
+            keep_alive: Some(Duration::from_millis(400)),
+            ..Timeouts::default()
+        })
+        .unwrap();
+    connection.login().unwrap();
+    connection.configure().unwrap();
+    let started = Instant::now();
+    let err = connection.play().unwrap_err();
+    assert!(err.to_string().contains("keep-alive"), "{err}");
+    // two keep-alives 100ms apart, then 400ms of silence
+    assert!(started.elapsed() >= Duration::from_millis(500));
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    let metrics = connection.keep_alive_metrics();
+    assert_eq!(metrics.count, 3);
+    assert!(metrics.interval.unwrap() >= Duration::from_millis(90));
+    assert!(metrics.answer_delay.unwrap() < Duration::from_millis(100));
+    server.join().unwrap();
+}
+
+#[test]
+fn watchdog_fires_while_other_packets_keep_arriving() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        login(&mut server);
+        // never a keep-alive, but never a quiet moment either
+        for _ in 0..40 {
+            server.send(ConfigurationUpdateTagsPacket {
+                tagged_registries: vec![],
+            });
+            thread::sleep(Duration::from_millis(25));
+        }
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection
+        .set_timeouts(Timeouts {
+            keep_alive: Some(Duration::from_millis(300)),
+            ..Timeouts::default()
+        })
+        .unwrap();
+    connection.login().unwrap();
+    let started = Instant::now();
+    let err = connection.configure().unwrap_err();
+    assert!(err.to_string().contains("keep-alive"), "{err}");
+    assert!(started.elapsed() < Duration::from_millis(800));
+    server.join().unwrap();
+}
+
+#[test]
+fn read_timeout_applies_outside_the_watchdog() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        server.recv::<LoginStartPacket>();
+        thread::sleep(Duration::from_secs(1));
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection
+        .set_timeouts(Timeouts {
+            read: Some(Duration::from_millis(200)),
+            ..Timeouts::default()
+        })
+        .unwrap();
+    let started = Instant::now();
+    let err = connection.login().unwrap_err();
+    assert!(err.to_string().contains("timed out"), "{err}");
+    assert!(started.elapsed() < Duration::from_secs(1));
+    server.join().unwrap();
+}
+
+#[test]
+fn watchdog_fires_when_the_server_stalls_mid_frame() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        login(&mut server);
+        // half a header, then half a body, then nothing
+        server.send_raw(&[0x80]);
+        thread::sleep(Duration::from_millis(100));
+        server.send_raw(&[0x01, 0x0D, 0x00]);
+        thread::sleep(Duration::from_secs(2));
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection
+        .set_timeouts(Timeouts {
+            keep_alive: Some(Duration::from_millis(300)),
+            ..Timeouts::default()
+        })
+        .unwrap();
+    connection.login().unwrap();
+    let started = Instant::now();
+    let err = connection.configure().unwrap_err();
+    assert!(err.to_string().contains("keep-alive"), "{err}");
+    assert!(started.elapsed() < Duration::from_secs(1));
+    server.join().unwrap();
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::FakeServer;
+use minecraft_client::{
+    connection::Connection,
+    datatypes::{GameProfile, Identifier, MinecraftData, RemainingBytes, VarInt, UUID},
+    packet::{
+        decode_packet_body, LoginAcknowledgedPacket, LoginPluginRequestPacket,
+        LoginPluginResponsePacket, LoginStartPacket, LoginSuccessPacket, MAX_LOGIN_PLUGIN_LEN,
+    },
+    protocol::ProtocolVersion,
+};
+
+fn request(message_id: i32, channel: &str, data: &[u8]) -> LoginPluginRequestPacket {
+    LoginPluginRequestPacket {
+        message_id: VarInt(message_id),
+        channel: channel.try_into().unwrap(),
+        data: RemainingBytes(data.to_vec()),
+    }
+}
+
+#[test]
+fn request_payload_runs_to_end_of_packet() {
+    let mut body = Vec::new();
+    request(7, "velocity:player_info", &[1, 2, 3])
+        .encode(&mut body)
+        .unwrap();
+    let decoded =
+        decode_packet_body::<LoginPluginRequestPacket>(&body, ProtocolVersion::LATEST).unwrap();
+    assert_eq!(decoded.message_id, VarInt(7));
+    assert_eq!(decoded.data.0, [1, 2, 3]);
+
+    assert!(request(7, "a:b", &vec![0; MAX_LOGIN_PLUGIN_LEN + 1])
+        .encode(&mut Vec::new())
+        .is_err());
+    let mut body = Vec::new();
+    VarInt(7).encode(&mut body).unwrap();
+    Identifier::try_from("a:b")
+        .unwrap()
+        .encode(&mut body)
+        .unwrap();
+    body.extend(vec![0; MAX_LOGIN_PLUGIN_LEN + 1]);
+    assert!(
+        decode_packet_body::<LoginPluginRequestPacket>(&body, ProtocolVersion::LATEST).is_err()
+    );
+}
+
+#[test]
+fn requests_are_answered_by_registered_handlers() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        let start = server.recv::<LoginStartPacket>();
+
+        server.send(request(1, "velocity:player_info", &[4]));
+        let resp = server.recv::<LoginPluginResponsePacket>();
+        assert_eq!(resp.message_id, VarInt(1));
+        assert_eq!(resp.data.unwrap().0, [4, 4]);
+
+        server.send(request(2, "fml:handshake", b"probe"));
+        let resp = server.recv::<LoginPluginResponsePacket>();
+        assert_eq!(resp.message_id, VarInt(2));
+        assert!(resp.data.is_none());
+
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.register_login_plugin_handler(
+        "velocity:player_info".try_into().unwrap(),
+        |data: &[u8]| Some([data, data].concat()),
+    );
+    connection.login().unwrap();
+    server.join().unwrap();
+}
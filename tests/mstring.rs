@@ -0,0 +1,86 @@
+use minecraft_client::datatypes::{MString, MinecraftData, VarInt};
+
+fn encode<T: MinecraftData>(value: T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).unwrap();
+    bytes
+}
+
+/// Builds the wire form of `s` without going through `MString` validation
+fn raw(s: &str) -> Vec<u8> {
+    let mut bytes = encode(VarInt(s.len() as i32));
+    bytes.extend_from_slice(s.as_bytes());
+    bytes
+}
+
+fn decode<const N: usize>(bytes: &[u8]) -> Result<MString<N>, minecraft_client::datatypes::Error> {
+    MString::<N>::decode(&mut &bytes[..])
+}
+
+#[test]
+fn ascii_at_limit() {
+    let s = "abcdefghijklmnop";
+    assert_eq!(decode::<16>(&raw(s)).unwrap().as_str(), s);
+    assert!(decode::<15>(&raw(s)).is_err());
+    assert!(MString::<16>::try_from(s).is_ok());
+    assert!(MString::<15>::try_from(s).is_err());
+}
+
+#[test]
+fn two_byte_characters() {
+    // 16 code units, 32 bytes
+    let s = "éééééééééééééééé";
+    assert_eq!(s.len(), 32);
+    let decoded = decode::<16>(&raw(s)).unwrap();
+    assert_eq!(decoded.as_str(), s);
+    assert_eq!(encode(decoded), raw(s));
+    assert!(decode::<15>(&raw(s)).is_err());
+}
+
+#[test]
+fn three_byte_characters() {
+    // 3 code units, 9 bytes: exactly the 3N byte limit
+    let s = "日本語";
+    assert_eq!(s.len(), 9);
+    let value = MString::<3>::try_from(s).unwrap();
+    assert_eq!(value.num_bytes(), 10);
+    assert_eq!(encode(value), raw(s));
+    assert_eq!(decode::<3>(&raw(s)).unwrap().as_str(), s);
+    assert!(decode::<2>(&raw(s)).is_err());
+}
+
+#[test]
+fn surrogate_pairs_count_as_two_units() {
+    // each emoji is 4 UTF-8 bytes and 2 UTF-16 code units
+    let s = "🙂🙂";
+    assert_eq!(s.len(), 8);
+    assert!(MString::<4>::try_from(s).is_ok());
+    assert!(MString::<3>::try_from(s).is_err());
+    assert!(decode::<4>(&raw(s)).is_ok());
+    assert!(decode::<3>(&raw(s)).is_err());
+}
+
+#[test]
+fn mixed_chat_message() {
+    let s = "Привет, 世界! 👋 ok";
+    let units = s.encode_utf16().count();
+    assert!(s.len() > units);
+    assert_eq!(decode::<256>(&raw(s)).unwrap().as_str(), s);
+    assert!(MString::<256>::try_from(s).is_ok());
+    assert!(MString::<17>::try_from(s).is_ok());
+    assert!(MString::<16>::try_from(s).is_err());
+}
+
+#[test]
+fn byte_prefix_over_three_n_is_rejected_before_reading() {
+    // claims 10 bytes for N = 3 but provides none, so this must fail on the length alone
+    let bytes = encode(VarInt(10));
+    let err = decode::<3>(&bytes).unwrap_err();
+    assert!(err.to_string().contains("too long"), "{err}");
+}
+
+#[test]
+fn negative_and_invalid_utf8() {
+    assert!(decode::<16>(&encode(VarInt(-1))).is_err());
+    assert!(decode::<16>(&[2, 0xc3, 0x28]).is_err());
+}
@@ -0,0 +1,309 @@
+mod common;
+
+use std::net::TcpListener;
+
+use common::{flat_chunk, flat_world, play_until_ended, FakeServer};
+use minecraft_client::{
+    block::{BlockState, BLOCKS_VERSION},
+    connection::Connection,
+    datatypes::{Position, VarInt, Vec3d},
+    packet::{SetPlayerPositionPacket, SynchronizePlayerPositionPacket, TeleportFlags},
+    pathfinding::{find_path, NavigationStatus, Navigator, PathCosts, Unreachable},
+    player::PlayerState,
+    protocol::ProtocolVersion,
+    world::World,
+};
+
+fn stone() -> i32 {
+    "stone".parse::<BlockState>().unwrap().0
+}
+
+fn pos(x: i32, y: i32, z: i32) -> Position {
+    Position::new(x, y, z)
+}
+
+/// Fills a box of blocks, both corners included
+fn fill(world: &mut World, from: Position, to: Position, state: i32) {
+    for x in from.x()..=to.x() {
+        for y in from.y()..=to.y() {
+            for z in from.z()..=to.z() {
+                world.set_block(pos(x, y, z), state);
+            }
+        }
+    }
+}
+
+/// A two block tall wall along x at z 5, with a gap at x 12
+fn wall_with_gap(world: &mut World) {
+    fill(world, pos(-16, 0, 5), pos(31, 1, 5), stone());
+    fill(world, pos(12, 0, 5), pos(12, 1, 5), 0);
+}
+
+fn standing_at(position: Position) -> PlayerState {
+    let mut player = PlayerState::new();
+    player.position = Vec3d {
+        x: position.x() as f64 + 0.5,
+        y: position.y() as f64,
+        z: position.z() as f64 + 0.5,
+    };
+    player.on_ground = true;
+    player
+}
+
+/// Ticks the player along the navigator's path, returning the ticks it took to finish
+fn follow(navigator: &mut Navigator, player: &mut PlayerState, world: &World) -> u32 {
+    for tick in 0..600 {
+        let input = navigator.steer(player, world);
+        if navigator.status() != NavigationStatus::Moving {
+            return tick;
+        }
+        player.tick(&input, world);
+    }
+    panic!("still moving at {:?}", player.position);
+}
+
+fn assert_connected(start: Position, path: &[Position]) {
+    let mut at = start;
+    for &next in path {
+        let (dx, dz) = (next.x() - at.x(), next.z() - at.z());
+        assert!(dx.abs() <= 1 && dz.abs() <= 1, "{at:?} to {next:?}");
+        at = next;
+    }
+}
+
+#[test]
+fn straight_and_diagonal_paths() {
+    let world = flat_world();
+    let costs = PathCosts::default();
+    let path = find_path(&world, pos(0, 0, 0), pos(6, 0, 0), &costs).unwrap();
+    assert_eq!(path.len(), 6);
+    assert_eq!(path.last(), Some(&pos(6, 0, 0)));
+    assert_connected(pos(0, 0, 0), &path);
+
+    let path = find_path(&world, pos(0, 0, 0), pos(4, 0, 4), &costs).unwrap();
+    assert_eq!(path.len(), 4);
+    assert_connected(pos(0, 0, 0), &path);
+
+    assert!(find_path(&world, pos(3, 0, 3), pos(3, 0, 3), &costs)
+        .unwrap()
+        .eq(&[pos(3, 0, 3)]));
+}
+
+#[test]
+fn paths_go_around_walls() {
+    let mut world = flat_world();
+    wall_with_gap(&mut world);
+    let path = find_path(&world, pos(0, 0, 0), pos(0, 0, 10), &PathCosts::default()).unwrap();
+    assert!(path.contains(&pos(12, 0, 5)));
+    assert_connected(pos(0, 0, 0), &path);
+    assert!(path.iter().all(|&position| world.is_walkable(position)));
+}
+
+#[test]
+fn jumps_and_falls() {
+    let mut world = flat_world();
+    // two one block steps up at x 3 and x 6, then a tower at x 9 that is four blocks taller
+    fill(&mut world, pos(3, 0, -8), pos(5, 0, 8), stone());
+    fill(&mut world, pos(6, 0, -8), pos(8, 1, 8), stone());
+    fill(&mut world, pos(9, 0, -8), pos(15, 5, 8), stone());
+
+    let costs = PathCosts::default();
+    let path = find_path(&world, pos(0, 0, 0), pos(7, 2, 0), &costs).unwrap();
+    let heights: Vec<i32> = path.iter().map(|position| position.y()).collect();
+    assert_eq!(heights, [0, 0, 1, 1, 1, 2, 2], "{path:?}");
+
+    // getting down off the tower drops four blocks at least, more than the default allows
+    let down = find_path(&world, pos(12, 6, 0), pos(0, 0, 0), &costs);
+    let err = down.unwrap_err();
+    let unreachable = err.downcast_ref::<Unreachable>().unwrap();
+    assert_eq!(unreachable.goal, pos(0, 0, 0));
+    let bolder = PathCosts {
+        max_fall: 4,
+        ..costs
+    };
+    let path = find_path(&world, pos(12, 6, 0), pos(0, 0, 0), &bolder).unwrap();
+    assert!(path.iter().any(|position| position.y() == 2), "{path:?}");
+}
+
+#[test]
+fn costs_steer_the_route() {
+    let mut world = flat_world();
+    // a one block hump in the way, and a walk around it through x 9
+    fill(&mut world, pos(-16, 0, 4), pos(8, 0, 4), stone());
+    let path = |costs: &PathCosts| find_path(&world, pos(0, 0, 0), pos(0, 0, 8), costs).unwrap();
+
+    let over = path(&PathCosts::default());
+    assert!(over.contains(&pos(0, 1, 4)), "{over:?}");
+
+    let around = path(&PathCosts {
+        jump: 30.0,
+        ..PathCosts::default()
+    });
+    assert!(
+        around.iter().all(|position| position.y() == 0),
+        "{around:?}"
+    );
+}
+
+#[test]
+fn unreachable_goals_are_reported() {
+    let mut world = flat_world();
+    fill(&mut world, pos(2, 0, 2), pos(6, 3, 6), stone());
+    fill(&mut world, pos(3, 0, 3), pos(5, 3, 5), 0);
+    let costs = PathCosts::default();
+    let err = find_path(&world, pos(0, 0, 0), pos(4, 0, 4), &costs).unwrap_err();
+    let unreachable = err.downcast_ref::<Unreachable>().unwrap();
+    assert!(unreachable.explored > 100);
+    assert!(err.to_string().starts_with("no path to 4 0 4"));
+
+    // beyond the loaded chunks
+    assert!(find_path(&world, pos(0, 0, 0), pos(40, 0, 0), &costs).is_err());
+    // giving up early
+    let hasty = PathCosts {
+        max_nodes: 5,
+        ..costs
+    };
+    assert!(find_path(&world, pos(0, 0, 0), pos(10, 0, 0), &hasty).is_err());
+}
+
+#[test]
+fn physics_follows_the_path() {
+    let mut world = flat_world();
+    wall_with_gap(&mut world);
+    fill(&mut world, pos(-5, 0, 9), pos(5, 0, 13), stone());
+
+    let mut player = standing_at(pos(0, 0, 0));
+    let goal = pos(0, 1, 11);
+    let mut navigator = Navigator::new(&world, &player, goal, PathCosts::default()).unwrap();
+    follow(&mut navigator, &mut player, &world);
+    assert_eq!(navigator.status(), NavigationStatus::Arrived);
+    assert_eq!(player.block_position(), goal);
+    assert!(navigator.path().next().is_none());
+}
+
+#[test]
+fn walks_level_ground_lower_than_a_full_block() {
+    let mut world = flat_world();
+    let path_block = "dirt_path".parse::<BlockState>().unwrap().0;
+    fill(&mut world, pos(-8, -1, -8), pos(8, -1, 8), path_block);
+
+    // the feet are inside the path block, but the player stands on top of it
+    let mut player = standing_at(pos(0, -1, 0));
+    player.position.y = -1.0 / 16.0;
+    let mut navigator =
+        Navigator::new(&world, &player, pos(6, 0, 0), PathCosts::default()).unwrap();
+    assert_eq!(navigator.path().next(), Some(&pos(1, 0, 0)));
+    for _ in 0..600 {
+        let input = navigator.steer(&mut player, &world);
+        assert!(!input.jump, "jumped at {:?}", player.position);
+        if navigator.status() != NavigationStatus::Moving {
+            break;
+        }
+        player.tick(&input, &world);
+    }
+    assert_eq!(navigator.status(), NavigationStatus::Arrived);
+    assert!(player.on_ground);
+    assert_eq!(player.position.y, -1.0 / 16.0);
+    assert_eq!(player.block_position(), pos(6, -1, 0));
+}
+
+#[test]
+fn block_changes_replan_the_path() {
+    let mut world = flat_world();
+    wall_with_gap(&mut world);
+    // a second gap, further from the straight line
+    fill(&mut world, pos(-14, 0, 5), pos(-14, 1, 5), 0);
+
+    let mut player = standing_at(pos(0, 0, 0));
+    let goal = pos(0, 0, 10);
+    let mut navigator = Navigator::new(&world, &player, goal, PathCosts::default()).unwrap();
+    assert!(navigator.path().any(|&position| position == pos(12, 0, 5)));
+    for _ in 0..5 {
+        let input = navigator.steer(&mut player, &world);
+        player.tick(&input, &world);
+    }
+
+    // closing the near gap sends it through the far one
+    fill(&mut world, pos(12, 0, 5), pos(12, 1, 5), stone());
+    navigator.steer(&mut player, &world);
+    assert!(navigator.path().any(|&position| position == pos(-14, 0, 5)));
+    follow(&mut navigator, &mut player, &world);
+    assert_eq!(navigator.status(), NavigationStatus::Arrived);
+    assert_eq!(player.block_position(), goal);
+
+    // and closing both on the way back leaves no way at all
+    let mut navigator =
+        Navigator::new(&world, &player, pos(0, 0, 0), PathCosts::default()).unwrap();
+    assert!(navigator.last_error().is_none());
+    fill(&mut world, pos(-14, 0, 5), pos(-14, 1, 5), stone());
+    let input = navigator.steer(&mut player, &world);
+    assert_eq!(navigator.status(), NavigationStatus::Unreachable);
+    assert_eq!(navigator.last_error().unwrap().goal, pos(0, 0, 0));
+    assert_eq!(input.forward, 0.0);
+}
+
+#[test]
+fn goto_walks_through_the_play_loop() {
+    let goal = pos(11, 0, 8);
+    let (port, server) = FakeServer::spawn(move |mut server| {
+        server.join_game();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                server.send(flat_chunk(x, z));
+            }
+        }
+        server.send(SynchronizePlayerPositionPacket {
+            teleport_id: VarInt(1),
+            position: Vec3d {
+                x: 8.5,
+                y: 0.0,
+                z: 8.5,
+            },
+            velocity: Vec3d::default(),
+            yaw: 0.0,
+            pitch: 0.0,
+            flags: TeleportFlags(0),
+        });
+        loop {
+            let moved = server.recv_skipping::<SetPlayerPositionPacket>();
+            if moved.position.x.floor() as i32 == goal.x()
+                && moved.position.z.floor() as i32 == goal.z()
+            {
+                break;
+            }
+        }
+        server.end_play();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    assert!(conn.goto(goal).is_err());
+    conn.on_tick(move |conn| {
+        if conn.navigator().is_none() && conn.player().is_some() {
+            conn.goto(goal)?;
+        }
+        Ok(())
+    });
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    play_until_ended(&mut conn);
+    server.join().unwrap();
+
+    assert_eq!(conn.navigator().unwrap().goal(), goal);
+}
+
+#[test]
+fn goto_needs_the_bundled_block_states() {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let mut connection =
+        Connection::connect_with_version("127.0.0.1".to_string(), port, ProtocolVersion::V1_21_5)
+            .unwrap();
+    assert!(!connection.blocks_known());
+    let err = connection.goto(pos(3, 0, 0)).unwrap_err();
+    assert!(err.to_string().contains("block states"), "{err}");
+    assert!(connection.navigator().is_none());
+
+    let connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    assert_eq!(connection.version(), BLOCKS_VERSION);
+    assert!(connection.blocks_known());
+}
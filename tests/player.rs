@@ -0,0 +1,337 @@
+mod common;
+
+use common::{flat_chunk, flat_world, play_until_ended, FakeServer};
+use minecraft_client::{
+    block::BlockState,
+    connection::Connection,
+    datatypes::{Position, VarInt, Vec3d},
+    packet::{
+        ConfirmTeleportationPacket, PlayerCommandPacket, PlayerInputPacket, PlayerLoadedPacket,
+        SetPlayerPositionAndRotationPacket, SetPlayerPositionPacket,
+        SynchronizePlayerPositionPacket, TeleportFlags,
+    },
+    player::{Input, MovementPacket, MovementReporter, PlayerState},
+};
+
+fn vec(x: f64, y: f64, z: f64) -> Vec3d {
+    Vec3d { x, y, z }
+}
+
+fn sync(position: Vec3d, yaw: f32, pitch: f32, flags: i32) -> SynchronizePlayerPositionPacket {
+    SynchronizePlayerPositionPacket {
+        teleport_id: VarInt(1),
+        position,
+        velocity: Vec3d::default(),
+        yaw,
+        pitch,
+        flags: TeleportFlags(flags),
+    }
+}
+
+fn assert_close(a: Vec3d, b: Vec3d) {
+    assert!(
+        (a.x - b.x).abs() < 1e-6 && (a.y - b.y).abs() < 1e-6 && (a.z - b.z).abs() < 1e-6,
+        "{a:?} != {b:?}"
+    );
+}
+
+#[test]
+fn absolute_and_relative_teleports() {
+    let mut player = PlayerState::new();
+    player.synchronize(&sync(vec(8.5, 64.0, -3.5), 90.0, 10.0, 0));
+    assert_eq!(player.position, vec(8.5, 64.0, -3.5));
+    assert_eq!((player.yaw, player.pitch), (90.0, 10.0));
+
+    player.synchronize(&sync(
+        vec(1.0, 100.0, -1.0),
+        45.0,
+        85.0,
+        TeleportFlags::X | TeleportFlags::Z | TeleportFlags::YAW | TeleportFlags::PITCH,
+    ));
+    assert_eq!(player.position, vec(9.5, 100.0, -4.5));
+    assert_eq!(player.yaw, 135.0);
+    // pitch is clamped to straight down
+    assert_eq!(player.pitch, 90.0);
+}
+
+#[test]
+fn velocity_can_be_kept_and_rotated() {
+    let mut player = PlayerState::new();
+    player.velocity = vec(0.0, 0.0, 1.0);
+    let mut packet = sync(Vec3d::default(), 0.0, 0.0, 0);
+    packet.velocity = vec(0.0, 0.5, 0.0);
+    packet.flags = TeleportFlags(TeleportFlags::VELOCITY_X | TeleportFlags::VELOCITY_Z);
+    player.synchronize(&packet);
+    assert_eq!(player.velocity, vec(0.0, 0.5, 1.0));
+
+    // turning a quarter to the right also turns the velocity that is kept
+    packet.yaw = 90.0;
+    packet.velocity = Vec3d::default();
+    packet.flags = TeleportFlags(
+        TeleportFlags::VELOCITY_X
+            | TeleportFlags::VELOCITY_Y
+            | TeleportFlags::VELOCITY_Z
+            | TeleportFlags::ROTATE_VELOCITY,
+    );
+    player.synchronize(&packet);
+    assert_close(player.velocity, vec(-1.0, 0.5, 0.0));
+}
+
+#[test]
+fn teleports_are_confirmed() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        server.send(SynchronizePlayerPositionPacket {
+            teleport_id: VarInt(42),
+            ..sync(vec(0.5, 70.0, 0.5), 180.0, 0.0, 0)
+        });
+        let confirm = server.recv::<ConfirmTeleportationPacket>();
+        assert_eq!(confirm.teleport_id.0, 42);
+        let moved = server.recv::<SetPlayerPositionAndRotationPacket>();
+        assert_eq!(moved.position, vec(0.5, 70.0, 0.5));
+        assert_eq!((moved.yaw, moved.pitch), (180.0, 0.0));
+        assert!(!moved.flags.on_ground());
+
+        server.send(sync(
+            vec(0.0, -2.0, 0.0),
+            0.0,
+            0.0,
+            TeleportFlags::Y | TeleportFlags::Z | TeleportFlags::YAW | TeleportFlags::PITCH,
+        ));
+        server.recv::<ConfirmTeleportationPacket>();
+        let moved = server.recv::<SetPlayerPositionAndRotationPacket>();
+        assert_eq!(moved.position, vec(0.0, 68.0, 0.5));
+        server.end_play();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    assert!(conn.player().is_none());
+    play_until_ended(&mut conn);
+    server.join().unwrap();
+
+    let player = conn.player().unwrap();
+    assert_eq!(player.position, vec(0.0, 68.0, 0.5));
+    assert_eq!(player.yaw, 180.0);
+}
+
+fn standing_at(x: f64, y: f64, z: f64) -> PlayerState {
+    let mut player = PlayerState::new();
+    player.position = vec(x, y, z);
+    player
+}
+
+fn forward() -> Input {
+    Input {
+        forward: 1.0,
+        ..Input::default()
+    }
+}
+
+#[test]
+fn falling_lands_on_the_floor() {
+    let world = flat_world();
+    let mut player = standing_at(8.5, 10.0, 8.5);
+    let mut ticks = 0;
+    while !player.on_ground {
+        player.tick(&Input::default(), &world);
+        ticks += 1;
+        assert!(ticks < 100);
+    }
+    assert_eq!(player.position, vec(8.5, 0.0, 8.5));
+    // 9.67 blocks in 17 ticks, so the 18th lands
+    assert_eq!(ticks, 18);
+    player.tick(&Input::default(), &world);
+    assert!(player.on_ground);
+    assert_eq!(player.position.y, 0.0);
+}
+
+#[test]
+fn walking_and_sprinting_speeds() {
+    let world = flat_world();
+    let speed = |input: Input| {
+        let mut player = standing_at(0.5, 0.0, -12.0);
+        player.on_ground = true;
+        for _ in 0..50 {
+            player.tick(&input, &world);
+        }
+        let before = player.position.z;
+        player.tick(&input, &world);
+        (player.position.z - before, player)
+    };
+
+    // about 4.317 blocks a second facing south
+    let (walking, player) = speed(forward());
+    assert!((walking - 0.21586).abs() < 1e-4, "{walking}");
+    assert!(!player.sprinting);
+    assert_eq!(player.position.x, 0.5);
+
+    let (sprinting, player) = speed(Input {
+        sprint: true,
+        ..forward()
+    });
+    assert!((sprinting - 0.28062).abs() < 1e-4, "{sprinting}");
+    assert!(player.sprinting);
+
+    let (sneaking, _) = speed(Input {
+        sneak: true,
+        sprint: true,
+        ..forward()
+    });
+    assert!((sneaking - 0.21586 * 0.3).abs() < 1e-4, "{sneaking}");
+
+    // facing east, strafing left goes north
+    let mut player = standing_at(0.5, 0.0, 0.5);
+    player.yaw = -90.0;
+    for _ in 0..10 {
+        player.tick(
+            &Input {
+                strafe: 1.0,
+                ..Input::default()
+            },
+            &world,
+        );
+    }
+    assert!(player.position.z < -1.0);
+    assert!((player.position.x - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn walls_stop_walking_and_jumping_climbs_them() {
+    let mut world = flat_world();
+    let stone = "stone".parse::<BlockState>().unwrap().0;
+    for x in -16..32 {
+        world.set_block(Position::new(x, 0, 5), stone);
+    }
+
+    let mut player = standing_at(0.5, 0.0, 0.5);
+    for _ in 0..40 {
+        player.tick(&forward(), &world);
+    }
+    assert_eq!(player.position.z, 4.7);
+    assert!(player.horizontal_collision);
+    assert!(player.on_ground);
+
+    let input = Input {
+        jump: true,
+        ..forward()
+    };
+    for _ in 0..20 {
+        player.tick(&input, &world);
+    }
+    assert!(player.position.z > 5.0);
+    assert!(player.position.y >= 1.0);
+}
+
+#[test]
+fn water_slows_falling_and_can_be_swum_up() {
+    let mut world = flat_world();
+    let water = "water".parse::<BlockState>().unwrap().0;
+    for y in 0..10 {
+        world.set_block(Position::new(3, y, 3), water);
+    }
+
+    let mut player = standing_at(3.5, 8.0, 3.5);
+    for _ in 0..20 {
+        player.tick(&Input::default(), &world);
+    }
+    // sinking, but far slower than the ten blocks a fall would have gone
+    assert!(player.position.y < 8.0 && player.position.y > 6.0);
+    assert!(player.velocity.y > -0.1);
+
+    let before = player.position.y;
+    let input = Input {
+        jump: true,
+        ..Input::default()
+    };
+    for _ in 0..10 {
+        player.tick(&input, &world);
+    }
+    assert!(player.position.y > before);
+}
+
+#[test]
+fn only_changes_are_reported() {
+    let mut reporter = MovementReporter::new();
+    let mut player = standing_at(0.5, 0.0, 0.5);
+    assert!(matches!(
+        reporter.report(&player),
+        Some(MovementPacket::PositionAndRotation(_))
+    ));
+    assert!(reporter.report(&player).is_none());
+
+    player.on_ground = true;
+    match reporter.report(&player) {
+        Some(MovementPacket::Flags(packet)) => assert!(packet.flags.on_ground()),
+        other => panic!("{other:?}"),
+    }
+    player.yaw = 10.0;
+    assert!(matches!(
+        reporter.report(&player),
+        Some(MovementPacket::Rotation(_))
+    ));
+    player.position.x += 0.1;
+    assert!(matches!(
+        reporter.report(&player),
+        Some(MovementPacket::Position(_))
+    ));
+
+    // the position is repeated once a second even when standing still
+    let reminders = (0..40)
+        .filter_map(|_| reporter.report(&player))
+        .filter(|packet| matches!(packet, MovementPacket::Position(_)))
+        .count();
+    assert_eq!(reminders, 2);
+
+    assert!(reporter.input_changed(&forward()));
+    assert!(!reporter.input_changed(&forward()));
+    assert_eq!(forward().flags(), PlayerInputPacket::FORWARD);
+}
+
+#[test]
+fn the_play_loop_walks() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                server.send(flat_chunk(x, z));
+            }
+        }
+        server.send(sync(vec(8.5, 0.0, 8.5), 0.0, 0.0, 0));
+        server.recv_skipping::<ConfirmTeleportationPacket>();
+        server.recv_skipping::<PlayerLoadedPacket>();
+
+        let input = server.recv_skipping::<PlayerInputPacket>();
+        assert_eq!(
+            input.flags,
+            PlayerInputPacket::FORWARD | PlayerInputPacket::SPRINT
+        );
+        let command = server.recv_skipping::<PlayerCommandPacket>();
+        assert_eq!(command.entity_id.0, common::PLAYER_ENTITY_ID);
+        let mut last_z = 8.5;
+        for _ in 0..5 {
+            let moved = server.recv_skipping::<SetPlayerPositionPacket>();
+            assert!(moved.position.z > last_z);
+            assert_eq!(moved.position.y, 0.0);
+            last_z = moved.position.z;
+        }
+        server.end_play();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    conn.on_tick(|conn| {
+        if conn.player().is_some_and(|player| player.on_ground) {
+            conn.set_input(1.0, 0.0, false, false, true);
+        }
+        Ok(())
+    });
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    play_until_ended(&mut conn);
+    server.join().unwrap();
+
+    let player = conn.player().unwrap();
+    assert!(player.position.z > 9.0);
+    assert!(player.sprinting);
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use common::{play_until_ended, FakeServer};
+use minecraft_client::{
+    connection::{Connection, ConnectionState},
+    datatypes::{GameProfile, Identifier, MinecraftData, UUID},
+    packet::{
+        decode_packet_body, AcknowledgeFinishConfigurationPacket, ChannelList,
+        ClientboundConfigurationPluginMessagePacket, ClientboundPlayPluginMessagePacket,
+        ConfigurationClientInformationPacket, FinishConfigurationPacket, LoginAcknowledgedPacket,
+        LoginStartPacket, LoginSuccessPacket, PluginChannelData,
+        ServerboundConfigurationPluginMessagePacket, ServerboundPlayPluginMessagePacket,
+    },
+    plugin::ChannelMessage,
+    protocol::ProtocolVersion,
+};
+
+#[derive(Debug, Clone, PartialEq, MinecraftData)]
+struct Echo {
+    value: i64,
+}
+
+impl ChannelMessage for Echo {
+    const CHANNEL: &'static str = "test:echo";
+}
+
+fn id(s: &str) -> Identifier {
+    s.try_into().unwrap()
+}
+
+fn roundtrip(data: PluginChannelData) -> PluginChannelData {
+    let mut body = Vec::new();
+    let packet = ClientboundConfigurationPluginMessagePacket { data };
+    let len = packet.num_bytes();
+    packet.encode(&mut body).unwrap();
+    assert_eq!(body.len(), len);
+    decode_packet_body::<ClientboundConfigurationPluginMessagePacket>(
+        &body,
+        ProtocolVersion::LATEST,
+    )
+    .unwrap()
+    .data
+}
+
+#[test]
+fn unknown_channels_keep_their_payload() {
+    let data = PluginChannelData::Unknown(id("bungeecord:main"), vec![0, 1, 2, 255]);
+    assert_eq!(roundtrip(data.clone()), data);
+    let empty = PluginChannelData::Unknown(id("a:b"), vec![]);
+    assert_eq!(roundtrip(empty.clone()), empty);
+}
+
+#[test]
+fn channel_lists_are_nul_separated() {
+    let list = ChannelList(vec![id("a:b"), id("c:d")]);
+    let mut bytes = Vec::new();
+    list.clone().encode(&mut bytes).unwrap();
+    assert_eq!(bytes, b"a:b\0c:d");
+    assert_eq!(
+        roundtrip(PluginChannelData::Register(list.clone())),
+        PluginChannelData::Register(list)
+    );
+    // a trailing separator, as some servers send, is ignored
+    assert_eq!(
+        ChannelList::decode(&mut &b"a:b\0"[..]).unwrap(),
+        ChannelList(vec![id("a:b")])
+    );
+}
+
+#[test]
+fn brand_register_and_typed_handlers() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        let start = server.recv::<LoginStartPacket>();
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+
+        let brand = server.recv::<ServerboundConfigurationPluginMessagePacket>();
+        assert_eq!(
+            brand.data,
+            PluginChannelData::MinecraftBrand("tester".try_into().unwrap())
+        );
+        let register = server.recv::<ServerboundConfigurationPluginMessagePacket>();
+        assert_eq!(
+            register.data,
+            PluginChannelData::Register(ChannelList(vec![id("test:echo")]))
+        );
+        server.recv::<ConfigurationClientInformationPacket>();
+
+        server.send(ClientboundConfigurationPluginMessagePacket {
+            data: PluginChannelData::MinecraftBrand("fake".try_into().unwrap()),
+        });
+        server.send(ClientboundConfigurationPluginMessagePacket {
+            data: PluginChannelData::Register(ChannelList(vec![id("s:one"), id("s:two")])),
+        });
+        server.send(ClientboundConfigurationPluginMessagePacket {
+            data: PluginChannelData::Unknown(
+                id("test:echo"),
+                Echo { value: 1 }.encode_payload().unwrap(),
+            ),
+        });
+        let reply = server.recv::<ServerboundConfigurationPluginMessagePacket>();
+        assert_eq!(
+            reply.data,
+            PluginChannelData::Unknown(
+                id("test:echo"),
+                Echo { value: 2 }.encode_payload().unwrap()
+            )
+        );
+        server.send(FinishConfigurationPacket);
+        server.recv::<AcknowledgeFinishConfigurationPacket>();
+
+        server.send(ClientboundPlayPluginMessagePacket {
+            data: PluginChannelData::Unregister(ChannelList(vec![id("s:one")])),
+        });
+        server.send(ClientboundPlayPluginMessagePacket {
+            data: PluginChannelData::Unknown(
+                id("test:echo"),
+                Echo { value: 5 }.encode_payload().unwrap(),
+            ),
+        });
+        let reply = server.recv::<ServerboundPlayPluginMessagePacket>();
+        assert_eq!(
+            reply.data,
+            PluginChannelData::Unknown(
+                id("test:echo"),
+                Echo { value: 6 }.encode_payload().unwrap()
+            )
+        );
+        server.end_play();
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.set_brand("tester".to_string());
+    connection
+        .plugin_channels_mut()
+        .register_typed(|echo: Echo| {
+            Some(Echo {
+                value: echo.value + 1,
+            })
+        });
+    connection.login().unwrap();
+    assert_eq!(connection.state(), ConnectionState::Configuration);
+    connection.configure().unwrap();
+    assert_eq!(connection.state(), ConnectionState::Play);
+    assert_eq!(connection.server_brand(), Some("fake"));
+    play_until_ended(&mut connection);
+    server.join().unwrap();
+
+    let channels = connection.plugin_channels();
+    assert!(!channels.server_listens_on(&id("s:one")));
+    assert!(channels.server_listens_on(&id("s:two")));
+}
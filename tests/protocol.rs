@@ -0,0 +1,369 @@
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use common::{play_until_ended, FakeServer, END_OF_TEST};
+use minecraft_client::{
+    connection::{Connection, ConnectionState},
+    datatypes::{decode_frame, GameProfile, MinecraftData, VarInt, UUID},
+    nbt::Nbt,
+    packet::{
+        decode_packet_body, AcknowledgeFinishConfigurationPacket, ClientboundPlayKeepAlivePacket,
+        FinishConfigurationPacket, HandshakeIntent, HandshakePacket, LoginAcknowledgedPacket,
+        LoginStartPacket, LoginSuccessPacket, Packet, PlayDisconnectPacket,
+        ServerboundPlayKeepAlivePacket, StatusRequestPacket, StatusResponsePacket,
+    },
+    protocol::{status_protocol, Direction, ProtocolVersion},
+    reconnect::{ClientEvent, ReconnectingClient},
+};
+
+use ConnectionState::{Configuration, Login, Play};
+use Direction::{Clientbound, Serverbound};
+use ProtocolVersion::*;
+
+#[test]
+fn latest_ids_are_unchanged() {
+    for id in 0..0x90 {
+        let latest = ProtocolVersion::LATEST;
+        assert_eq!(
+            latest.packet_id(Play, Clientbound, VarInt(id)),
+            Some(VarInt(id))
+        );
+        assert_eq!(
+            latest.latest_packet_id(Play, Clientbound, VarInt(id)),
+            VarInt(id)
+        );
+    }
+}
+
+#[test]
+fn ids_map_between_versions() {
+    // keep alive, after the debug packets added in 1.21.9
+    assert_eq!(
+        V1_21_5.packet_id(Play, Clientbound, VarInt(0x2B)),
+        Some(VarInt(0x26))
+    );
+    assert_eq!(
+        V1_21_7.packet_id(Play, Clientbound, VarInt(0x2B)),
+        Some(VarInt(0x26))
+    );
+    assert_eq!(
+        V1_21_5.packet_id(Play, Serverbound, VarInt(0x1B)),
+        Some(VarInt(0x1A))
+    );
+    assert_eq!(
+        V1_21_7.packet_id(Play, Serverbound, VarInt(0x1B)),
+        Some(VarInt(0x1B))
+    );
+    // before anything was added
+    assert_eq!(
+        V1_21_5.packet_id(Play, Clientbound, VarInt(0x19)),
+        Some(VarInt(0x19))
+    );
+    assert_eq!(
+        V1_21_5.packet_id(Login, Clientbound, VarInt(0x02)),
+        Some(VarInt(0x02))
+    );
+    // transfer
+    assert_eq!(
+        V1_21_5.packet_id(Play, Clientbound, VarInt(0x7F)),
+        Some(VarInt(0x7A))
+    );
+    assert_eq!(
+        V1_21_5.packet_id(Configuration, Clientbound, VarInt(0x0B)),
+        Some(VarInt(0x0B))
+    );
+    // dialogs only exist since 1.21.6
+    assert_eq!(
+        V1_21_5.packet_id(Configuration, Clientbound, VarInt(0x12)),
+        None
+    );
+    assert_eq!(
+        V1_21_6.packet_id(Configuration, Clientbound, VarInt(0x12)),
+        Some(VarInt(0x12))
+    );
+    assert_eq!(
+        V1_21_6.packet_id(Configuration, Clientbound, VarInt(0x13)),
+        None
+    );
+
+    for version in ProtocolVersion::ALL {
+        for state in [Configuration, Play] {
+            for direction in [Clientbound, Serverbound] {
+                for id in 0..0x90 {
+                    if let Some(old) = version.packet_id(state, direction, VarInt(id)) {
+                        assert_eq!(
+                            version.latest_packet_id(state, direction, old),
+                            VarInt(id),
+                            "{version:?} {state:?} {direction:?} {id:#x}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn versions_from_status() {
+    let json =
+        r#"{"version": {"name": "1.21.5", "protocol": 770}, "players": {"max": 20, "online": 0}}"#;
+    assert_eq!(status_protocol(json), Some(770));
+    assert_eq!(ProtocolVersion::from_status(json), Some(V1_21_5));
+    assert_eq!(
+        ProtocolVersion::from_status(r#"{"version":{"name":"1.8","protocol":47}}"#),
+        None
+    );
+    assert_eq!(status_protocol(r#"{"description":"no version"}"#), None);
+    // only the top-level version object counts, whatever the MOTD says
+    let motd = r#"{"description": {"text": "join! \"version\": {\"protocol\": 47}"},
+        "players": {"sample": [{"name": "version", "id": "0"}]},
+        "version": {"protocol": 773, "name": "1.21.10"}}"#;
+    assert_eq!(status_protocol(motd), Some(773));
+    assert_eq!(ProtocolVersion::from_status(motd), Some(V1_21_9));
+    let motd_only = r#"{"description": "\"version\": {\"protocol\": 770}"}"#;
+    assert_eq!(status_protocol(motd_only), None);
+    assert_eq!(status_protocol(r#"{"version": {"protocol": 770}"#), None);
+    // moderate nesting in skipped values is fine, hostile nesting is refused rather than
+    // overflowing the stack
+    let nested = format!(
+        r#"{{"a": {}{}, "version": {{"protocol": 770}}}}"#,
+        "[".repeat(32),
+        "]".repeat(32)
+    );
+    assert_eq!(status_protocol(&nested), Some(770));
+    let hostile = format!(r#"{{"a":{}"#, "[".repeat(32_000));
+    std::thread::Builder::new()
+        .stack_size(64 * 1024)
+        .spawn(move || assert_eq!(status_protocol(&hostile), None))
+        .unwrap()
+        .join()
+        .unwrap();
+    for version in ProtocolVersion::ALL {
+        assert_eq!(
+            ProtocolVersion::from_protocol(version.protocol()),
+            Some(version)
+        );
+    }
+}
+
+#[derive(Debug, PartialEq, MinecraftData)]
+struct Gated {
+    a: u8,
+    #[mc(since = ProtocolVersion::V1_21_6)]
+    added: Option<u8>,
+    #[mc(before = ProtocolVersion::V1_21_9)]
+    removed: Option<u8>,
+}
+
+#[test]
+fn version_gated_fields() {
+    let value = || Gated {
+        a: 1,
+        added: Some(2),
+        removed: Some(3),
+    };
+    let encode = |version: ProtocolVersion| {
+        let mut bytes = Vec::new();
+        let value = value();
+        let len = version.num_bytes(&value);
+        version.encode(value, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), len);
+        bytes
+    };
+    assert_eq!(encode(V1_21_5), [1, 3]);
+    assert_eq!(encode(V1_21_7), [1, 2, 3]);
+    assert_eq!(encode(V1_21_9), [1, 2]);
+
+    let decoded: Gated = V1_21_5.decode(&mut &[1u8, 3][..]).unwrap();
+    assert_eq!(
+        decoded,
+        Gated {
+            a: 1,
+            added: None,
+            removed: Some(3)
+        }
+    );
+    // straight through the trait, outside any entry point that takes a version
+    let decoded = Gated::decode(&mut &[1u8, 2][..]).unwrap();
+    assert_eq!(
+        decoded,
+        Gated {
+            a: 1,
+            added: Some(2),
+            removed: None
+        }
+    );
+}
+
+#[test]
+fn version_is_kept_per_call_across_threads() {
+    let decoded = thread::spawn(|| decode_frame::<Gated>(&mut &[1u8, 3][..], V1_21_5))
+        .join()
+        .unwrap()
+        .unwrap();
+    assert_eq!(decoded.removed, Some(3));
+    let decoded: Gated = decode_frame(&mut &[1u8, 2][..], V1_21_9).unwrap();
+    assert_eq!(decoded.added, Some(2));
+}
+
+#[test]
+fn connects_with_an_older_version() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        let handshake = server.recv::<HandshakePacket>();
+        assert_eq!(handshake.protocol_version, VarInt(770));
+        let start = server.recv::<LoginStartPacket>();
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+        server.send(FinishConfigurationPacket);
+        server.recv_skipping::<AcknowledgeFinishConfigurationPacket>();
+
+        let mut bytes = Vec::new();
+        ClientboundPlayKeepAlivePacket { keep_alive_id: 7 }
+            .encode_packet_with_id(V1_21_5, VarInt(0x26), &mut bytes)
+            .unwrap();
+        server.send_raw(&bytes);
+        let (id, body) = server.recv_raw();
+        assert_eq!(id, VarInt(0x1A));
+        let resp: ServerboundPlayKeepAlivePacket = decode_packet_body(&body, V1_21_5).unwrap();
+        assert_eq!(resp.keep_alive_id, 7);
+        let mut bytes = Vec::new();
+        PlayDisconnectPacket {
+            reason: Nbt::String(END_OF_TEST.to_string()),
+        }
+        .encode_packet_with_id(
+            V1_21_5,
+            V1_21_5
+                .packet_id(Play, Clientbound, PlayDisconnectPacket::ID)
+                .unwrap(),
+            &mut bytes,
+        )
+        .unwrap();
+        server.send_raw(&bytes);
+    });
+
+    let mut conn = Connection::connect_with_version("127.0.0.1".to_owned(), port, V1_21_5).unwrap();
+    assert_eq!(conn.version(), V1_21_5);
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    play_until_ended(&mut conn);
+    assert_eq!(conn.keep_alive_metrics().count, 1);
+    server.join().unwrap();
+    assert_eq!(ServerboundPlayKeepAlivePacket::ID, VarInt(0x1B));
+}
+
+#[test]
+fn closest_versions() {
+    assert_eq!(ProtocolVersion::closest(772), V1_21_7);
+    assert_eq!(ProtocolVersion::closest(800), ProtocolVersion::LATEST);
+    assert_eq!(ProtocolVersion::closest(47), V1_21_5);
+}
+
+fn status_script(protocol: i32) -> Box<dyn FnOnce(FakeServer) + Send> {
+    Box::new(move |mut server| {
+        let handshake = server.recv::<HandshakePacket>();
+        assert!(matches!(handshake.intent, HandshakeIntent::Status));
+        server.recv::<StatusRequestPacket>();
+        server.send(StatusResponsePacket {
+            json_response: format!(r#"{{"version":{{"name":"test","protocol":{protocol}}}}}"#)
+                .try_into()
+                .unwrap(),
+        });
+    })
+}
+
+#[test]
+fn connect_auto_uses_the_status_version() {
+    let (port, server) = FakeServer::spawn_each(vec![
+        status_script(771),
+        Box::new(|mut server| {
+            let handshake = server.recv::<HandshakePacket>();
+            assert!(matches!(handshake.intent, HandshakeIntent::Login));
+            assert_eq!(handshake.protocol_version, VarInt(771));
+            server.recv::<LoginStartPacket>();
+        }),
+    ]);
+
+    let mut conn = Connection::connect_auto("127.0.0.1".to_owned(), port).unwrap();
+    assert_eq!(conn.version(), V1_21_6);
+    assert_eq!(conn.server_protocol(), Some(771));
+    // the server hangs up after login start
+    assert!(conn.login().is_err());
+    server.join().unwrap();
+}
+
+#[test]
+fn connect_auto_falls_back_to_the_closest_version() {
+    let (port, server) = FakeServer::spawn_each(vec![
+        status_script(900),
+        Box::new(|mut server| {
+            let handshake = server.recv::<HandshakePacket>();
+            assert_eq!(
+                handshake.protocol_version,
+                VarInt(ProtocolVersion::LATEST.protocol())
+            );
+        }),
+    ]);
+
+    let mut conn = Connection::connect_auto("127.0.0.1".to_owned(), port).unwrap();
+    assert_eq!(conn.version(), ProtocolVersion::LATEST);
+    assert_eq!(conn.server_protocol(), Some(900));
+    assert!(conn.login().is_err());
+    server.join().unwrap();
+}
+
+#[test]
+fn reconnecting_client_reports_the_chosen_version() {
+    let (port, server) = FakeServer::spawn_each(vec![
+        status_script(900),
+        Box::new(|mut server| {
+            server.recv::<HandshakePacket>();
+        }),
+    ]);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut client = ReconnectingClient::new("127.0.0.1".to_owned(), port);
+    client.set_version(None);
+    client.set_max_attempts(Some(1));
+    let log = events.clone();
+    client.on_event(move |event| log.lock().unwrap().push(event.clone()));
+    assert!(client.run().is_err());
+    server.join().unwrap();
+
+    assert!(events
+        .lock()
+        .unwrap()
+        .contains(&ClientEvent::VersionChosen {
+            server_protocol: 900,
+            version: ProtocolVersion::LATEST,
+        }));
+}
+
+#[test]
+fn unhandled_packets_are_skipped() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        // Award Statistics, which this client has no use for, with an empty list
+        server.send_raw(&[2, 0x03, 0]);
+        server.send(ClientboundPlayKeepAlivePacket { keep_alive_id: 9 });
+        let resp = server.recv_skipping::<ServerboundPlayKeepAlivePacket>();
+        assert_eq!(resp.keep_alive_id, 9);
+        server.end_play();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    play_until_ended(&mut conn);
+    assert_eq!(conn.keep_alive_metrics().count, 1);
+    server.join().unwrap();
+}
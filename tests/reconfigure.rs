@@ -0,0 +1,188 @@
+mod common;
+
+use common::{flat_chunk, play_until_ended, FakeServer};
+use minecraft_client::{
+    connection::{Connection, ConnectionState},
+    data_pack::LocalDataPack,
+    datatypes::{GameProfile, Tag, VarInt, Vec3d, UUID},
+    nbt::Nbt,
+    packet::{
+        AcknowledgeFinishConfigurationPacket, ClientboundKnownPacksPacket,
+        ConfigurationAcknowledgedPacket, ConfigurationClientInformationPacket,
+        ConfigurationUpdateTagsPacket, DataPack, FinishConfigurationPacket,
+        LoginAcknowledgedPacket, LoginStartPacket, LoginSuccessPacket, RegistryDataPacket,
+        RegistryEntry, ServerboundKnownPacksPacket, StartConfigurationPacket,
+        SynchronizePlayerPositionPacket, TaggedRegistry, TeleportFlags,
+    },
+};
+
+fn registry(registry_id: &str, ids: &[&str]) -> RegistryDataPacket {
+    RegistryDataPacket {
+        registry_id: registry_id.try_into().unwrap(),
+        entries: ids
+            .iter()
+            .map(|id| RegistryEntry {
+                id: (*id).try_into().unwrap(),
+                data: Some(Nbt::Compound(vec![("height".to_string(), Nbt::Int(384))])),
+            })
+            .collect(),
+    }
+}
+
+fn pack(id: &str) -> DataPack {
+    DataPack {
+        namespace: "minecraft".try_into().unwrap(),
+        id: id.try_into().unwrap(),
+        version: "1.21.10".try_into().unwrap(),
+    }
+}
+
+/// Runs one configuration phase, sending one registry and waiting for the client to finish
+fn configure(server: &mut FakeServer, packs: Vec<DataPack>, biomes: &[&str]) {
+    server.recv_skipping::<ConfigurationClientInformationPacket>();
+    server.send(ClientboundKnownPacksPacket { known_packs: packs });
+    server.recv::<ServerboundKnownPacksPacket>();
+    server.send(registry("minecraft:worldgen/biome", biomes));
+    server.send(ConfigurationUpdateTagsPacket {
+        tagged_registries: vec![TaggedRegistry {
+            registry: "minecraft:worldgen/biome".try_into().unwrap(),
+            tags: vec![Tag {
+                name: "minecraft:is_overworld".try_into().unwrap(),
+                entries: (0..biomes.len() as i32).map(VarInt).collect(),
+            }],
+        }],
+    });
+    server.send(FinishConfigurationPacket);
+    server.recv::<AcknowledgeFinishConfigurationPacket>();
+}
+
+#[test]
+fn start_configuration_replaces_registries() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        let start = server.recv::<LoginStartPacket>();
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+        server.send(registry(
+            "minecraft:dimension_type",
+            &["minecraft:overworld"],
+        ));
+        configure(
+            &mut server,
+            vec![pack("core"), pack("extra")],
+            &["minecraft:plains", "minecraft:desert"],
+        );
+
+        server.send(StartConfigurationPacket);
+        server.recv_skipping::<ConfigurationAcknowledgedPacket>();
+        configure(&mut server, vec![pack("core")], &["minecraft:the_void"]);
+        server.end_play();
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection
+        .data_packs_mut()
+        .add(LocalDataPack::new(pack("core")));
+    connection.login().unwrap();
+    connection.configure().unwrap();
+    let registries = connection.registries();
+    assert_eq!(
+        registries.id_of("minecraft:worldgen/biome", "minecraft:desert"),
+        Some(1)
+    );
+    assert!(registries.registry("minecraft:dimension_type").is_some());
+    // we only have core, so extra is not claimed
+    assert_eq!(registries.known_packs(), [pack("core")]);
+
+    play_until_ended(&mut connection);
+    server.join().unwrap();
+    assert_eq!(connection.state(), ConnectionState::Play);
+    let registries = connection.registries();
+    let biomes = registries.registry("minecraft:worldgen/biome").unwrap();
+    assert_eq!(biomes.len(), 1);
+    assert_eq!(biomes[0].id.as_str(), "minecraft:the_void");
+    assert_eq!(
+        biomes[0]
+            .data
+            .as_ref()
+            .unwrap()
+            .get("height")
+            .unwrap()
+            .as_i64(),
+        Some(384)
+    );
+    assert!(registries.registry("minecraft:dimension_type").is_none());
+    assert_eq!(
+        registries.tags("minecraft:worldgen/biome").unwrap()[0]
+            .entries
+            .len(),
+        1
+    );
+    assert_eq!(registries.known_packs(), [pack("core")]);
+}
+
+#[test]
+fn unknown_packets_in_reentered_configuration_are_skipped() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        server.send(StartConfigurationPacket);
+        server.recv_skipping::<ConfigurationAcknowledgedPacket>();
+        // Server Links with no links, which we do not handle
+        server.send_raw(&[2, 0x10, 0x00]);
+        configure(&mut server, vec![], &["minecraft:the_void"]);
+        server.end_play();
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.login().unwrap();
+    connection.configure().unwrap();
+    play_until_ended(&mut connection);
+    server.join().unwrap();
+    assert_eq!(connection.state(), ConnectionState::Play);
+    let biomes = connection
+        .registries()
+        .registry("minecraft:worldgen/biome")
+        .unwrap();
+    assert_eq!(biomes[0].id.as_str(), "minecraft:the_void");
+}
+
+#[test]
+fn start_configuration_leaves_the_old_world() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        server.send(SynchronizePlayerPositionPacket {
+            teleport_id: VarInt(1),
+            position: Vec3d {
+                x: 8.5,
+                y: 0.0,
+                z: 8.5,
+            },
+            velocity: Vec3d::default(),
+            yaw: 0.0,
+            pitch: 0.0,
+            flags: TeleportFlags(0),
+        });
+        server.send(flat_chunk(0, 0));
+        server.send(StartConfigurationPacket);
+        server.recv_skipping::<ConfigurationAcknowledgedPacket>();
+        configure(&mut server, vec![], &["minecraft:the_void"]);
+        server.end_play();
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.login().unwrap();
+    connection.configure().unwrap();
+    play_until_ended(&mut connection);
+    server.join().unwrap();
+    // the next server sends its own Login and position, nothing of the old one is kept
+    assert!(connection.player().is_none());
+    assert!(connection.world().is_empty());
+    assert!(connection.world().dimension().is_none());
+    assert!(connection.entities().is_empty());
+}
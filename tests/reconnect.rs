@@ -0,0 +1,227 @@
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::FakeServer;
+use minecraft_client::{
+    connection::Disconnected,
+    datatypes::{GameProfile, UUID},
+    nbt::Nbt,
+    packet::{
+        AcknowledgeFinishConfigurationPacket, FinishConfigurationPacket, LoginAcknowledgedPacket,
+        LoginDisconnectPacket, LoginStartPacket, LoginSuccessPacket, PlayDisconnectPacket,
+    },
+    reconnect::{
+        is_temporary_ban, Backoff, ClientEvent, PermanentFailure, PermanentFailureKind,
+        ReconnectingClient,
+    },
+    text::plain_text,
+};
+
+fn backoff() -> Backoff {
+    Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(40),
+        multiplier: 2.0,
+        jitter: 0.0,
+    }
+}
+
+fn client(port: u16) -> (ReconnectingClient, Arc<Mutex<Vec<ClientEvent>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut client = ReconnectingClient::new("127.0.0.1".to_string(), port);
+    client.set_backoff(backoff());
+    let log = events.clone();
+    client.on_event(move |event| log.lock().unwrap().push(event.clone()));
+    (client, events)
+}
+
+fn login_disconnect(reason: &str) -> Box<dyn FnOnce(FakeServer) + Send> {
+    let reason = reason.to_string();
+    Box::new(move |mut server| {
+        server.recv_raw();
+        server.recv::<LoginStartPacket>();
+        server.send(LoginDisconnectPacket {
+            reason: reason.try_into().unwrap(),
+        });
+    })
+}
+
+#[test]
+fn backoff_grows_and_is_capped() {
+    let backoff = backoff();
+    let delays: Vec<_> = (0..5).map(|attempt| backoff.delay(attempt)).collect();
+    assert_eq!(delays[0], Duration::from_millis(10));
+    assert_eq!(delays[2], Duration::from_millis(40));
+    assert_eq!(delays[4], Duration::from_millis(40));
+
+    let jittery = Backoff {
+        jitter: 0.5,
+        ..Backoff::default()
+    };
+    for _ in 0..100 {
+        let delay = jittery.delay(1);
+        assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(3));
+    }
+}
+
+#[test]
+fn permanent_failures_are_recognised() {
+    let banned = Nbt::Compound(vec![
+        (
+            "translate".to_string(),
+            Nbt::String("multiplayer.disconnect.banned.reason".to_string()),
+        ),
+        (
+            "with".to_string(),
+            Nbt::List(vec![Nbt::String("griefing".to_string())]),
+        ),
+    ]);
+    let text = plain_text(&banned);
+    assert_eq!(text, "multiplayer.disconnect.banned.reason [griefing]");
+    assert_eq!(
+        PermanentFailureKind::from_reason(&text),
+        Some(PermanentFailureKind::Banned)
+    );
+    assert_eq!(
+        PermanentFailureKind::from_reason(r#"{"text":"You are not whitelisted on this server!"}"#),
+        Some(PermanentFailureKind::NotWhitelisted)
+    );
+    assert_eq!(
+        PermanentFailureKind::from_reason(
+            r#"{"translate":"multiplayer.disconnect.outdated_client","with":["1.21.10"]}"#
+        ),
+        Some(PermanentFailureKind::OutdatedClient)
+    );
+    assert_eq!(
+        PermanentFailureKind::from_reason(
+            r#"{"translate":"multiplayer.disconnect.incompatible","with":["1.21.10"]}"#
+        ),
+        Some(PermanentFailureKind::Incompatible)
+    );
+    assert_eq!(PermanentFailureKind::from_reason("Server closed"), None);
+
+    // a ban that runs out is worth waiting for
+    let temporary = "multiplayer.disconnect.banned.reason [griefing]\
+        multiplayer.disconnect.banned.expiration [2026-10-19]";
+    assert_eq!(PermanentFailureKind::from_reason(temporary), None);
+    assert!(is_temporary_ban(temporary));
+    assert!(!is_temporary_ban(&text));
+}
+
+#[test]
+fn reconnects_until_banned() {
+    let (port, server) = FakeServer::spawn_each(vec![
+        // dropped straight away
+        Box::new(|_| {}),
+        login_disconnect(r#"{"text":"Server is restarting"}"#),
+        // gets into play before being kicked, which resets the backoff
+        Box::new(|mut server| {
+            server.recv_raw();
+            let start = server.recv::<LoginStartPacket>();
+            server.send(LoginSuccessPacket {
+                client: GameProfile {
+                    uuid: UUID(1),
+                    username: start.name,
+                    properties: vec![],
+                },
+            });
+            server.recv::<LoginAcknowledgedPacket>();
+            server.send(FinishConfigurationPacket);
+            server.recv_skipping::<AcknowledgeFinishConfigurationPacket>();
+            server.send(PlayDisconnectPacket {
+                reason: Nbt::String("Kicked for spamming".to_string()),
+            });
+        }),
+        login_disconnect(r#"{"translate":"multiplayer.disconnect.banned"}"#),
+    ]);
+
+    let (mut client, events) = client(port);
+    let err = client.run().unwrap_err();
+    let failure = err.downcast_ref::<PermanentFailure>().unwrap();
+    assert_eq!(failure.kind, PermanentFailureKind::Banned);
+    server.join().unwrap();
+
+    let events = events.lock().unwrap();
+    let attempts: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            ClientEvent::Connecting { attempt } => Some(*attempt),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(attempts, [0, 1, 2, 0]);
+    let delays: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            ClientEvent::Reconnecting { delay } => Some(delay.as_millis()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(delays, [10, 20, 10]);
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| **event == ClientEvent::Connected)
+            .count(),
+        1
+    );
+    assert!(events.contains(&ClientEvent::Disconnected {
+        reason: Disconnected {
+            reason: "Kicked for spamming".to_string()
+        }
+        .to_string()
+    }));
+    assert!(matches!(events.last(), Some(ClientEvent::GaveUp { .. })));
+}
+
+#[test]
+fn temporary_bans_wait_longer() {
+    let (port, server) = FakeServer::spawn_each(vec![
+        login_disconnect(
+            r#"{"translate":"multiplayer.disconnect.banned.reason","with":["spam"],"extra":[{"translate":"multiplayer.disconnect.banned.expiration","with":["tomorrow"]}]}"#,
+        ),
+        login_disconnect(r#"{"text":"Server is restarting"}"#),
+    ]);
+
+    let (mut client, events) = client(port);
+    client.set_ban_backoff(Backoff {
+        initial: Duration::from_millis(100),
+        max: Duration::from_millis(400),
+        ..backoff()
+    });
+    client.set_max_attempts(Some(2));
+    client.run().unwrap_err();
+    server.join().unwrap();
+
+    let delays: Vec<_> = events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|event| match event {
+            ClientEvent::Reconnecting { delay } => Some(delay.as_millis()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(delays, [100]);
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let (port, server) =
+        FakeServer::spawn_each(vec![Box::new(|_| {}), Box::new(|_| {}), Box::new(|_| {})]);
+    let (mut client, events) = client(port);
+    client.set_max_attempts(Some(3));
+    assert!(client.run().is_err());
+    server.join().unwrap();
+    let connects = events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|event| matches!(event, ClientEvent::Connecting { .. }))
+        .count();
+    assert_eq!(connects, 3);
+}
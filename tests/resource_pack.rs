@@ -0,0 +1,157 @@
+mod common;
+
+use anyhow::anyhow;
+use common::{play_until_ended, FakeServer};
+use minecraft_client::{
+    connection::Connection,
+    datatypes::{GameProfile, UUID},
+    packet::{
+        AcknowledgeFinishConfigurationPacket, ConfigurationAddResourcePackPacket,
+        ConfigurationRemoveResourcePackPacket, ConfigurationResourcePackResponsePacket,
+        FinishConfigurationPacket, LoginAcknowledgedPacket, LoginStartPacket, LoginSuccessPacket,
+        PlayAddResourcePackPacket, PlayResourcePackResponsePacket, ResourcePackPush,
+        ResourcePackStatus,
+    },
+    resource_pack::{ResourcePackDecision, ResourcePackFailure, ResourcePackPolicy, ResourcePacks},
+};
+
+use ResourcePackStatus::*;
+
+const ABC_SHA1: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+
+fn push(uuid: u128, url: &str, hash: &str) -> ResourcePackPush {
+    ResourcePackPush {
+        uuid: UUID(uuid),
+        url: url.try_into().unwrap(),
+        hash: hash.try_into().unwrap(),
+        forced: true,
+        prompt: None,
+    }
+}
+
+#[test]
+fn accept_without_fetcher_reports_loaded() {
+    let mut packs = ResourcePacks::new();
+    assert_eq!(
+        packs.push(&push(1, "https://example.com/a.zip", ABC_SHA1)),
+        [Accepted, Downloaded, SuccessfullyLoaded]
+    );
+    assert_eq!(packs.loaded().len(), 1);
+    assert!(packs.loaded()[0].data.is_none());
+    assert_eq!(
+        packs.push(&push(2, "ftp://example.com/a.zip", "")),
+        [InvalidUrl]
+    );
+    packs.pop(Some(UUID(1)));
+    assert!(packs.loaded().is_empty());
+}
+
+#[test]
+fn fetched_packs_are_verified() {
+    let mut packs = ResourcePacks::new();
+    packs.set_fetcher(|url: &str| match url {
+        "https://example.com/abc.zip" => Ok(b"abc".to_vec()),
+        _ => Err(anyhow!("404")),
+    });
+    assert_eq!(
+        packs.push(&push(
+            1,
+            "https://example.com/abc.zip",
+            &ABC_SHA1.to_uppercase()
+        )),
+        [Accepted, Downloaded, SuccessfullyLoaded]
+    );
+    assert_eq!(packs.loaded()[0].data.as_deref(), Some(&b"abc"[..]));
+    assert_eq!(
+        packs.push(&push(2, "https://example.com/abc.zip", &"0".repeat(40))),
+        [Accepted, FailedDownload]
+    );
+    assert_eq!(
+        packs.push(&push(3, "https://example.com/missing.zip", "")),
+        [Accepted, FailedDownload]
+    );
+    assert_eq!(packs.loaded().len(), 1);
+
+    // why each one failed is kept for the caller
+    let failed = packs.failed();
+    assert_eq!(failed.len(), 2);
+    assert_eq!(failed[0].uuid, UUID(2));
+    match &failed[0].reason {
+        ResourcePackFailure::HashMismatch { expected, actual } => {
+            assert_eq!(expected, &"0".repeat(40));
+            assert_eq!(actual, ABC_SHA1);
+        }
+        other => panic!("{other:?}"),
+    }
+    assert_eq!(failed[1].url, "https://example.com/missing.zip");
+    assert_eq!(failed[1].reason.to_string(), "download failed: 404");
+
+    // and forgotten once the pack is removed or loads
+    packs.pop(Some(UUID(3)));
+    assert_eq!(packs.failed().len(), 1);
+    packs.push(&push(2, "https://example.com/abc.zip", ABC_SHA1));
+    assert!(packs.failed().is_empty());
+}
+
+#[test]
+fn decline_and_callback_policies() {
+    let mut packs = ResourcePacks::new();
+    packs.set_policy(ResourcePackPolicy::Decline);
+    assert_eq!(packs.push(&push(1, "https://a.b/c", "")), [Declined]);
+    packs.set_policy(ResourcePackPolicy::callback(|pack| {
+        if pack.forced {
+            ResourcePackDecision::Accept
+        } else {
+            ResourcePackDecision::Decline
+        }
+    }));
+    assert_eq!(packs.push(&push(1, "https://a.b/c", ""))[0], Accepted);
+    let mut optional = push(2, "https://a.b/d", "");
+    optional.forced = false;
+    assert_eq!(packs.push(&optional), [Declined]);
+}
+
+#[test]
+fn responses_are_sent_in_configuration_and_play() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.recv_raw();
+        let start = server.recv::<LoginStartPacket>();
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: start.name,
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+        server.send(ConfigurationAddResourcePackPacket {
+            pack: push(7, "https://example.com/a.zip", ""),
+        });
+        for status in [Accepted, Downloaded, SuccessfullyLoaded] {
+            let resp = server.recv_skipping::<ConfigurationResourcePackResponsePacket>();
+            assert_eq!(resp.uuid, UUID(7));
+            assert_eq!(resp.result, status);
+        }
+        server.send(ConfigurationRemoveResourcePackPacket { uuid: None });
+        server.send(FinishConfigurationPacket);
+        server.recv::<AcknowledgeFinishConfigurationPacket>();
+
+        server.send(PlayAddResourcePackPacket {
+            pack: push(8, "https://example.com/b.zip", ""),
+        });
+        for status in [Accepted, Downloaded, SuccessfullyLoaded] {
+            let resp = server.recv::<PlayResourcePackResponsePacket>();
+            assert_eq!(resp.uuid, UUID(8));
+            assert_eq!(resp.result, status);
+        }
+        server.end_play();
+    });
+
+    let mut connection = Connection::connect("127.0.0.1".to_string(), port).unwrap();
+    connection.login().unwrap();
+    connection.configure().unwrap();
+    assert!(connection.resource_packs().loaded().is_empty());
+    play_until_ended(&mut connection);
+    server.join().unwrap();
+    assert_eq!(connection.resource_packs().loaded()[0].uuid, UUID(8));
+}
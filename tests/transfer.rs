@@ -0,0 +1,130 @@
+mod common;
+
+use common::{flat_chunk, play_until_ended, FakeServer};
+use minecraft_client::{
+    connection::Connection,
+    cookie::Cookie,
+    datatypes::{GameProfile, VarInt, Vec3d, UUID},
+    packet::{
+        AcknowledgeFinishConfigurationPacket, ConfigurationClientInformationPacket,
+        ConfigurationStoreCookiePacket, ConfigurationTransferPacket, FinishConfigurationPacket,
+        HandshakeIntent, HandshakePacket, LoginAcknowledgedPacket, LoginCookieRequestPacket,
+        LoginCookieResponsePacket, LoginStartPacket, LoginSuccessPacket, PlayTransferPacket,
+        SynchronizePlayerPositionPacket, TeleportFlags, TransferTarget,
+    },
+};
+
+fn login_success(server: &mut FakeServer) {
+    let start = server.recv::<LoginStartPacket>();
+    server.send(LoginSuccessPacket {
+        client: GameProfile {
+            uuid: UUID(1),
+            username: start.name,
+            properties: vec![],
+        },
+    });
+    server.recv::<LoginAcknowledgedPacket>();
+}
+
+#[test]
+fn transfer_during_configuration_replays_cookies() {
+    let (game_port, game) = FakeServer::spawn(|mut server| {
+        let handshake = server.recv::<HandshakePacket>();
+        assert!(matches!(handshake.intent, HandshakeIntent::Transfer));
+        server.recv::<LoginStartPacket>();
+        server.send(LoginCookieRequestPacket {
+            key: "lobby:ticket".try_into().unwrap(),
+        });
+        let resp = server.recv::<LoginCookieResponsePacket>();
+        assert_eq!(resp.key.as_str(), "lobby:ticket");
+        assert_eq!(resp.payload.as_deref(), Some(&b"abc"[..]));
+        server.send(LoginCookieRequestPacket {
+            key: "lobby:missing".try_into().unwrap(),
+        });
+        let resp = server.recv::<LoginCookieResponsePacket>();
+        assert!(resp.payload.is_none());
+        server.send(LoginSuccessPacket {
+            client: GameProfile {
+                uuid: UUID(1),
+                username: "robot".try_into().unwrap(),
+                properties: vec![],
+            },
+        });
+        server.recv::<LoginAcknowledgedPacket>();
+        server.send(FinishConfigurationPacket);
+        server.recv_skipping::<AcknowledgeFinishConfigurationPacket>();
+    });
+
+    let (lobby_port, lobby) = FakeServer::spawn(move |mut server| {
+        let handshake = server.recv::<HandshakePacket>();
+        assert!(matches!(handshake.intent, HandshakeIntent::Login));
+        login_success(&mut server);
+        server.recv_skipping::<ConfigurationClientInformationPacket>();
+        server.send(ConfigurationStoreCookiePacket {
+            cookie: Cookie {
+                key: "lobby:ticket".try_into().unwrap(),
+                payload: b"abc".to_vec(),
+            },
+        });
+        server.send(ConfigurationTransferPacket {
+            target: TransferTarget {
+                host: "127.0.0.1".try_into().unwrap(),
+                port: VarInt(game_port as i32),
+            },
+        });
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), lobby_port).unwrap();
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    lobby.join().unwrap();
+    game.join().unwrap();
+}
+
+#[test]
+fn transfer_during_play_leaves_the_old_world() {
+    // the lobby stays up until we have moved on, since we keep talking to it until then
+    let (transferred, moved_on) = std::sync::mpsc::channel();
+    let (game_port, game) = FakeServer::spawn(move |mut server| {
+        server.recv::<HandshakePacket>();
+        transferred.send(()).unwrap();
+        login_success(&mut server);
+        server.send(FinishConfigurationPacket);
+        server.recv_skipping::<AcknowledgeFinishConfigurationPacket>();
+        server.end_play();
+    });
+
+    let (lobby_port, lobby) = FakeServer::spawn(move |mut server| {
+        server.join_game();
+        server.send(SynchronizePlayerPositionPacket {
+            teleport_id: VarInt(1),
+            position: Vec3d {
+                x: 8.5,
+                y: 0.0,
+                z: 8.5,
+            },
+            velocity: Vec3d::default(),
+            yaw: 0.0,
+            pitch: 0.0,
+            flags: TeleportFlags(0),
+        });
+        server.send(flat_chunk(0, 0));
+        server.send(PlayTransferPacket {
+            target: TransferTarget {
+                host: "127.0.0.1".try_into().unwrap(),
+                port: VarInt(game_port as i32),
+            },
+        });
+        moved_on.recv().unwrap();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), lobby_port).unwrap();
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    play_until_ended(&mut conn);
+    lobby.join().unwrap();
+    game.join().unwrap();
+    assert!(conn.player().is_none());
+    assert!(conn.world().is_empty());
+    assert!(conn.entities().is_empty());
+}
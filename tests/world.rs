@@ -0,0 +1,243 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{play_until_ended, FakeServer};
+use minecraft_client::{
+    chunk::{
+        ChunkSection, Dimension, LightArray, LightData, Palette, PaletteStrategy, PalettedContainer,
+    },
+    connection::Connection,
+    datatypes::{BitSet, Position, VarInt, VarLong},
+    packet::{
+        BlockUpdatePacket, ChunkBatchFinishedPacket, ChunkBatchReceivedPacket,
+        ChunkBatchStartPacket, ChunkDataAndUpdateLightPacket, SectionBlocksUpdatePacket,
+        UnloadChunkPacket, UpdateLightPacket,
+    },
+    world::{chunk_of, ChunkBatchRate, World},
+};
+
+const DIMENSION: Dimension = Dimension {
+    min_y: -64,
+    height: 384,
+    biome_count: 4,
+};
+
+const STONE: i32 = 1;
+
+fn chunk_packet(x: i32, z: i32) -> ChunkDataAndUpdateLightPacket {
+    let mut data = Vec::new();
+    for i in 0..DIMENSION.section_count() {
+        // stone below y 0, air above
+        let block = if i < 4 { STONE } else { 0 };
+        ChunkSection {
+            block_count: if block == 0 { 0 } else { 4096 },
+            block_states: PalettedContainer::single(PaletteStrategy::BLOCK_STATES, block),
+            biomes: PalettedContainer::single(DIMENSION.biome_strategy(), 0),
+        }
+        .encode(&mut data)
+        .unwrap();
+    }
+    ChunkDataAndUpdateLightPacket {
+        chunk_x: x,
+        chunk_z: z,
+        heightmaps: vec![],
+        data,
+        block_entities: vec![],
+        light: LightData::default(),
+    }
+}
+
+fn section_position(x: i32, y: i32, z: i32) -> i64 {
+    ((x as i64 & 0x3FFFFF) << 42) | ((z as i64 & 0x3FFFFF) << 20) | (y as i64 & 0xFFFFF)
+}
+
+#[test]
+fn blocks_by_position() {
+    let mut world = World::new();
+    assert!(world.load(chunk_packet(0, 0)).is_err());
+    world.set_dimension(DIMENSION);
+    world.load(chunk_packet(0, 0)).unwrap();
+    world.load(chunk_packet(-1, -1)).unwrap();
+    assert_eq!(world.len(), 2);
+
+    assert_eq!(world.block_at(Position::new(5, -1, 5)), Some(STONE));
+    assert_eq!(world.block_at(Position::new(5, 0, 5)), Some(0));
+    assert_eq!(world.block_at(Position::new(-1, -64, -16)), Some(STONE));
+    assert_eq!(world.block_at(Position::new(-1, -65, -16)), None);
+    assert_eq!(world.block_at(Position::new(16, 0, 0)), None);
+    assert_eq!(chunk_of(Position::new(-17, 0, 15)), (-2, 0));
+
+    assert!(world.set_block(Position::new(-3, 70, -7), 42));
+    assert_eq!(world.block_at(Position::new(-3, 70, -7)), Some(42));
+    assert_eq!(world.block_at(Position::new(-3, 71, -7)), Some(0));
+    assert!(!world.set_block(Position::new(100, 70, 0), 42));
+    assert!(!world.set_block(Position::new(0, 320, 0), 42));
+
+    let mut coords: Vec<_> = world.chunks().map(|chunk| (chunk.x, chunk.z)).collect();
+    coords.sort();
+    assert_eq!(coords, [(-1, -1), (0, 0)]);
+    assert!(world.unload(0, 0).is_some());
+    assert_eq!(world.block_at(Position::new(5, -1, 5)), None);
+
+    world.set_dimension(DIMENSION);
+    assert!(world.is_empty());
+}
+
+#[test]
+fn palettes_grow_as_blocks_are_set() {
+    let mut container = PalettedContainer::single(PaletteStrategy::BLOCK_STATES, 0);
+    container.set(0, 0);
+    assert_eq!(container.palette(), &Palette::Single(0));
+    for i in 0..300 {
+        container.set(i, i as i32 + 1);
+        match i {
+            0..15 => assert_eq!(container.bits(), 4),
+            15..255 => assert!(matches!(container.palette(), Palette::Indirect(_))),
+            _ => assert_eq!(container.palette(), &Palette::Direct),
+        }
+    }
+    for i in 0..4096 {
+        let expected = if i < 300 { i as i32 + 1 } else { 0 };
+        assert_eq!(container.get(i), expected);
+    }
+}
+
+#[test]
+fn section_updates() {
+    let mut world = World::new();
+    world.set_dimension(DIMENSION);
+    world.load(chunk_packet(-1, 2)).unwrap();
+    // section -1, 3, 2 covers y 48 to 63
+    let packet = SectionBlocksUpdatePacket {
+        section_position: section_position(-1, 3, 2),
+        blocks: vec![
+            VarLong((7 << 12) | (1 << 8) | (2 << 4) | 3),
+            VarLong((8 << 12) | (15 << 8) | (15 << 4) | 15),
+        ],
+    };
+    assert_eq!(packet.section(), (-1, 3, 2));
+    assert_eq!(world.apply_section_update(&packet), 2);
+    assert_eq!(world.block_at(Position::new(-15, 51, 34)), Some(7));
+    assert_eq!(world.block_at(Position::new(-1, 63, 47)), Some(8));
+}
+
+#[test]
+fn light_updates_replace_and_clear_sections() {
+    let array = |level: u8| LightArray(vec![level * 0x11; LightArray::LEN]);
+    let mask = |bits: &[usize]| {
+        let mut mask = BitSet::default();
+        for &bit in bits {
+            mask.set(bit, true);
+        }
+        mask
+    };
+    let mut light = LightData {
+        sky_light_mask: mask(&[1, 2, 3]),
+        sky_light: vec![array(1), array(2), array(3)],
+        ..LightData::default()
+    };
+    light.update(LightData {
+        sky_light_mask: mask(&[2, 5]),
+        empty_sky_light_mask: mask(&[3]),
+        sky_light: vec![array(9), array(5)],
+        ..LightData::default()
+    });
+    assert_eq!(light.sky_light(1), Some(&array(1)));
+    assert_eq!(light.sky_light(2), Some(&array(9)));
+    assert_eq!(light.sky_light(3), None);
+    assert!(light.empty_sky_light_mask.get(3));
+    assert_eq!(light.sky_light(5), Some(&array(5)));
+    assert_eq!(light.block_light(1), None);
+}
+
+#[test]
+fn play_loop_keeps_the_world() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        server.send(ChunkBatchStartPacket);
+        server.send(chunk_packet(0, 0));
+        server.send(chunk_packet(1, 0));
+        server.send(chunk_packet(2, 0));
+        server.send(ChunkBatchFinishedPacket {
+            batch_size: VarInt(3),
+        });
+        let ack = server.recv::<ChunkBatchReceivedPacket>();
+        assert!(ack.chunks_per_tick > 0.0);
+        server.send(BlockUpdatePacket {
+            location: Position::new(3, 64, 4),
+            block_state: VarInt(5),
+        });
+        server.send(SectionBlocksUpdatePacket {
+            section_position: section_position(1, 0, 0),
+            blocks: vec![VarLong(6 << 12)],
+        });
+        server.send(UpdateLightPacket {
+            chunk_x: VarInt(0),
+            chunk_z: VarInt(0),
+            light: LightData::default(),
+        });
+        server.send(UnloadChunkPacket {
+            chunk_z: 0,
+            chunk_x: 2,
+        });
+        server.end_play();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    play_until_ended(&mut conn);
+    server.join().unwrap();
+
+    let world = conn.world();
+    assert_eq!(world.len(), 2);
+    assert!(world.chunk(2, 0).is_none());
+    assert_eq!(world.block_at(Position::new(3, 64, 4)), Some(5));
+    assert_eq!(world.block_at(Position::new(16, 0, 0)), Some(6));
+    assert_eq!(world.block_at(Position::new(0, -1, 0)), Some(STONE));
+}
+
+#[test]
+fn chunk_rate_follows_batch_times() {
+    let mut rate = ChunkBatchRate::new();
+    // 2ms a chunk to start with and 7ms a tick to spend
+    assert_eq!(rate.desired_chunks_per_tick(), 3.5);
+
+    // a slow batch can only triple the estimate, and is averaged with the initial guess
+    rate.record(Duration::from_millis(100), 1);
+    assert_eq!(rate.time_per_chunk(), Duration::from_millis(4));
+    for _ in 0..200 {
+        rate.record(Duration::from_millis(10), 10);
+    }
+    assert!((rate.desired_chunks_per_tick() - 7.0).abs() < 0.1);
+    rate.record(Duration::ZERO, 0);
+    assert!((rate.desired_chunks_per_tick() - 7.0).abs() < 0.1);
+
+    rate.max_chunks_per_tick = Some(0.5);
+    assert_eq!(rate.desired_chunks_per_tick(), 0.5);
+}
+
+#[test]
+fn slow_crawlers_ask_for_fewer_chunks() {
+    let (port, server) = FakeServer::spawn(|mut server| {
+        server.join_game();
+        for _ in 0..2 {
+            server.send(ChunkBatchStartPacket);
+            server.send(chunk_packet(0, 0));
+            server.send(ChunkBatchFinishedPacket {
+                batch_size: VarInt(1),
+            });
+            let ack = server.recv::<ChunkBatchReceivedPacket>();
+            assert_eq!(ack.chunks_per_tick, 0.25);
+        }
+        server.end_play();
+    });
+
+    let mut conn = Connection::connect("127.0.0.1".to_owned(), port).unwrap();
+    conn.world_mut().chunk_rate_mut().max_chunks_per_tick = Some(0.25);
+    conn.login().unwrap();
+    conn.configure().unwrap();
+    play_until_ended(&mut conn);
+    server.join().unwrap();
+}